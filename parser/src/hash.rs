@@ -0,0 +1,537 @@
+use crate::types::{
+    Annotation, ClassField, DataKeyword, EnumVariant, Expression, ExpressionType, FunctionHeader, Literal, Statement,
+    StatementType, TokenType, Type,
+};
+
+/// 64-bit FNV-1a.
+struct StableHasher(u64);
+
+impl StableHasher {
+    fn new() -> StableHasher {
+        StableHasher(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn tag(&mut self, tag: u8) {
+        self.write(&[tag]);
+    }
+
+    fn length(&mut self, length: usize) {
+        self.write(&(length as u64).to_le_bytes());
+    }
+}
+
+/// Writes a value into a `StableHasher`. Every variant writes its own tag and every string or
+/// list its length first, so the bytes only depend on this file and not on the `Hash`
+/// implementations of the standard library, which can change between toolchains.
+trait StableHash {
+    fn stable_hash(&self, hasher: &mut StableHasher);
+}
+
+impl StableHash for str {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.length(self.len());
+        hasher.write(self.as_bytes());
+    }
+}
+
+impl StableHash for bool {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.tag(*self as u8);
+    }
+}
+
+impl StableHash for usize {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.length(*self);
+    }
+}
+
+impl<T: StableHash + ?Sized> StableHash for &T {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        (**self).stable_hash(hasher);
+    }
+}
+
+impl<T: StableHash + ?Sized> StableHash for Box<T> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        (**self).stable_hash(hasher);
+    }
+}
+
+impl<T: StableHash> StableHash for [T] {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.length(self.len());
+        for element in self {
+            element.stable_hash(hasher);
+        }
+    }
+}
+
+impl<T: StableHash> StableHash for Vec<T> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.as_slice().stable_hash(hasher);
+    }
+}
+
+impl<T: StableHash> StableHash for Option<T> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        match self {
+            None => hasher.tag(0),
+            Some(value) => {
+                hasher.tag(1);
+                value.stable_hash(hasher);
+            }
+        }
+    }
+}
+
+impl<A: StableHash, B: StableHash> StableHash for (A, B) {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.0.stable_hash(hasher);
+        self.1.stable_hash(hasher);
+    }
+}
+
+impl StableHash for DataKeyword {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.tag(match self {
+            DataKeyword::True => 0,
+            DataKeyword::False => 1,
+            DataKeyword::Nil => 2,
+        });
+    }
+}
+
+impl<'a> StableHash for Literal<'a> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        match self {
+            Literal::QuotedString(value) => {
+                hasher.tag(0);
+                value.stable_hash(hasher);
+            }
+            Literal::Keyword(keyword) => {
+                hasher.tag(1);
+                keyword.stable_hash(hasher);
+            }
+            Literal::Float(value) => {
+                hasher.tag(2);
+                hasher.write(&value.to_bits().to_le_bytes());
+            }
+            Literal::Integer(value) => {
+                hasher.tag(3);
+                hasher.write(&value.to_le_bytes());
+            }
+        }
+    }
+}
+
+impl<'a> StableHash for TokenType<'a> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        let tag = match self {
+            TokenType::LeftParen => 0,
+            TokenType::RightParen => 1,
+            TokenType::LeftBrace => 2,
+            TokenType::RightBrace => 3,
+            TokenType::LeftSquareBrace => 4,
+            TokenType::RightSquareBrace => 5,
+            TokenType::DoubleColon => 6,
+            TokenType::Colon => 7,
+            TokenType::Comma => 8,
+            TokenType::Dot => 9,
+            TokenType::Minus => 10,
+            TokenType::Plus => 11,
+            TokenType::Semicolon => 12,
+            TokenType::Slash => 13,
+            TokenType::Star => 14,
+            TokenType::Percent => 15,
+            TokenType::Bang => 16,
+            TokenType::BangEqual => 17,
+            TokenType::Equal => 18,
+            TokenType::EqualEqual => 19,
+            TokenType::Greater => 20,
+            TokenType::GreaterEqual => 21,
+            TokenType::Less => 22,
+            TokenType::LessEqual => 23,
+            TokenType::And => 24,
+            TokenType::Class => 25,
+            TokenType::Else => 26,
+            TokenType::Fun => 27,
+            TokenType::For => 28,
+            TokenType::Break => 29,
+            TokenType::If => 30,
+            TokenType::Or => 31,
+            TokenType::Print => 32,
+            TokenType::Question => 33,
+            TokenType::QuestionDot => 34,
+            TokenType::DoubleQuestion => 35,
+            TokenType::Return => 36,
+            TokenType::Var => 37,
+            TokenType::While => 38,
+            TokenType::Comment => 39,
+            TokenType::EOF => 40,
+            TokenType::Setter => 41,
+            TokenType::Getter => 42,
+            TokenType::Trait => 43,
+            TokenType::Import => 44,
+            TokenType::Arrow => 45,
+            TokenType::Match => 46,
+            TokenType::IsType => 47,
+            TokenType::UppercaseNil => 48,
+            TokenType::Boolean => 49,
+            TokenType::Integer => 50,
+            TokenType::Float => 51,
+            TokenType::String => 52,
+            TokenType::Function => 53,
+            TokenType::UppercaseClass => 54,
+            TokenType::UppercaseTrait => 55,
+            TokenType::Array => 56,
+            TokenType::Module => 57,
+            TokenType::Mod => 58,
+            TokenType::Enum => 59,
+            TokenType::Final => 60,
+            TokenType::In => 61,
+            TokenType::At => 62,
+            TokenType::Bar => 63,
+            TokenType::Identifier { name } => {
+                hasher.tag(64);
+                return name.stable_hash(hasher);
+            }
+            TokenType::TokenLiteral { value } => {
+                hasher.tag(65);
+                return value.stable_hash(hasher);
+            }
+        };
+        hasher.tag(tag);
+    }
+}
+
+impl<'a> StableHash for Type<'a> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        let tag = match self {
+            Type::Nil => 0,
+            Type::Boolean => 1,
+            Type::Integer => 2,
+            Type::Float => 3,
+            Type::String => 4,
+            Type::Function => 5,
+            Type::Class => 6,
+            Type::Trait => 7,
+            Type::Array => 8,
+            Type::Module => 9,
+            Type::UserDefined(expression) => {
+                hasher.tag(10);
+                return expression.stable_hash(hasher);
+            }
+        };
+        hasher.tag(tag);
+    }
+}
+
+impl<'a> StableHash for FunctionHeader<'a> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.name.stable_hash(hasher);
+        self.arity.stable_hash(hasher);
+    }
+}
+
+impl<'a> StableHash for EnumVariant<'a> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.name.stable_hash(hasher);
+        self.fields.stable_hash(hasher);
+    }
+}
+
+impl<'a> StableHash for Annotation<'a> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.key.stable_hash(hasher);
+        self.value.stable_hash(hasher);
+    }
+}
+
+impl<'a> StableHash for ClassField<'a> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.name.stable_hash(hasher);
+        self.field_type.stable_hash(hasher);
+    }
+}
+
+/// Only the structure is hashed, not the location or the id.
+impl<'a> StableHash for Expression<'a> {
+    fn stable_hash(&self, h: &mut StableHasher) {
+        match &self.expression_type {
+            ExpressionType::Conditional { condition, then_branch, else_branch } => {
+                h.tag(0);
+                condition.stable_hash(h);
+                then_branch.stable_hash(h);
+                else_branch.stable_hash(h);
+            }
+            ExpressionType::Binary { right, operator, left } => {
+                h.tag(1);
+                right.stable_hash(h);
+                operator.stable_hash(h);
+                left.stable_hash(h);
+            }
+            ExpressionType::Call { callee, arguments } => {
+                h.tag(2);
+                callee.stable_hash(h);
+                arguments.stable_hash(h);
+            }
+            ExpressionType::NamedArgument { name, value } => {
+                h.tag(3);
+                name.stable_hash(h);
+                value.stable_hash(h);
+            }
+            ExpressionType::Unary { operator, operand } => {
+                h.tag(4);
+                operator.stable_hash(h);
+                operand.stable_hash(h);
+            }
+            ExpressionType::Grouping { expression } => {
+                h.tag(5);
+                expression.stable_hash(h);
+            }
+            ExpressionType::ExpressionLiteral { value } => {
+                h.tag(6);
+                value.stable_hash(h);
+            }
+            ExpressionType::ModuleLiteral { module, field } => {
+                h.tag(7);
+                module.stable_hash(h);
+                field.stable_hash(h);
+            }
+            ExpressionType::VariableLiteral { identifier } => {
+                h.tag(8);
+                identifier.stable_hash(h);
+            }
+            ExpressionType::VariableAssignment { identifier, expression } => {
+                h.tag(9);
+                identifier.stable_hash(h);
+                expression.stable_hash(h);
+            }
+            ExpressionType::TupleAssignment { targets, value } => {
+                h.tag(10);
+                targets.stable_hash(h);
+                value.stable_hash(h);
+            }
+            ExpressionType::AnonymousFunction { arguments, body } => {
+                h.tag(11);
+                arguments.stable_hash(h);
+                body.stable_hash(h);
+            }
+            ExpressionType::Get { callee, property } => {
+                h.tag(12);
+                callee.stable_hash(h);
+                property.stable_hash(h);
+            }
+            ExpressionType::OptionalGet { callee, property } => {
+                h.tag(13);
+                callee.stable_hash(h);
+                property.stable_hash(h);
+            }
+            ExpressionType::Set { callee, property, value } => {
+                h.tag(14);
+                callee.stable_hash(h);
+                property.stable_hash(h);
+                value.stable_hash(h);
+            }
+            ExpressionType::Array { elements } => {
+                h.tag(15);
+                elements.stable_hash(h);
+            }
+            ExpressionType::Tuple { elements } => {
+                h.tag(16);
+                elements.stable_hash(h);
+            }
+            ExpressionType::RepeatedElementArray { element, length } => {
+                h.tag(17);
+                element.stable_hash(h);
+                length.stable_hash(h);
+            }
+            ExpressionType::Map { entries } => {
+                h.tag(18);
+                entries.stable_hash(h);
+            }
+            ExpressionType::ArrayElement { array, index } => {
+                h.tag(19);
+                array.stable_hash(h);
+                index.stable_hash(h);
+            }
+            ExpressionType::ArrayElementSet { array, index, value } => {
+                h.tag(20);
+                array.stable_hash(h);
+                index.stable_hash(h);
+                value.stable_hash(h);
+            }
+            ExpressionType::IsType { value, checked_type } => {
+                h.tag(21);
+                value.stable_hash(h);
+                checked_type.stable_hash(h);
+            }
+            ExpressionType::UpliftFunctionVariables(name) => {
+                h.tag(22);
+                name.stable_hash(h);
+            }
+            ExpressionType::UpliftClassVariables(name) => {
+                h.tag(23);
+                name.stable_hash(h);
+            }
+        }
+    }
+}
+
+/// Only the structure is hashed, not the location or the id.
+impl<'a> StableHash for Statement<'a> {
+    fn stable_hash(&self, h: &mut StableHasher) {
+        match &self.statement_type {
+            StatementType::Module { name, statements } => {
+                h.tag(0);
+                name.stable_hash(h);
+                statements.stable_hash(h);
+            }
+            StatementType::Expression { expression } => {
+                h.tag(1);
+                expression.stable_hash(h);
+            }
+            StatementType::PrintStatement { expression } => {
+                h.tag(2);
+                expression.stable_hash(h);
+            }
+            StatementType::TraitDeclaration { name, methods, getters, setters, static_methods } => {
+                h.tag(3);
+                name.stable_hash(h);
+                methods.stable_hash(h);
+                getters.stable_hash(h);
+                setters.stable_hash(h);
+                static_methods.stable_hash(h);
+            }
+            StatementType::TraitImplementation {
+                trait_name, class_name, methods, static_methods, getters, setters,
+            } => {
+                h.tag(4);
+                trait_name.stable_hash(h);
+                class_name.stable_hash(h);
+                methods.stable_hash(h);
+                static_methods.stable_hash(h);
+                getters.stable_hash(h);
+                setters.stable_hash(h);
+            }
+            StatementType::ClassDeclaration {
+                name, superclass, fields, is_final, annotations, methods, static_methods, getters, setters,
+            } => {
+                h.tag(5);
+                name.stable_hash(h);
+                superclass.stable_hash(h);
+                fields.stable_hash(h);
+                is_final.stable_hash(h);
+                annotations.stable_hash(h);
+                methods.stable_hash(h);
+                static_methods.stable_hash(h);
+                getters.stable_hash(h);
+                setters.stable_hash(h);
+            }
+            StatementType::EnumDeclaration { name, variants } => {
+                h.tag(6);
+                name.stable_hash(h);
+                variants.stable_hash(h);
+            }
+            StatementType::VariableDeclaration { expression, name } => {
+                h.tag(7);
+                expression.stable_hash(h);
+                name.stable_hash(h);
+            }
+            StatementType::TupleDeclaration { names, expression } => {
+                h.tag(8);
+                names.stable_hash(h);
+                expression.stable_hash(h);
+            }
+            StatementType::FunctionDeclaration {
+                name, arguments, keyword_arguments, body, context_variables, annotations,
+            } => {
+                h.tag(9);
+                name.stable_hash(h);
+                arguments.stable_hash(h);
+                keyword_arguments.stable_hash(h);
+                body.stable_hash(h);
+                context_variables.stable_hash(h);
+                annotations.stable_hash(h);
+            }
+            StatementType::Block { body } => {
+                h.tag(10);
+                body.stable_hash(h);
+            }
+            StatementType::If { condition, then, otherwise } => {
+                h.tag(11);
+                condition.stable_hash(h);
+                then.stable_hash(h);
+                otherwise.stable_hash(h);
+            }
+            StatementType::While { condition, action } => {
+                h.tag(12);
+                condition.stable_hash(h);
+                action.stable_hash(h);
+            }
+            StatementType::ForIn { variable, iterable, body } => {
+                h.tag(13);
+                variable.stable_hash(h);
+                iterable.stable_hash(h);
+                body.stable_hash(h);
+            }
+            StatementType::Return { value } => {
+                h.tag(14);
+                value.stable_hash(h);
+            }
+            StatementType::Import { name } => {
+                h.tag(15);
+                name.stable_hash(h);
+            }
+            StatementType::Break => h.tag(16),
+            StatementType::RebindLoopVariables => h.tag(17),
+            StatementType::EOF => h.tag(18),
+        }
+    }
+}
+
+/// Hash of the structure of a parsed program, for hosts that cache work on unchanged
+/// sources. Locations and ids aren't part of it, so sources that only differ in whitespace
+/// or comments hash the same. It doesn't change between runs, platforms or toolchains.
+pub fn program_hash(statements: &[Statement]) -> u64 {
+    let mut hasher = StableHasher::new();
+    statements.stable_hash(&mut hasher);
+    hasher.0
+}
+
+#[cfg(test)]
+mod test {
+    use crate::hash::program_hash;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn hash(source: &str) -> u64 {
+        let tokens = Lexer::new(source, "test").parse().unwrap();
+        program_hash(&Parser::new(tokens.into_iter().peekable()).parse().unwrap().0)
+    }
+
+    #[test]
+    fn test_program_hash() {
+        let original = hash("fun add(a, b) { return a + b; }\nprint add(1, 2.5);");
+        let reformatted = hash(
+            "// Adds two numbers.\nfun add(a, b) {\n    return a + b;\n}\n\n\nprint add( 1, 2.5 ); // 3.5\n"
+        );
+        let changed = hash("fun add(a, b) { return a - b; }\nprint add(1, 2.5);");
+        let renamed = hash("fun add(a, c) { return a + c; }\nprint add(1, 2.5);");
+
+        assert_eq!(original, reformatted);
+        assert_ne!(original, changed);
+        assert_ne!(original, renamed);
+        assert_ne!(hash("print \"ab\" + \"c\";"), hash("print \"a\" + \"bc\";"));
+        // Pinned, as the value has to stay the same for hashes stored by earlier builds.
+        assert_eq!(hash("print 1;"), 0xd1e9_921f_c35e_f4cc);
+    }
+}
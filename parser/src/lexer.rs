@@ -1,4 +1,4 @@
-use crate::types::{DataKeyword, Literal, ProgramError, SourceCodeLocation, Token, TokenType};
+use crate::types::{DataKeyword, ErrorCode, Literal, ProgramError, SourceCodeLocation, Token, TokenType};
 use std::str::FromStr;
 
 pub struct Lexer<'a> {
@@ -50,6 +50,16 @@ impl<'a> Lexer<'a> {
                 }
                 ('/', _) => Some(self.create_token(TokenType::Slash)),
                 ('*', _) => Some(self.create_token(TokenType::Star)),
+                ('%', _) => Some(self.create_token(TokenType::Percent)),
+                ('@', _) => Some(self.create_token(TokenType::At)),
+                ('?', Some('.')) => {
+                    self.current += 1;
+                    Some(self.create_token(TokenType::QuestionDot))
+                }
+                ('?', Some('?')) => {
+                    self.current += 1;
+                    Some(self.create_token(TokenType::DoubleQuestion))
+                }
                 ('?', _) => Some(self.create_token(TokenType::Question)),
                 ('!', Some('=')) => {
                     self.current += 1;
@@ -129,7 +139,7 @@ impl<'a> Lexer<'a> {
                 }
                 (c, _) if c.is_alphabetic() || c == '_' => {
                     let init = self.current;
-                    self.take_while(|s| s.is_digit(10) || s.is_alphabetic() || c == '_');
+                    self.take_while(|s| s.is_digit(10) || s.is_alphabetic() || s == '_');
                     let string_content = &self.file_content[init..self.current+1];
                     match string_content {
                         "and" => Some(self.create_token(TokenType::And)),
@@ -139,6 +149,7 @@ impl<'a> Lexer<'a> {
                         "for" => Some(self.create_token(TokenType::For)),
                         "break" => Some(self.create_token(TokenType::Break)),
                         "if" => Some(self.create_token(TokenType::If)),
+                        "in" => Some(self.create_token(TokenType::In)),
                         "or" => Some(self.create_token(TokenType::Or)),
                         "print" => Some(self.create_token(TokenType::Print)),
                         "return" => Some(self.create_token(TokenType::Return)),
@@ -151,6 +162,8 @@ impl<'a> Lexer<'a> {
                         "match" => Some(self.create_token(TokenType::Match)),
                         "istype" => Some(self.create_token(TokenType::IsType)),
                         "mod" => Some(self.create_token(TokenType::Mod)),
+                        "enum" => Some(self.create_token(TokenType::Enum)),
+                        "final" => Some(self.create_token(TokenType::Final)),
                         "Nil" => Some(self.create_token(TokenType::UppercaseNil)),
                         "Boolean" => Some(self.create_token(TokenType::Boolean)),
                         "Integer" => Some(self.create_token(TokenType::Integer)),
@@ -183,6 +196,16 @@ impl<'a> Lexer<'a> {
                         )),
                     }
                 }
+                ('\\', Some('\n')) => {
+                    self.current += 1;
+                    self.line += 1;
+                    None
+                }
+                ('\\', Some('\r')) if self.content.get(self.current + 2) == Some(&'\n') => {
+                    self.current += 2;
+                    self.line += 1;
+                    None
+                }
                 ('\n', _) => {
                     self.line += 1;
                     None
@@ -208,6 +231,7 @@ impl<'a> Lexer<'a> {
 
     fn create_error(&self, message: &str) -> ProgramError<'a> {
         ProgramError {
+            code: ErrorCode::SyntaxError,
             location: self.get_current_location(),
             message: message.to_owned(),
         }
@@ -245,14 +269,14 @@ impl<'a> Lexer<'a> {
 #[cfg(test)]
 mod tests {
     use super::Lexer;
-    use crate::types::{DataKeyword, Literal, ProgramError, SourceCodeLocation, Token, TokenType};
+    use crate::types::{DataKeyword, ErrorCode, Literal, ProgramError, SourceCodeLocation, Token, TokenType};
 
     #[test]
     fn test_lexer_with_no_error() {
         let s = "(){}:,.-+;/!*!=;=;==>>=<<=;and;class;else;fun;for;break;if;or;print?return;;".to_owned() +
             "var;while\n// comment\nidentifier\n\"string\"\n123.123\ntrue;false;nil;setter;getter;" +
             "trait[];import;::;123;=>;match;istype;Nil;Boolean;Integer;Float;String;Function;Class;" +
-            "Array;Module;Trait;mod";
+            "Array;Module;Trait;mod;enum";
     let mut lexer = Lexer::new(s.as_str(), "file");
         let expected = Ok(vec![
             Token {
@@ -957,6 +981,20 @@ mod tests {
                     line: 5,
                 },
             },
+            Token {
+                token_type: TokenType::Semicolon,
+                location: SourceCodeLocation {
+                    file: "file",
+                    line: 5,
+                },
+            },
+            Token {
+                token_type: TokenType::Enum,
+                location: SourceCodeLocation {
+                    file: "file",
+                    line: 5,
+                },
+            },
         ]);
         assert_eq!(lexer.parse(), expected);
     }
@@ -966,6 +1004,7 @@ mod tests {
         let text = "var s = \"aasdfsadfsdfsadfasdfsdfaasdf";
         let mut lexer = Lexer::new(text, "file");
         let expected = Err(vec![ProgramError {
+            code: ErrorCode::SyntaxError,
             location: SourceCodeLocation {
                 file: "file",
                 line: 0,
@@ -977,14 +1016,15 @@ mod tests {
 
     #[test]
     fn test_lexer_with_unexpected_character() {
-        let text = "var s = 123;@";
+        let text = "var s = 123;$";
         let mut lexer = Lexer::new(text, "file");
         let expected = Err(vec![ProgramError {
+            code: ErrorCode::SyntaxError,
             location: SourceCodeLocation {
                 file: "file",
                 line: 0,
             },
-            message: "Unexpected character @".to_owned(),
+            message: "Unexpected character $".to_owned(),
         }]);
         assert_eq!(lexer.parse(), expected);
     }
@@ -994,6 +1034,7 @@ mod tests {
         let text = "var s = 123a;";
         let mut lexer = Lexer::new(text, "file");
         let expected = Err(vec![ProgramError {
+            code: ErrorCode::SyntaxError,
             location: SourceCodeLocation {
                 file: "file",
                 line: 0,
@@ -1003,12 +1044,69 @@ mod tests {
         assert_eq!(lexer.parse(), expected);
     }
 
+    #[test]
+    fn test_lexer_with_underscore_identifiers() {
+        let text = "byte_len _private";
+        let mut lexer = Lexer::new(text, "file");
+        let location = SourceCodeLocation {
+            file: "file",
+            line: 0,
+        };
+        let expected = Ok(vec![
+            Token {
+                token_type: TokenType::Identifier { name: "byte_len" },
+                location: location.clone(),
+            },
+            Token {
+                token_type: TokenType::Identifier { name: "_private" },
+                location,
+            },
+        ]);
+        assert_eq!(lexer.parse(), expected);
+    }
+
+    #[test]
+    fn test_lexer_with_line_continuation() {
+        for text in ["1 + \\\n2", "1 + \\\r\n2"].iter() {
+            let mut lexer = Lexer::new(text, "file");
+            let expected = Ok(vec![
+                Token {
+                    token_type: TokenType::TokenLiteral {
+                        value: Literal::Integer(1),
+                    },
+                    location: SourceCodeLocation {
+                        file: "file",
+                        line: 0,
+                    },
+                },
+                Token {
+                    token_type: TokenType::Plus,
+                    location: SourceCodeLocation {
+                        file: "file",
+                        line: 0,
+                    },
+                },
+                Token {
+                    token_type: TokenType::TokenLiteral {
+                        value: Literal::Integer(2),
+                    },
+                    location: SourceCodeLocation {
+                        file: "file",
+                        line: 1,
+                    },
+                },
+            ]);
+            assert_eq!(lexer.parse(), expected);
+        }
+    }
+
     #[test]
     fn test_lexer_with_more_than_one_error() {
-        let text = "var s = 123a;\nvar n = 123;@";
+        let text = "var s = 123a;\nvar n = 123;$";
         let mut lexer = Lexer::new(text, "file");
         let expected = Err(vec![
             ProgramError {
+                code: ErrorCode::SyntaxError,
                 location: SourceCodeLocation {
                     file: "file",
                     line: 0,
@@ -1016,11 +1114,12 @@ mod tests {
                 message: "Couldn\'t parse 123a as integer".to_owned(),
             },
             ProgramError {
+                code: ErrorCode::SyntaxError,
                 location: SourceCodeLocation {
                     file: "file",
                     line: 1,
                 },
-                message: "Unexpected character @".to_owned(),
+                message: "Unexpected character $".to_owned(),
             },
         ]);
         assert_eq!(lexer.parse(), expected);
@@ -1,5 +1,5 @@
-use crate::types::{DataKeyword, Expression, ExpressionFactory, ExpressionType, FunctionHeader, Literal, ProgramError, SourceCodeLocation, Statement, StatementType, Token, TokenType, Type, StatementFactory};
-use std::cell::RefCell;
+use crate::types::{Annotation, ClassField, DataKeyword, ErrorCode, EnumVariant, Expression, ExpressionFactory, ExpressionType, FunctionHeader, Literal, ProgramError, SourceCodeLocation, Statement, StatementType, Token, TokenType, Type, StatementFactory};
+use std::cell::{RefCell, RefMut};
 use std::iter::Peekable;
 
 const INTERNAL_MATCH_VALUE_NAME: &str = "@match_value";
@@ -48,7 +48,7 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
         let mut output_vec = vec![];
         let mut error_vec = vec![];
 
-        while self.content.borrow_mut().peek().is_some() {
+        while self.tokens().peek().is_some() {
             match self.parse_statement() {
                 Ok(s) => output_vec.push(s),
                 Err(e) => error_vec.push(e),
@@ -82,7 +82,19 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
             Some(Token {
                 location,
                 token_type: TokenType::Class,
+            })
+            | Some(Token {
+                location,
+                token_type: TokenType::Final,
             }) => self.parse_class_statement(&location),
+            Some(Token {
+                location,
+                token_type: TokenType::At,
+            }) => self.parse_annotated_statement(&location),
+            Some(Token {
+                location,
+                token_type: TokenType::Enum,
+            }) => self.parse_enum_statement(&location),
             Some(Token {
                 location,
                 token_type: TokenType::If,
@@ -160,12 +172,14 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
                     Ok(self.statement_factory.borrow_mut().new_statement(location, StatementType::Break))
                 } else {
                     Err(ProgramError {
+                        code: ErrorCode::SyntaxError,
                         message: "Break statement can't go here".to_owned(),
                         location,
                     })
                 }
             }
             None => Err(ProgramError {
+                code: ErrorCode::SyntaxError,
                 message: "Unexpected end of file".to_owned(),
                 location: SourceCodeLocation {
                     line: 0,
@@ -187,20 +201,28 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
         }
     }
 
+    /// The remaining tokens, with any comments at the front dropped, as they don't take part
+    /// in the grammar.
+    fn tokens(&self) -> RefMut<'_, Peekable<I>> {
+        let mut content = self.content.borrow_mut();
+        while content.next_if(|t| t.token_type == TokenType::Comment).is_some() {}
+        content
+    }
+
     #[inline]
     fn dry_next(&self) -> Option<Token<'a>> {
-        self.content.borrow_mut().peek().cloned()
+        self.tokens().peek().cloned()
     }
 
     #[inline]
     fn next(&self) -> Option<Token<'a>> {
-        self.content.borrow_mut().next()
+        self.tokens().next()
     }
 
     #[cfg(test)]
     #[inline]
     fn is_empty(&self) -> bool {
-        self.content.borrow_mut().peek().is_none()
+        self.tokens().peek().is_none()
     }
 
     fn parse_method_set<T, C: Fn(&mut Vec<T>, &SourceCodeLocation<'a>, bool) -> Result<(), ProgramError<'a>>>(
@@ -257,6 +279,7 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
             }
         } else {
             Err(ProgramError {
+                code: ErrorCode::SyntaxError,
                 message: "Expected trait name".to_owned(),
                 location: location.clone(),
             })
@@ -277,6 +300,7 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
             self.parse_variable_or_module_access(name, &location)
         } else {
             Err(ProgramError {
+                code: ErrorCode::SyntaxError,
                 location: location.clone(),
                 message: "Expected object name for trait implementation".to_owned(),
             })
@@ -321,7 +345,7 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
             } else {
                 vec!["this"]
             };
-            let (name, extra_arguments) = self.parse_function_header(location)?;
+            let (name, extra_arguments, _) = self.parse_function_header(location)?;
             arguments.extend(&extra_arguments);
             self.consume(
                 TokenType::Semicolon,
@@ -355,7 +379,11 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
         &self,
         location: &SourceCodeLocation<'a>,
     ) -> Result<Statement<'a>, ProgramError<'a>> {
-        self.next();
+        let is_final = self.peek(TokenType::Final);
+        if is_final {
+            self.next();
+        }
+        self.consume(TokenType::Class, "Expected class after final", location)?;
         if let Some(Token {
             token_type: TokenType::Identifier { name },
             location,
@@ -367,6 +395,7 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
                     Some(self.parse_variable_or_module_access(name, &location)?)
                 } else {
                     return Err(ProgramError {
+                        code: ErrorCode::SyntaxError,
                         message: "Expect superclass name.".to_owned(),
                         location: location.clone(),
                     });
@@ -379,6 +408,7 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
                 "Expected '{' before class body",
                 &location,
             )?;
+            let fields = self.parse_class_fields(&location)?;
             let method_set = self.parse_class_methods(&location)?;
             self.consume(
                 TokenType::RightBrace,
@@ -395,16 +425,172 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
                     setters: method_set.setters,
                     static_methods: method_set.static_methods,
                     superclass,
+                    fields,
+                    is_final,
+                    annotations: vec![],
                 },
             ))
         } else {
             Err(ProgramError {
+                code: ErrorCode::SyntaxError,
                 message: "Expected name in class definition".to_owned(),
                 location: location.clone(),
             })
         }
     }
 
+    fn parse_annotated_statement(
+        &self,
+        location: &SourceCodeLocation<'a>,
+    ) -> Result<Statement<'a>, ProgramError<'a>> {
+        let mut annotations = vec![];
+        while self.peek(TokenType::At) {
+            self.next();
+            annotations.push(self.parse_annotation(location)?);
+        }
+        let mut statement = self.parse_statement()?;
+        match &mut statement.statement_type {
+            StatementType::FunctionDeclaration { annotations: target, .. }
+            | StatementType::ClassDeclaration { annotations: target, .. } => {
+                *target = annotations;
+                Ok(statement)
+            }
+            _ => Err(ProgramError {
+                code: ErrorCode::SyntaxError,
+                message: "Only functions and classes can be annotated".to_owned(),
+                location: location.clone(),
+            }),
+        }
+    }
+
+    fn parse_annotation(
+        &self,
+        location: &SourceCodeLocation<'a>,
+    ) -> Result<Annotation<'a>, ProgramError<'a>> {
+        let kind = self.parse_identifier()?;
+        self.consume(TokenType::LeftParen, "Expected '(' after annotation name", location)?;
+        let key = match kind {
+            "doc" => "doc",
+            "attr" => {
+                let key = match self.next().map(|t| t.token_type) {
+                    Some(TokenType::Identifier { name }) => name,
+                    Some(TokenType::TokenLiteral { value: Literal::QuotedString(name) }) => name,
+                    _ => return Err(ProgramError {
+                        code: ErrorCode::SyntaxError,
+                        message: "Expected attribute name".to_owned(),
+                        location: location.clone(),
+                    }),
+                };
+                self.consume(TokenType::Comma, "Expected ',' after attribute name", location)?;
+                key
+            }
+            _ => return Err(ProgramError {
+                code: ErrorCode::SyntaxError,
+                message: format!("Unknown annotation `@{}`", kind),
+                location: location.clone(),
+            }),
+        };
+        let value = match self.next().map(|t| t.token_type) {
+            Some(TokenType::TokenLiteral { value }) => value,
+            _ => return Err(ProgramError {
+                code: ErrorCode::SyntaxError,
+                message: "Expected a literal as annotation value".to_owned(),
+                location: location.clone(),
+            }),
+        };
+        self.consume(TokenType::RightParen, "Expected ')' after annotation", location)?;
+        Ok(Annotation { key, value })
+    }
+
+    fn parse_enum_statement(
+        &self,
+        location: &SourceCodeLocation<'a>,
+    ) -> Result<Statement<'a>, ProgramError<'a>> {
+        self.next();
+        let name = self.parse_identifier()?;
+        self.consume(
+            TokenType::LeftBrace,
+            "Expected '{' before enum body",
+            location,
+        )?;
+        let mut variants: Vec<EnumVariant<'a>> = vec![];
+        while !self.peek(TokenType::RightBrace) {
+            let variant_name = self.parse_identifier()?;
+            if variants.iter().any(|v| v.name == variant_name) {
+                return Err(ProgramError {
+                    code: ErrorCode::SyntaxError,
+                    message: format!("Variant `{}` already declared in enum `{}`", variant_name, name),
+                    location: location.clone(),
+                });
+            }
+            let fields = if self.peek(TokenType::LeftParen) {
+                self.next();
+                let fields = self.parse_parameters(location, Parser::parse_identifier)?;
+                self.consume(
+                    TokenType::RightParen,
+                    "Expected ')' after variant fields",
+                    location,
+                )?;
+                fields
+            } else {
+                vec![]
+            };
+            variants.push(EnumVariant {
+                name: variant_name,
+                fields,
+            });
+            if !self.peek(TokenType::RightBrace) {
+                self.consume(
+                    TokenType::Comma,
+                    "Expected ',' between enum variants",
+                    location,
+                )?;
+            }
+        }
+        self.consume(
+            TokenType::RightBrace,
+            "Expected '}' after enum body",
+            location,
+        )?;
+        Ok(self.statement_factory.borrow_mut().new_statement(
+            location.clone(),
+            StatementType::EnumDeclaration { name, variants },
+        ))
+    }
+
+    /// Field declarations at the top of a class body, like `var age: Integer;`. The type is
+    /// optional.
+    fn parse_class_fields(
+        &self,
+        location: &SourceCodeLocation<'a>,
+    ) -> Result<Vec<ClassField<'a>>, ProgramError<'a>> {
+        let mut fields: Vec<ClassField<'a>> = vec![];
+        while self.peek(TokenType::Var) {
+            self.next();
+            let name = self.parse_identifier()?;
+            if fields.iter().any(|f| f.name == name) {
+                return Err(ProgramError {
+                    code: ErrorCode::SyntaxError,
+                    message: format!("Field `{}` already declared", name),
+                    location: location.clone(),
+                });
+            }
+            let field_type = if self.peek(TokenType::Colon) {
+                self.next();
+                Some(self.parse_checked_type(location)?)
+            } else {
+                None
+            };
+            self.consume(
+                TokenType::Semicolon,
+                "Expected ';' after field declaration",
+                location,
+            )?;
+            fields.push(ClassField { name, field_type });
+        }
+        Ok(fields)
+    }
+
     fn parse_class_methods(
         &self,
         location: &SourceCodeLocation<'a>,
@@ -418,6 +604,7 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
             if let StatementType::FunctionDeclaration { arguments, .. } = &getter.statement_type {
                 if arguments.len() != 1 {
                     return Err(ProgramError {
+                        code: ErrorCode::SyntaxError,
                         message: "Getter function should take no arguments".to_owned(),
                         location: getter.location.clone(),
                     });
@@ -428,6 +615,7 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
             if let StatementType::FunctionDeclaration { arguments, .. } = &setter.statement_type {
                 if arguments.len() != 2 {
                     return Err(ProgramError {
+                        code: ErrorCode::SyntaxError,
                         message: "Setter function should take one argument".to_owned(),
                         location: setter.location.clone(),
                     });
@@ -448,6 +636,7 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
         let (literal_branches, types_branches) = self.parse_match_branches(location)?;
         if !literal_branches.is_empty() && !types_branches.is_empty() {
             return Err(ProgramError {
+                code: ErrorCode::SyntaxError,
                 message: "`match` statement mixes values and types in branches".to_owned(),
                 location: location.clone(),
             })
@@ -478,19 +667,17 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
         } else {
             self.build_if_type_chain(types_branches, match_all)
         };
+        let match_value = self.statement_factory.borrow_mut().new_statement(
+            location.clone(),
+            StatementType::VariableDeclaration {
+                name: INTERNAL_MATCH_VALUE_NAME,
+                expression: Some(value),
+            }
+        );
         Ok(self.statement_factory.borrow_mut().new_statement(
             location.clone(),
             StatementType::Block {
-                body: vec![
-                    Box::new(self.statement_factory.borrow_mut().new_statement(
-                        location.clone(),
-                        StatementType::VariableDeclaration {
-                            name: INTERNAL_MATCH_VALUE_NAME,
-                            expression: Some(value),
-                        }
-                    )),
-                    Box::new(if_elses),
-                ]
+                body: vec![Box::new(match_value), Box::new(if_elses)],
             }
         ))
     }
@@ -595,10 +782,40 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
                     parse_branch!(self, types, location, Type::Trait, next_location);
                 }
                 Some(TokenType::Identifier { name }) => {
-                    let _obj = Box::new(self.parse_variable_or_module_access(name, location)?);
-                    parse_branch!(self, types, location, Type::UserDefined(_obj), next_location);
+                    let mut checked_type = self.parse_variable_or_module_access(name, location)?;
+                    if self.peek(TokenType::Dot) {
+                        checked_type = self.parse_call_property(checked_type)?;
+                    }
+                    let bindings = if self.peek(TokenType::LeftParen) {
+                        self.next();
+                        let bindings = self.parse_parameters(location, Parser::parse_identifier)?;
+                        self.consume(
+                            TokenType::RightParen,
+                            "Expected ')' after variant bindings",
+                            location,
+                        )?;
+                        bindings
+                    } else {
+                        vec![]
+                    };
+                    self.consume(
+                        TokenType::Arrow,
+                        "Expecting `=>` after variant on match!",
+                        &location,
+                    )?;
+                    let branch = self.parse_block_statement(next_location)?;
+                    types.push((
+                        Type::UserDefined(Box::new(checked_type)),
+                        self.bind_variant_fields(bindings, branch),
+                    ));
+                    self.consume(
+                        TokenType::Comma,
+                        "Expecting `,` at the end of branch on match!",
+                        &location,
+                    )?;
                 }
                 _ => return Err(ProgramError {
+                    code: ErrorCode::SyntaxError,
                     message: "All branches should match using literals".to_owned(),
                     location: next_location,
                 }),
@@ -607,6 +824,34 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
         Ok((branches, types))
     }
 
+    /// Declares the bound names from the fields of the matched variant. Destructuring the
+    /// variant fails when it doesn't have as many fields as names.
+    fn bind_variant_fields(&self, bindings: Vec<&'a str>, branch: Statement<'a>) -> Statement<'a> {
+        if bindings.is_empty() {
+            return branch;
+        }
+        let location = branch.location.clone();
+        let variant = self.expression_factory.borrow_mut().new_expression(
+            ExpressionType::VariableLiteral {
+                identifier: INTERNAL_MATCH_VALUE_NAME,
+            },
+            location.clone(),
+        );
+        let fields = self.statement_factory.borrow_mut().new_statement(
+            location.clone(),
+            StatementType::TupleDeclaration {
+                names: bindings,
+                expression: variant,
+            },
+        );
+        self.statement_factory.borrow_mut().new_statement(
+            location,
+            StatementType::Block {
+                body: vec![Box::new(fields), Box::new(branch)],
+            },
+        )
+    }
+
     fn parse_if_statement(&self, location: &SourceCodeLocation<'a>) -> Result<Statement<'a>, ProgramError<'a>> {
         self.next();
         self.consume(
@@ -641,7 +886,7 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
         &self,
         location: SourceCodeLocation<'a>,
     ) -> Result<Statement<'a>, ProgramError<'a>> {
-        self.content.borrow_mut().next();
+        self.tokens().next();
         if self.peek(TokenType::Semicolon) {
             self.consume(TokenType::Semicolon, "Expected semicolon", &location)?;
             Ok(self.statement_factory.borrow_mut().new_statement(
@@ -663,40 +908,86 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
         location: &SourceCodeLocation<'a>,
     ) -> Result<Statement<'a>, ProgramError<'a>> {
         self.next();
+        if self.peek(TokenType::LeftParen) {
+            return self.parse_tuple_declaration(location);
+        }
         if let Some(TokenType::Identifier { name }) = self.next().map(|t| t.token_type) {
-            match self.next() {
-                Some(Token {
-                    token_type: TokenType::Semicolon,
-                    ..
-                }) => Ok(self.statement_factory.borrow_mut().new_statement(
+            self.parse_variable_initializer(name, location)
+        } else {
+            Err(ProgramError {
+                code: ErrorCode::SyntaxError,
+                location: location.clone(),
+                message: "Invalid variable declaration!".to_owned(),
+            })
+        }
+    }
+
+    /// The rest of a variable declaration once its name has been consumed.
+    fn parse_variable_initializer(
+        &self,
+        name: &'a str,
+        location: &SourceCodeLocation<'a>,
+    ) -> Result<Statement<'a>, ProgramError<'a>> {
+        match self.next() {
+            Some(Token {
+                token_type: TokenType::Semicolon,
+                ..
+            }) => Ok(self.statement_factory.borrow_mut().new_statement(
+                location.clone(),
+                StatementType::VariableDeclaration {
+                    name,
+                    expression: None,
+                },
+            )),
+            Some(Token {
+                token_type: TokenType::Equal,
+                ..
+            }) => {
+                let expression = Some(self.parse_expression()?);
+                self.consume(TokenType::Semicolon, "Expected semicolon", location)?;
+                Ok(self.statement_factory.borrow_mut().new_statement(
                     location.clone(),
-                    StatementType::VariableDeclaration {
-                        name,
-                        expression: None,
-                    },
-                )),
-                Some(Token {
-                    token_type: TokenType::Equal,
-                    ..
-                }) => {
-                    let expression = Some(self.parse_expression()?);
-                    self.consume(TokenType::Semicolon, "Expected semicolon", location)?;
-                    Ok(self.statement_factory.borrow_mut().new_statement(
-                        location.clone(),
-                        StatementType::VariableDeclaration { name, expression },
-                    ))
-                }
+                    StatementType::VariableDeclaration { name, expression },
+                ))
+            }
+            _ => Err(ProgramError {
+                code: ErrorCode::SyntaxError,
+                location: location.clone(),
+                message: "Invalid variable declaration!".to_owned(),
+            }),
+        }
+    }
+
+    fn parse_tuple_declaration(
+        &self,
+        location: &SourceCodeLocation<'a>,
+    ) -> Result<Statement<'a>, ProgramError<'a>> {
+        self.consume(TokenType::LeftParen, "Expected `(` on tuple declaration", location)?;
+        let names = self.parse_parameters(location, |parser| {
+            match parser.next().map(|t| t.token_type) {
+                Some(TokenType::Identifier { name }) => Ok(name),
                 _ => Err(ProgramError {
+                    code: ErrorCode::SyntaxError,
                     location: location.clone(),
-                    message: "Invalid variable declaration!".to_owned(),
+                    message: "Expected variable name in tuple declaration!".to_owned(),
                 }),
             }
-        } else {
-            Err(ProgramError {
+        })?;
+        self.consume(TokenType::RightParen, "Expected `)` on tuple declaration", location)?;
+        if names.is_empty() {
+            return Err(ProgramError {
+                code: ErrorCode::SyntaxError,
                 location: location.clone(),
-                message: "Invalid variable declaration!".to_owned(),
-            })
+                message: "Tuple declaration needs at least one variable!".to_owned(),
+            });
         }
+        self.consume(TokenType::Equal, "Expected `=` after tuple declaration", location)?;
+        let expression = self.parse_expression()?;
+        self.consume(TokenType::Semicolon, "Expected semicolon", location)?;
+        Ok(self.statement_factory.borrow_mut().new_statement(
+            location.clone(),
+            StatementType::TupleDeclaration { names, expression },
+        ))
     }
 
     fn parse_block_statement(
@@ -747,14 +1038,14 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
         &self,
         location: &SourceCodeLocation<'a>,
     ) -> Result<Statement<'a>, ProgramError<'a>> {
-        self.content.borrow_mut().next();
+        self.tokens().next();
         self.parse_function(location, false)
     }
 
     fn parse_function_header(
         &self,
         location: &SourceCodeLocation<'a>,
-    ) -> Result<(&'a str, Vec<&'a str>), ProgramError<'a>> {
+    ) -> Result<(&'a str, Vec<&'a str>, Option<&'a str>), ProgramError<'a>> {
         if let Some(Token {
             token_type: TokenType::Identifier { name },
             location,
@@ -765,15 +1056,32 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
                 "Expected a parenthesis after name!",
                 &location,
             )?;
-            let arguments = self.parse_parameters(&location, Parser::parse_identifier)?;
+            let parameters = self.parse_parameters(&location, Parser::parse_function_parameter)?;
             self.consume(
                 TokenType::RightParen,
                 "Expected a parenthesis after parameters!",
                 &location,
             )?;
-            Ok((name, arguments))
+            let mut arguments = vec![];
+            let mut keyword_arguments = None;
+            for (parameter, collects_keywords) in parameters {
+                if keyword_arguments.is_some() {
+                    return Err(ProgramError {
+                        code: ErrorCode::SyntaxError,
+                        location,
+                        message: "Keyword arguments parameter must be the last one!".to_owned(),
+                    });
+                }
+                if collects_keywords {
+                    keyword_arguments = Some(parameter);
+                } else {
+                    arguments.push(parameter);
+                }
+            }
+            Ok((name, arguments, keyword_arguments))
         } else {
             Err(ProgramError {
+                code: ErrorCode::SyntaxError,
                 location: location.clone(),
                 message: "Expected a function name!".to_owned(),
             })
@@ -785,7 +1093,7 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
         if add_this {
             arguments.push("this");
         }
-        let (name, extra_arguments) = self.parse_function_header(location)?;
+        let (name, extra_arguments, keyword_arguments) = self.parse_function_header(location)?;
         arguments.extend(&extra_arguments);
         let body = if let StatementType::Block { body } =
             self.parse_block_statement(location.clone())?.statement_type
@@ -800,7 +1108,9 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
                 context_variables: vec![],
                 name,
                 arguments,
+                keyword_arguments,
                 body,
+                annotations: vec![],
             },
         ))
     }
@@ -861,8 +1171,24 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
                     },
                 ))
             }
+            Some(Token {
+                location: var_location,
+                token_type: TokenType::Var,
+            }) => {
+                self.next();
+                if self.peek(TokenType::LeftParen) {
+                    self.parse_tuple_declaration(&var_location)
+                } else {
+                    let name = self.parse_identifier()?;
+                    if self.peek(TokenType::In) {
+                        return self.parse_for_in_statement(name, location);
+                    }
+                    self.parse_variable_initializer(name, &var_location)
+                }
+            }
             Some(_) => self.parse_statement(),
             _ => Err(ProgramError {
+                code: ErrorCode::SyntaxError,
                 message: "Unexpected end of file".to_owned(),
                 location: SourceCodeLocation {
                     line: 0,
@@ -871,10 +1197,13 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
             }),
         }?;
         let init = match &temp_init.statement_type {
-            StatementType::VariableDeclaration { .. } | StatementType::Expression { .. } => {
+            StatementType::VariableDeclaration { .. } |
+            StatementType::TupleDeclaration { .. } |
+            StatementType::Expression { .. } => {
                 Ok(temp_init)
             }
             _ => Err(ProgramError {
+                code: ErrorCode::SyntaxError,
                 location: temp_init.location,
                 message: "Invalid statement for initialization!".to_owned(),
             }),
@@ -920,10 +1249,20 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
             )
         };
         let body = self.parse_statement()?;
+        let mut action_body = vec![Box::new(body)];
+        if !matches!(init.statement_type, StatementType::Expression { .. }) {
+            // Closures created in the body keep the bindings of their own iteration; the
+            // increment runs on a copy.
+            action_body.push(Box::new(self.statement_factory.borrow_mut().new_statement(
+                location.clone(),
+                StatementType::RebindLoopVariables,
+            )));
+        }
+        action_body.push(Box::new(incr));
         let action = Box::new(self.statement_factory.borrow_mut().new_statement(
             location.clone(),
             StatementType::Block {
-                body: vec![Box::new(body), Box::new(incr)],
+                body: action_body,
             },
         ));
         let body_block = Box::new(self.statement_factory.borrow_mut().new_statement(
@@ -941,6 +1280,32 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
         ))
     }
 
+    /// `for (var name in iterable) body`, once `for (var name` has been consumed.
+    fn parse_for_in_statement(
+        &self,
+        variable: &'a str,
+        location: &SourceCodeLocation<'a>,
+    ) -> Result<Statement<'a>, ProgramError<'a>> {
+        self.consume(TokenType::In, "Expected `in` after for variable", location)?;
+        let iterable = self.parse_expression()?;
+        self.consume(
+            TokenType::RightParen,
+            "Expecting ')' after expression.",
+            &iterable.location,
+        )?;
+        *self.block_stack.borrow_mut() += 1;
+        let body = self.parse_statement()?;
+        *self.block_stack.borrow_mut() -= 1;
+        Ok(self.statement_factory.borrow_mut().new_statement(
+            location.clone(),
+            StatementType::ForIn {
+                variable,
+                iterable,
+                body: Box::new(body),
+            },
+        ))
+    }
+
     pub(crate) fn parse_expression(&self) -> Result<Expression<'a>, ProgramError<'a>> {
         self.parse_assignment()
     }
@@ -991,13 +1356,27 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
                             },
                             location,
                         )),
+                        Expression {
+                            expression_type: ExpressionType::Tuple { elements },
+                            location,
+                            ..
+                        } if elements.iter().all(|e| matches!(e.expression_type, ExpressionType::VariableLiteral { .. })) =>
+                            Ok(self.expression_factory.borrow_mut().new_expression(
+                                ExpressionType::TupleAssignment {
+                                    targets: elements,
+                                    value: Box::new(expression),
+                                },
+                                location,
+                            )),
                         _ => Err(ProgramError {
+                            code: ErrorCode::SyntaxError,
                             location,
                             message: "Invalid assignment target".to_owned(),
                         }),
                     }
                 } else {
                     Err(ProgramError {
+                        code: ErrorCode::SyntaxError,
                         location,
                         message: "No right side in assignment".to_owned(),
                     })
@@ -1033,7 +1412,11 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
     }
 
     fn parse_sequence(&self) -> Result<Expression<'a>, ProgramError<'a>> {
-        self.parse_binary(Parser::parse_or, Parser::parse_sequence, &[TokenType::Bar])
+        self.parse_binary(Parser::parse_nullish, Parser::parse_sequence, &[TokenType::Bar])
+    }
+
+    fn parse_nullish(&self) -> Result<Expression<'a>, ProgramError<'a>> {
+        self.parse_binary(Parser::parse_or, Parser::parse_nullish, &[TokenType::DoubleQuestion])
     }
 
     fn parse_or(&self) -> Result<Expression<'a>, ProgramError<'a>> {
@@ -1077,7 +1460,7 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
         self.parse_binary(
             Parser::parse_unary,
             Parser::parse_multiplication,
-            &[TokenType::Star, TokenType::Slash],
+            &[TokenType::Star, TokenType::Slash, TokenType::Percent],
         )
     }
 
@@ -1135,11 +1518,14 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
             Some(Token {
                 token_type: TokenType::Identifier { name }, location, ..
             }) => {
-                Ok(Type::UserDefined(Box::new(
-                    self.parse_variable_or_module_access(name, &location)?
-                )))
+                let mut checked_type = self.parse_variable_or_module_access(name, &location)?;
+                if self.peek(TokenType::Dot) {
+                    checked_type = self.parse_call_property(checked_type)?;
+                }
+                Ok(Type::UserDefined(Box::new(checked_type)))
             }
             _ => Err(ProgramError {
+                code: ErrorCode::SyntaxError,
                 message: "Expected type literal in istype right hand operand".to_owned(),
                 location: location.clone(),
             })
@@ -1156,6 +1542,9 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
                 Some(TokenType::Dot) => {
                     callee = self.parse_call_property(callee)?;
                 }
+                Some(TokenType::QuestionDot) => {
+                    callee = self.parse_optional_property(callee)?;
+                }
                 _ => return Ok(callee),
             }
         }
@@ -1177,7 +1566,27 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
                 location,
             ))
         } else {
-            Err(callee.create_program_error("Expected property name after '.'"))
+            Err(callee.create_program_error(ErrorCode::SyntaxError, "Expected property name after '.'"))
+        }
+    }
+
+    fn parse_optional_property(&self, callee: Expression<'a>) -> Result<Expression<'a>, ProgramError<'a>> {
+        self.consume(
+            TokenType::QuestionDot,
+            "Expected '?.' on optional property expression",
+            &callee.location,
+        )?;
+        if let Some(TokenType::Identifier { name }) = self.next().map(|t| t.token_type) {
+            let location = callee.location.clone();
+            Ok(self.expression_factory.borrow_mut().new_expression(
+                ExpressionType::OptionalGet {
+                    callee: Box::new(callee),
+                    property: name,
+                },
+                location,
+            ))
+        } else {
+            Err(callee.create_program_error(ErrorCode::SyntaxError, "Expected property name after '?.'"))
         }
     }
 
@@ -1187,12 +1596,23 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
             "Expected '(' on function call expression",
             &callee.location,
         )?;
-        let args = self.parse_parameters(&callee.location, Parser::parse_ternary)?;
+        let args = self.parse_parameters(&callee.location, Parser::parse_call_argument)?;
         self.consume(
             TokenType::RightParen,
             "Expected ')' on function call expression",
             &callee.location,
         )?;
+        let first_named = args
+            .iter()
+            .position(|a| matches!(a.expression_type, ExpressionType::NamedArgument { .. }));
+        if let Some(first_named) = first_named {
+            if let Some(a) = args[first_named..]
+                .iter()
+                .find(|a| !matches!(a.expression_type, ExpressionType::NamedArgument { .. }))
+            {
+                return Err(a.create_program_error(ErrorCode::SyntaxError, "Positional arguments must come before named arguments!"));
+            }
+        }
         let location = callee.location.clone();
         Ok(self.expression_factory.borrow_mut().new_expression(
             ExpressionType::Call {
@@ -1232,6 +1652,42 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
         Ok(args)
     }
 
+    /// Parses a call argument, turning `name: value` into a named argument.
+    fn parse_call_argument(&self) -> Result<Expression<'a>, ProgramError<'a>> {
+        let argument = self.parse_ternary()?;
+        match argument.expression_type {
+            ExpressionType::VariableLiteral { identifier } if self.peek(TokenType::Colon) => {
+                self.next();
+                let value = self.parse_ternary()?;
+                Ok(self.expression_factory.borrow_mut().new_expression(
+                    ExpressionType::NamedArgument {
+                        name: identifier,
+                        value: Box::new(value),
+                    },
+                    argument.location,
+                ))
+            }
+            _ => Ok(argument),
+        }
+    }
+
+    /// Parses a function parameter. The flag is true for `**name`, which collects the named
+    /// arguments that don't match any other parameter.
+    fn parse_function_parameter(&self) -> Result<(&'a str, bool), ProgramError<'a>> {
+        if self.peek(TokenType::Star) {
+            if let Some(Token { location, .. }) = self.next() {
+                self.consume(
+                    TokenType::Star,
+                    "Expected '**' before keyword arguments parameter",
+                    &location,
+                )?;
+            }
+            Ok((self.parse_identifier()?, true))
+        } else {
+            Ok((self.parse_identifier()?, false))
+        }
+    }
+
     fn parse_identifier(&self) -> Result<&'a str, ProgramError<'a>> {
         match self.next() {
             Some(Token {
@@ -1239,6 +1695,7 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
                 ..
             }) => Ok(name),
             Some(Token { location, token_type, .. }) => Err(ProgramError {
+                code: ErrorCode::SyntaxError,
                 location,
                 message: format!("Expected identifier! Got {:?}", token_type),
             }),
@@ -1247,8 +1704,8 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
     }
 
     fn parse_array_element(&self) -> Result<Expression<'a>, ProgramError<'a>> {
-        let array = self.parse_primary()?;
-        if self.peek(TokenType::LeftSquareBrace) {
+        let mut array = self.parse_primary()?;
+        while self.peek(TokenType::LeftSquareBrace) {
             self.next();
             let index = Box::new(self.parse_expression()?);
             self.consume(
@@ -1257,16 +1714,15 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
                 &index.location,
             )?;
             let location = array.location.clone();
-            Ok(self.expression_factory.borrow_mut().new_expression(
+            array = self.expression_factory.borrow_mut().new_expression(
                 ExpressionType::ArrayElement {
                     array: Box::new(array),
                     index,
                 },
                 location,
-            ))
-        } else {
-            Ok(array)
+            );
         }
+        Ok(array)
     }
 
     fn parse_primary(&self) -> Result<Expression<'a>, ProgramError<'a>> {
@@ -1275,6 +1731,10 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
                 token_type: TokenType::LeftSquareBrace,
                 location,
             }) => self.parse_array(location),
+            Some(Token {
+                token_type: TokenType::LeftBrace,
+                location,
+            }) => self.parse_map(location),
             Some(Token {
                 token_type: TokenType::Fun,
                 location,
@@ -1295,6 +1755,7 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
                 location,
             }) => self.parse_group(location),
             None => Err(ProgramError {
+                code: ErrorCode::SyntaxError,
                 message: "Unexpected end of file! Expecting primary".to_owned(),
                 location: SourceCodeLocation {
                     file: "",
@@ -1327,13 +1788,43 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
         }
     }
 
-    fn parse_array(&self, location: SourceCodeLocation<'a>) -> Result<Expression<'a>, ProgramError<'a>> {
-        if self.peek(TokenType::RightSquareBrace) {
-            self.next();
-            return Ok(self
-                .expression_factory
-                .borrow_mut()
-                .new_expression(ExpressionType::Array { elements: vec![] }, location));
+    fn parse_map(&self, location: SourceCodeLocation<'a>) -> Result<Expression<'a>, ProgramError<'a>> {
+        let mut entries = vec![];
+        while !self.peek(TokenType::RightBrace) {
+            let key = Box::new(self.parse_expression()?);
+            self.consume(
+                TokenType::Colon,
+                "Expected `:` between map key and value",
+                &key.location,
+            )?;
+            let value = Box::new(self.parse_expression()?);
+            if !self.peek(TokenType::RightBrace) {
+                self.consume(
+                    TokenType::Comma,
+                    "Expected `,` between map entries",
+                    &value.location,
+                )?;
+            }
+            entries.push((key, value));
+        }
+        self.consume(
+            TokenType::RightBrace,
+            "Expected `}` at the end of map literal",
+            &location,
+        )?;
+        Ok(self
+            .expression_factory
+            .borrow_mut()
+            .new_expression(ExpressionType::Map { entries }, location))
+    }
+
+    fn parse_array(&self, location: SourceCodeLocation<'a>) -> Result<Expression<'a>, ProgramError<'a>> {
+        if self.peek(TokenType::RightSquareBrace) {
+            self.next();
+            return Ok(self
+                .expression_factory
+                .borrow_mut()
+                .new_expression(ExpressionType::Array { elements: vec![] }, location));
         }
         let element = Box::new(self.parse_expression()?);
         match self.next() {
@@ -1390,10 +1881,12 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
             Some(Token {
                 location, token_type,
             }) => Err(ProgramError {
+                code: ErrorCode::SyntaxError,
                 message: format!("Unexpected token `{:?}`", token_type),
                 location,
             }),
             None => Err(ProgramError {
+                code: ErrorCode::SyntaxError,
                 message: "Unexpected end of file".to_owned(),
                 location,
             }),
@@ -1409,6 +1902,7 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
             TokenType::EqualEqual | TokenType::BangEqual => {
                 self.parse_equality()?;
                 Err(ProgramError {
+                    code: ErrorCode::SyntaxError,
                     location: location.clone(),
                     message: "Equality without left side".to_owned(),
                 })
@@ -1419,6 +1913,7 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
             | TokenType::LessEqual => {
                 self.parse_comparison()?;
                 Err(ProgramError {
+                    code: ErrorCode::SyntaxError,
                     location: location.clone(),
                     message: "Comparision without left side".to_owned(),
                 })
@@ -1426,18 +1921,21 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
             TokenType::Plus => {
                 self.parse_addition()?;
                 Err(ProgramError {
+                    code: ErrorCode::SyntaxError,
                     location: location.clone(),
                     message: "Addition without left side".to_owned(),
                 })
             }
-            TokenType::Slash | TokenType::Star => {
+            TokenType::Slash | TokenType::Star | TokenType::Percent => {
                 self.parse_multiplication()?;
                 Err(ProgramError {
+                    code: ErrorCode::SyntaxError,
                     location: location.clone(),
                     message: "Multiplication without left side".to_owned(),
                 })
             }
             _ => Err(ProgramError {
+                code: ErrorCode::SyntaxError,
                 location: location.clone(),
                 message: format!("Expecting a literal, but got {:?}!", token_type),
             }),
@@ -1446,6 +1944,9 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
 
     fn parse_group(&self, location: SourceCodeLocation<'a>) -> Result<Expression<'a>, ProgramError<'a>> {
         let expression = Box::new(self.parse_expression()?);
+        if self.peek(TokenType::Comma) {
+            return self.parse_tuple(expression, location);
+        }
         self.consume(TokenType::RightParen, "Missing `)`", &location)?;
         Ok(self
             .expression_factory
@@ -1453,6 +1954,26 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
             .new_expression(ExpressionType::Grouping { expression }, location))
     }
 
+    fn parse_tuple(
+        &self,
+        first: Box<Expression<'a>>,
+        location: SourceCodeLocation<'a>,
+    ) -> Result<Expression<'a>, ProgramError<'a>> {
+        let mut elements = vec![first];
+        while self.peek(TokenType::Comma) {
+            self.next();
+            if self.peek(TokenType::RightParen) {
+                break;
+            }
+            elements.push(Box::new(self.parse_expression()?));
+        }
+        self.consume(TokenType::RightParen, "Missing `)` after tuple elements", &location)?;
+        Ok(self
+            .expression_factory
+            .borrow_mut()
+            .new_expression(ExpressionType::Tuple { elements }, location))
+    }
+
     fn parse_binary<
         L: Fn(&Parser<'a, I>) -> Result<Expression<'a>, ProgramError<'a>>,
         R: Fn(&Parser<'a, I>) -> Result<Expression<'a>, ProgramError<'a>>,
@@ -1485,7 +2006,7 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
     }
 
     fn peek(&self, token: TokenType) -> bool {
-        if let Some(t) = self.content.borrow_mut().peek() {
+        if let Some(t) = self.tokens().peek() {
             token == t.token_type
         } else {
             false
@@ -1501,10 +2022,12 @@ impl<'a, I: Iterator<Item = Token<'a>>> Parser<'a, I> {
         match self.next() {
             Some(t) if t.token_type == token => Ok(()),
             Some(t) => Err(ProgramError {
+                code: ErrorCode::SyntaxError,
                 location: t.location,
                 message: message.to_owned(),
             }),
             _ => Err(ProgramError {
+                code: ErrorCode::SyntaxError,
                 location: location.clone(),
                 message: message.to_owned(),
             }),
@@ -1517,7 +2040,7 @@ mod test {
     use super::Parser;
     use crate::types::ExpressionType::ExpressionLiteral;
     use crate::types::StatementType::VariableDeclaration;
-    use crate::types::{Expression, ExpressionFactory, ExpressionType, Literal, SourceCodeLocation, Statement, StatementType, Token, TokenType, StatementFactory};
+    use crate::types::{Annotation, ClassField, EnumVariant, Expression, ExpressionFactory, ExpressionType, Literal, SourceCodeLocation, Statement, StatementType, Token, TokenType, StatementFactory, Type};
 
     fn create_expression<'a>(
         expression_type: ExpressionType<'a>,
@@ -1818,6 +2341,147 @@ mod test {
         assert!(parser.is_empty());
     }
 
+    #[test]
+    fn parse_call_with_named_arguments() {
+        let location = SourceCodeLocation {
+            line: 1,
+            file: "",
+        };
+        let input = vec![
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Identifier {
+                    name: "identifier",
+                },
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::LeftParen,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Identifier {
+                    name: "name",
+                },
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Colon,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Identifier {
+                    name: "value",
+                },
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::RightParen,
+            },
+        ];
+        let parser = Parser::new(input.into_iter().peekable());
+        let result = parser.parse_expression().unwrap();
+        assert_eq!(
+            result,
+            create_expression_with_id(
+                ExpressionType::Call {
+                    callee: Box::new(create_expression(
+                        ExpressionType::VariableLiteral {
+                            identifier: "identifier",
+                        },
+                        location.clone(),
+                    )),
+                    arguments: vec![Box::new(create_expression_with_id(
+                        ExpressionType::NamedArgument {
+                            name: "name",
+                            value: Box::new(create_expression_with_id(
+                                ExpressionType::VariableLiteral {
+                                    identifier: "value",
+                                },
+                                location.clone(),
+                                2,
+                            )),
+                        },
+                        location.clone(),
+                        3,
+                    ))],
+                },
+                location.clone(),
+                4,
+            )
+        );
+        assert!(parser.is_empty());
+    }
+
+    #[test]
+    fn parse_optional_property_with_nullish_default() {
+        let location = SourceCodeLocation {
+            line: 1,
+            file: "",
+        };
+        let input = vec![
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Identifier {
+                    name: "object",
+                },
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::QuestionDot,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Identifier {
+                    name: "property",
+                },
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::DoubleQuestion,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Identifier {
+                    name: "fallback",
+                },
+            },
+        ];
+        let parser = Parser::new(input.into_iter().peekable());
+        let result = parser.parse_expression().unwrap();
+        assert_eq!(
+            result,
+            create_expression_with_id(
+                ExpressionType::Binary {
+                    left: Box::new(create_expression_with_id(
+                        ExpressionType::OptionalGet {
+                            callee: Box::new(create_expression(
+                                ExpressionType::VariableLiteral {
+                                    identifier: "object",
+                                },
+                                location.clone(),
+                            )),
+                            property: "property",
+                        },
+                        location.clone(),
+                        1,
+                    )),
+                    right: Box::new(create_expression_with_id(
+                        ExpressionType::VariableLiteral {
+                            identifier: "fallback",
+                        },
+                        location.clone(),
+                        2,
+                    )),
+                    operator: TokenType::DoubleQuestion,
+                },
+                location.clone(),
+                3,
+            )
+        );
+        assert!(parser.is_empty());
+    }
+
     #[test]
     fn parse_call_with_multiple_arguments() {
         let location = SourceCodeLocation {
@@ -1912,6 +2576,11 @@ mod test {
         test_binary(TokenType::Star);
     }
 
+    #[test]
+    fn parse_multiplication_with_percent() {
+        test_binary(TokenType::Percent);
+    }
+
     #[test]
     fn parse_addition_with_minus() {
         test_binary(TokenType::Minus);
@@ -2031,7 +2700,7 @@ mod test {
     }
 
     #[test]
-    fn parse_assignment() {
+    fn parse_map() {
         let location = SourceCodeLocation {
             line: 1,
             file: "",
@@ -2039,35 +2708,50 @@ mod test {
         let input = vec![
             Token {
                 location: location.clone(),
-                token_type: TokenType::Identifier {
-                    name: "identifier",
+                token_type: TokenType::LeftBrace,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::TokenLiteral {
+                    value: Literal::QuotedString("key"),
                 },
             },
             Token {
                 location: location.clone(),
-                token_type: TokenType::Equal,
+                token_type: TokenType::Colon,
             },
             Token {
                 location: location.clone(),
                 token_type: TokenType::TokenLiteral {
-                    value: Literal::Float(1.0),
+                    value: Literal::Integer(1),
                 },
             },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::RightBrace,
+            },
         ];
         let parser = Parser::new(input.into_iter().peekable());
         let result = parser.parse_expression().unwrap();
         assert_eq!(
             result,
             create_expression_with_id(
-                ExpressionType::VariableAssignment {
-                    identifier: "identifier",
-                    expression: Box::new(create_expression_with_id(
-                        ExpressionLiteral {
-                            value: Literal::Float(1.0),
-                        },
-                        location.clone(),
-                        1,
-                    ))
+                ExpressionType::Map {
+                    entries: vec![(
+                        Box::new(create_expression(
+                            ExpressionType::ExpressionLiteral {
+                                value: Literal::QuotedString("key"),
+                            },
+                            location.clone(),
+                        )),
+                        Box::new(create_expression_with_id(
+                            ExpressionType::ExpressionLiteral {
+                                value: Literal::Integer(1),
+                            },
+                            location.clone(),
+                            1,
+                        )),
+                    )],
                 },
                 location.clone(),
                 2,
@@ -2077,85 +2761,39 @@ mod test {
     }
 
     #[test]
-    fn parse_if() {
+    fn parse_tuple_assignment() {
         let location = SourceCodeLocation {
             line: 1,
             file: "",
         };
         let input = vec![
-            Token {
-                location: location.clone(),
-                token_type: TokenType::If,
-            },
             Token {
                 location: location.clone(),
                 token_type: TokenType::LeftParen,
             },
             Token {
                 location: location.clone(),
-                token_type: TokenType::TokenLiteral {
-                    value: Literal::Float(1.0),
+                token_type: TokenType::Identifier {
+                    name: "a",
                 },
             },
             Token {
                 location: location.clone(),
-                token_type: TokenType::RightParen,
+                token_type: TokenType::Comma,
             },
             Token {
                 location: location.clone(),
-                token_type: TokenType::TokenLiteral {
-                    value: Literal::Float(1.0),
+                token_type: TokenType::Identifier {
+                    name: "b",
                 },
             },
             Token {
                 location: location.clone(),
-                token_type: TokenType::Semicolon,
-            },
-        ];
-        let parser = Parser::new(input.into_iter().peekable());
-        let result = parser.parse_statement().unwrap();
-        let mut statement_factory = StatementFactory::new();
-        let then = Box::new(statement_factory.new_statement(
-            location.clone(),
-            StatementType::Expression {
-                expression: create_expression_with_id(
-                    ExpressionType::ExpressionLiteral {
-                        value: Literal::Float(1.0),
-                    },
-                    location.clone(),
-                    1,
-                ),
+                token_type: TokenType::RightParen,
             },
-        ));
-        assert_eq!(
-            result,
-            statement_factory.new_statement(
-                location.clone(),
-                StatementType::If {
-                    condition: create_expression(
-                        ExpressionType::ExpressionLiteral {
-                            value: Literal::Float(1.0),
-                        },
-                        location.clone(),
-                    ),
-                    otherwise: None,
-                    then,
-                },
-            )
-        );
-        assert!(parser.is_empty());
-    }
-
-    #[test]
-    fn parse_if_else() {
-        let location = SourceCodeLocation {
-            line: 1,
-            file: "",
-        };
-        let input = vec![
             Token {
                 location: location.clone(),
-                token_type: TokenType::If,
+                token_type: TokenType::Equal,
             },
             Token {
                 location: location.clone(),
@@ -2163,18 +2801,222 @@ mod test {
             },
             Token {
                 location: location.clone(),
-                token_type: TokenType::TokenLiteral {
-                    value: Literal::Float(1.0),
+                token_type: TokenType::Identifier {
+                    name: "b",
                 },
             },
             Token {
                 location: location.clone(),
-                token_type: TokenType::RightParen,
+                token_type: TokenType::Comma,
             },
             Token {
                 location: location.clone(),
-                token_type: TokenType::TokenLiteral {
-                    value: Literal::Float(1.0),
+                token_type: TokenType::Identifier {
+                    name: "a",
+                },
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::RightParen,
+            },
+        ];
+        let parser = Parser::new(input.into_iter().peekable());
+        let result = parser.parse_expression().unwrap();
+        assert_eq!(
+            result,
+            create_expression_with_id(
+                ExpressionType::TupleAssignment {
+                    targets: vec![
+                        Box::new(create_expression_with_id(
+                            ExpressionType::VariableLiteral {
+                                identifier: "a",
+                            },
+                            location.clone(),
+                            0,
+                        )),
+                        Box::new(create_expression_with_id(
+                            ExpressionType::VariableLiteral {
+                                identifier: "b",
+                            },
+                            location.clone(),
+                            1,
+                        )),
+                    ],
+                    value: Box::new(create_expression_with_id(
+                        ExpressionType::Tuple {
+                            elements: vec![
+                                Box::new(create_expression_with_id(
+                                ExpressionType::VariableLiteral {
+                                    identifier: "b",
+                                },
+                                location.clone(),
+                                3,
+                            )),
+                                Box::new(create_expression_with_id(
+                                ExpressionType::VariableLiteral {
+                                    identifier: "a",
+                                },
+                                location.clone(),
+                                4,
+                            )),
+                            ],
+                        },
+                        location.clone(),
+                        5,
+                    )),
+                },
+                location.clone(),
+                6,
+            )
+        );
+        assert!(parser.is_empty());
+    }
+
+    #[test]
+    fn parse_assignment() {
+        let location = SourceCodeLocation {
+            line: 1,
+            file: "",
+        };
+        let input = vec![
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Identifier {
+                    name: "identifier",
+                },
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Equal,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::TokenLiteral {
+                    value: Literal::Float(1.0),
+                },
+            },
+        ];
+        let parser = Parser::new(input.into_iter().peekable());
+        let result = parser.parse_expression().unwrap();
+        assert_eq!(
+            result,
+            create_expression_with_id(
+                ExpressionType::VariableAssignment {
+                    identifier: "identifier",
+                    expression: Box::new(create_expression_with_id(
+                        ExpressionLiteral {
+                            value: Literal::Float(1.0),
+                        },
+                        location.clone(),
+                        1,
+                    ))
+                },
+                location.clone(),
+                2,
+            )
+        );
+        assert!(parser.is_empty());
+    }
+
+    #[test]
+    fn parse_if() {
+        let location = SourceCodeLocation {
+            line: 1,
+            file: "",
+        };
+        let input = vec![
+            Token {
+                location: location.clone(),
+                token_type: TokenType::If,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::LeftParen,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::TokenLiteral {
+                    value: Literal::Float(1.0),
+                },
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::RightParen,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::TokenLiteral {
+                    value: Literal::Float(1.0),
+                },
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Semicolon,
+            },
+        ];
+        let parser = Parser::new(input.into_iter().peekable());
+        let result = parser.parse_statement().unwrap();
+        let mut statement_factory = StatementFactory::new();
+        let then = Box::new(statement_factory.new_statement(
+            location.clone(),
+            StatementType::Expression {
+                expression: create_expression_with_id(
+                    ExpressionType::ExpressionLiteral {
+                        value: Literal::Float(1.0),
+                    },
+                    location.clone(),
+                    1,
+                ),
+            },
+        ));
+        assert_eq!(
+            result,
+            statement_factory.new_statement(
+                location.clone(),
+                StatementType::If {
+                    condition: create_expression(
+                        ExpressionType::ExpressionLiteral {
+                            value: Literal::Float(1.0),
+                        },
+                        location.clone(),
+                    ),
+                    otherwise: None,
+                    then,
+                },
+            )
+        );
+        assert!(parser.is_empty());
+    }
+
+    #[test]
+    fn parse_if_else() {
+        let location = SourceCodeLocation {
+            line: 1,
+            file: "",
+        };
+        let input = vec![
+            Token {
+                location: location.clone(),
+                token_type: TokenType::If,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::LeftParen,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::TokenLiteral {
+                    value: Literal::Float(1.0),
+                },
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::RightParen,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::TokenLiteral {
+                    value: Literal::Float(1.0),
                 },
             },
             Token {
@@ -2232,7 +3074,227 @@ mod test {
     }
 
     #[test]
-    fn parse_var() {
+    fn parse_var() {
+        let location = SourceCodeLocation {
+            line: 1,
+            file: "",
+        };
+        let input = vec![
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Var,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Identifier {
+                    name: "identifier",
+                },
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Semicolon,
+            },
+        ];
+        let parser = Parser::new(input.into_iter().peekable());
+        let result = parser.parse_statement().unwrap();
+        assert_eq!(result.location, location);
+        assert_eq!(result.statement_type, StatementType::VariableDeclaration {
+            name: "identifier",
+            expression: None,
+        });
+        assert!(parser.is_empty());
+    }
+
+    #[test]
+    fn parse_enum() {
+        let location = SourceCodeLocation {
+            line: 1,
+            file: "",
+        };
+        let input = vec![
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Enum,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Identifier { name: "Shape" },
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::LeftBrace,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Identifier { name: "Rect" },
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::LeftParen,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Identifier { name: "w" },
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Comma,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Identifier { name: "h" },
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::RightParen,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Comma,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Identifier { name: "Empty" },
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::RightBrace,
+            },
+        ];
+        let parser = Parser::new(input.into_iter().peekable());
+        let result = parser.parse_statement().unwrap();
+        assert_eq!(result.location, location);
+        assert_eq!(result.statement_type, StatementType::EnumDeclaration {
+            name: "Shape",
+            variants: vec![
+                EnumVariant { name: "Rect", fields: vec!["w", "h"] },
+                EnumVariant { name: "Empty", fields: vec![] },
+            ],
+        });
+        assert!(parser.is_empty());
+    }
+
+    #[test]
+    fn parse_final_class() {
+        let location = SourceCodeLocation {
+            line: 1,
+            file: "",
+        };
+        let input = vec![
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Final,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Class,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Identifier { name: "Point" },
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::LeftBrace,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::RightBrace,
+            },
+        ];
+        let parser = Parser::new(input.into_iter().peekable());
+        let result = parser.parse_statement().unwrap();
+        assert_eq!(result.statement_type, StatementType::ClassDeclaration {
+            name: "Point",
+            superclass: None,
+            methods: vec![],
+            static_methods: vec![],
+            getters: vec![],
+            setters: vec![],
+            fields: vec![],
+            is_final: true,
+            annotations: vec![],
+        });
+        assert!(parser.is_empty());
+    }
+
+    #[test]
+    fn parse_class_with_typed_fields() {
+        let location = SourceCodeLocation {
+            line: 1,
+            file: "",
+        };
+        let input = vec![
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Class,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Identifier { name: "Person" },
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::LeftBrace,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Var,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Identifier { name: "age" },
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Colon,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Integer,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Semicolon,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Var,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Identifier { name: "name" },
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Semicolon,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::RightBrace,
+            },
+        ];
+        let parser = Parser::new(input.into_iter().peekable());
+        let result = parser.parse_statement().unwrap();
+        assert_eq!(result.statement_type, StatementType::ClassDeclaration {
+            name: "Person",
+            superclass: None,
+            methods: vec![],
+            static_methods: vec![],
+            getters: vec![],
+            setters: vec![],
+            fields: vec![
+                ClassField { name: "age", field_type: Some(Type::Integer) },
+                ClassField { name: "name", field_type: None },
+            ],
+            is_final: false,
+            annotations: vec![],
+        });
+        assert!(parser.is_empty());
+    }
+
+    #[test]
+    fn parse_var_with_expression() {
         let location = SourceCodeLocation {
             line: 1,
             file: "",
@@ -2248,6 +3310,16 @@ mod test {
                     name: "identifier",
                 },
             },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Equal,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::TokenLiteral {
+                    value: Literal::Float(1.0),
+                },
+            },
             Token {
                 location: location.clone(),
                 token_type: TokenType::Semicolon,
@@ -2258,13 +3330,18 @@ mod test {
         assert_eq!(result.location, location);
         assert_eq!(result.statement_type, StatementType::VariableDeclaration {
             name: "identifier",
-            expression: None,
+            expression: Some(create_expression(
+                ExpressionType::ExpressionLiteral {
+                    value: Literal::Float(1.0),
+                },
+                location.clone(),
+            )),
         });
         assert!(parser.is_empty());
     }
 
     #[test]
-    fn parse_var_with_expression() {
+    fn parse_var_with_tuple_destructuring() {
         let location = SourceCodeLocation {
             line: 1,
             file: "",
@@ -2276,20 +3353,52 @@ mod test {
             },
             Token {
                 location: location.clone(),
-                token_type: TokenType::Identifier {
-                    name: "identifier",
-                },
+                token_type: TokenType::LeftParen,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Identifier { name: "q" },
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Comma,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Identifier { name: "r" },
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::RightParen,
             },
             Token {
                 location: location.clone(),
                 token_type: TokenType::Equal,
             },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::LeftParen,
+            },
             Token {
                 location: location.clone(),
                 token_type: TokenType::TokenLiteral {
-                    value: Literal::Float(1.0),
+                    value: Literal::Integer(1),
+                },
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Comma,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::TokenLiteral {
+                    value: Literal::Integer(2),
                 },
             },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::RightParen,
+            },
             Token {
                 location: location.clone(),
                 token_type: TokenType::Semicolon,
@@ -2298,14 +3407,29 @@ mod test {
         let parser = Parser::new(input.into_iter().peekable());
         let result = parser.parse_statement().unwrap();
         assert_eq!(result.location, location);
-        assert_eq!(result.statement_type, StatementType::VariableDeclaration {
-            name: "identifier",
-            expression: Some(create_expression(
-                ExpressionType::ExpressionLiteral {
-                    value: Literal::Float(1.0),
+        assert_eq!(result.statement_type, StatementType::TupleDeclaration {
+            names: vec!["q", "r"],
+            expression: create_expression_with_id(
+                ExpressionType::Tuple {
+                    elements: vec![
+                        Box::new(create_expression(
+                            ExpressionType::ExpressionLiteral {
+                                value: Literal::Integer(1),
+                            },
+                            location.clone(),
+                        )),
+                        Box::new(create_expression_with_id(
+                            ExpressionType::ExpressionLiteral {
+                                value: Literal::Integer(2),
+                            },
+                            location.clone(),
+                            1,
+                        )),
+                    ],
                 },
                 location.clone(),
-            )),
+                2,
+            ),
         });
         assert!(parser.is_empty());
     }
@@ -2440,6 +3564,7 @@ mod test {
                 StatementType::FunctionDeclaration {
                     name: "identifier",
                     arguments: vec!["argument"],
+                    keyword_arguments: None,
                     body: vec![
                         Box::new(create_statement_expression_with_id(
                             ExpressionType::VariableLiteral {
@@ -2459,6 +3584,7 @@ mod test {
                         )),
                     ],
                     context_variables: vec![],
+                    annotations: vec![],
                 },
                 location.clone(),
                 3,
@@ -2467,6 +3593,105 @@ mod test {
         assert!(parser.is_empty());
     }
 
+    #[test]
+    fn parse_annotated_function() {
+        let location = SourceCodeLocation {
+            line: 1,
+            file: "",
+        };
+        let input = vec![
+            Token {
+                location: location.clone(),
+                token_type: TokenType::At,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Identifier { name: "doc" },
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::LeftParen,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::TokenLiteral { value: Literal::QuotedString("Adds one") },
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::RightParen,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::At,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Identifier { name: "attr" },
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::LeftParen,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Identifier { name: "version" },
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Comma,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::TokenLiteral { value: Literal::Integer(2) },
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::RightParen,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Fun,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Identifier { name: "identifier" },
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::LeftParen,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::RightParen,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::LeftBrace,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::RightBrace,
+            },
+        ];
+        let parser = Parser::new(input.into_iter().peekable());
+        let result = parser.parse_statement().unwrap();
+        assert_eq!(
+            result.statement_type,
+            StatementType::FunctionDeclaration {
+                name: "identifier",
+                arguments: vec![],
+                keyword_arguments: None,
+                body: vec![],
+                context_variables: vec![],
+                annotations: vec![
+                    Annotation { key: "doc", value: Literal::QuotedString("Adds one") },
+                    Annotation { key: "version", value: Literal::Integer(2) },
+                ],
+            }
+        );
+        assert!(parser.is_empty());
+    }
+
     #[test]
     fn parse_while() {
         let location = SourceCodeLocation {
@@ -2565,6 +3790,108 @@ mod test {
         assert!(parser.is_empty());
     }
 
+    #[test]
+    fn parse_for_in() {
+        let location = SourceCodeLocation {
+            line: 1,
+            file: "",
+        };
+        let input = vec![
+            Token {
+                location: location.clone(),
+                token_type: TokenType::For,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::LeftParen,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Var,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Identifier {
+                    name: "item",
+                },
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::In,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Identifier {
+                    name: "items",
+                },
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::RightParen,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::LeftBrace,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Print,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Identifier {
+                    name: "item",
+                },
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::Semicolon,
+            },
+            Token {
+                location: location.clone(),
+                token_type: TokenType::RightBrace,
+            },
+        ];
+        let parser = Parser::new(input.into_iter().peekable());
+        let result = parser.parse_statement().unwrap();
+        assert_eq!(
+            result,
+            create_statement_with_id(
+                StatementType::ForIn {
+                    variable: "item",
+                    iterable: create_expression(
+                        ExpressionType::VariableLiteral {
+                            identifier: "items",
+                        },
+                        location.clone(),
+                    ),
+                    body: Box::new(create_statement_with_id(
+                        StatementType::Block {
+                            body: vec![Box::new(create_statement_with_id(
+                                StatementType::PrintStatement {
+                                    expression: create_expression_with_id(
+                                        ExpressionType::VariableLiteral {
+                                            identifier: "item",
+                                        },
+                                        location.clone(),
+                                        1,
+                                    ),
+                                },
+                                location.clone(),
+                                0,
+                            ))],
+                        },
+                        location.clone(),
+                        1,
+                    )),
+                },
+                location.clone(),
+                2,
+            )
+        );
+        assert!(parser.is_empty());
+    }
+
     #[test]
     fn parse_full_for() {
         let location = SourceCodeLocation {
@@ -2681,6 +4008,11 @@ mod test {
                     StatementType::Block {
                         body: vec![
                             Box::new(body_statement),
+                            Box::new(create_statement_with_id(
+                                StatementType::RebindLoopVariables,
+                                location.clone(),
+                                5,
+                            )),
                             Box::new(create_statement_with_id(
                                 StatementType::Expression {
                                     expression: create_expression_with_id(
@@ -2697,11 +4029,11 @@ mod test {
                         ],
                     },
                     location.clone(),
-                    5,
+                    6,
                 )),
             },
             location.clone(),
-            6,
+            7,
         );
         let for_block = create_statement_with_id(
             StatementType::Block {
@@ -2718,7 +4050,7 @@ mod test {
                 ],
             },
             location.clone(),
-            7,
+            8,
         );
         assert_eq!(result, for_block);
         assert!(parser.is_empty());
@@ -1,5 +1,5 @@
-use crate::types::{Expression, ExpressionType, ProgramError, SourceCodeLocation, Statement, StatementType, Pass};
-use ahash::{AHashMap as HashMap};
+use crate::types::{EnumVariant, ErrorCode, Expression, ExpressionType, ProgramError, SourceCodeLocation, Statement, StatementType, Pass};
+use ahash::{AHashMap as HashMap, AHashSet as HashSet};
 
 pub struct Resolver<'a> {
     check_used: bool,
@@ -7,6 +7,9 @@ pub struct Resolver<'a> {
     uses: Vec<HashMap<&'a str, usize>>,
     locations: Vec<HashMap<&'a str, &'a SourceCodeLocation<'a>>>,
     locals: HashMap<usize, usize>,
+    builtins: HashSet<&'a str>,
+    class_scopes: Vec<usize>,
+    warnings: Vec<ProgramError<'a>>,
 }
 
 impl<'a> Resolver<'a> {
@@ -17,6 +20,9 @@ impl<'a> Resolver<'a> {
             locations: vec![HashMap::default()],
             scopes: vec![HashMap::default()],
             uses: vec![HashMap::default()],
+            builtins: HashSet::default(),
+            class_scopes: vec![],
+            warnings: vec![],
         }
     }
     pub fn new_without_check_used() -> Resolver<'a> {
@@ -26,8 +32,20 @@ impl<'a> Resolver<'a> {
             locations: vec![HashMap::default()],
             scopes: vec![HashMap::default()],
             uses: vec![HashMap::default()],
+            builtins: HashSet::default(),
+            class_scopes: vec![],
+            warnings: vec![],
         }
     }
+    /// Names that are defined before the program runs. Declaring one of them adds a warning
+    /// instead of an error, as the declaration is valid but hides the built-in.
+    pub fn with_builtins<I: IntoIterator<Item = &'a str>>(mut self, builtins: I) -> Resolver<'a> {
+        self.builtins.extend(builtins);
+        self
+    }
+    pub fn warnings(&self) -> &[ProgramError<'a>] {
+        &self.warnings
+    }
     fn push_scope(&mut self, scope: HashMap<&'a str, bool>) {
         self.scopes.push(scope);
         self.uses.push(HashMap::default());
@@ -46,6 +64,7 @@ impl<'a> Resolver<'a> {
                 .iter()
                 .filter(|(_, uses)| self.check_used && **uses == 0)
                 .map(|p| ProgramError {
+                    code: ErrorCode::NameError,
                     message: format!("Variable `{}` never used.", *p.0),
                     location: locations[*p.0].clone(),
                 })
@@ -61,14 +80,23 @@ impl<'a> Resolver<'a> {
         name: &'a str,
         location: &'a SourceCodeLocation<'a>,
     ) -> Result<(), ProgramError<'a>> {
+        let is_method = self.class_scopes.last() == Some(&self.scopes.len());
         if let Some(s) = self.scopes.last_mut() {
             if s.contains_key(name) {
                 return Err(ProgramError {
+                    code: ErrorCode::NameError,
                     message: format!("Variable `{}` already declared in this scope!", name),
                     location: location.clone(),
                 });
             }
             s.insert(name, false);
+            if !is_method && self.builtins.contains(name) {
+                self.warnings.push(ProgramError {
+                    code: ErrorCode::NameError,
+                    message: format!("`{}` shadows a built-in global.", name),
+                    location: location.clone(),
+                });
+            }
             if let Some(locations) = self.locations.last_mut() {
                 locations.insert(name, location);
             }
@@ -96,6 +124,7 @@ impl<'a> Resolver<'a> {
         {
             if !scope[name] {
                 return Err(ProgramError {
+                    code: ErrorCode::NameError,
                     message: format!("`{}` used without being initialized.", name),
                     location: expression.location.clone(),
                 })
@@ -131,12 +160,13 @@ impl<'a> Resolver<'a> {
     fn resolve_function<'b>(
         &mut self,
         arguments: &'a [&'a str],
+        keyword_arguments: Option<&'a str>,
         context_variables: Option<&'a [&'a str]>,
         body: &'b [&'a Statement<'a>],
         location: &'a SourceCodeLocation<'a>,
     ) -> Result<(), Vec<ProgramError<'a>>> {
         self.push_scope(HashMap::default());
-        for arg in arguments {
+        for arg in arguments.iter().chain(keyword_arguments.iter()) {
             self.declare(arg, location).map_err(|e| vec![e])?;
             self.define(arg);
         }
@@ -157,14 +187,23 @@ impl<'a> Resolver<'a> {
 impl<'a> Pass<'a, HashMap<usize, usize>> for Resolver<'a> {
     fn run(&mut self, ss: &'a [Statement<'a>]) -> Result<HashMap<usize, usize>, Vec<ProgramError<'a>>> {
         ss.iter()
-            .filter(|s| s.is_variable_declaration())
             .map(|s| {
-                if let StatementType::VariableDeclaration { name, expression } = &s.statement_type {
-                    self.declare(name, &s.location)
-                        .map_err(|e| vec![e])?;
-                    if expression.is_some() {
-                        self.define(name);
+                match &s.statement_type {
+                    StatementType::VariableDeclaration { name, expression } => {
+                        self.declare(name, &s.location)
+                            .map_err(|e| vec![e])?;
+                        if expression.is_some() {
+                            self.define(name);
+                        }
+                    }
+                    StatementType::TupleDeclaration { names, .. } => {
+                        for name in names {
+                            self.declare(name, &s.location)
+                                .map_err(|e| vec![e])?;
+                            self.define(name);
+                        }
                     }
+                    _ => {}
                 }
                 Ok(())
             })
@@ -211,6 +250,22 @@ impl<'a> Pass<'a, HashMap<usize, usize>> for Resolver<'a> {
         self.pop_scope()
     }
 
+    fn pass_for_in(
+        &mut self,
+        variable: &'a str,
+        iterable: &'a Expression<'a>,
+        body: &'a Statement<'a>,
+        statement: &'a Statement<'a>,
+    ) -> Result<(), Vec<ProgramError<'a>>> {
+        self.pass_expression(iterable)?;
+        self.push_scope(HashMap::default());
+        self.declare(variable, &statement.location)
+            .map_err(|e| vec![e])?;
+        self.define(variable);
+        self.pass(body)?;
+        self.pop_scope()
+    }
+
     fn pass_variable_declaration(
         &mut self,
         name: &'a str,
@@ -230,6 +285,27 @@ impl<'a> Pass<'a, HashMap<usize, usize>> for Resolver<'a> {
         Ok(())
     }
 
+    fn pass_tuple_declaration(
+        &mut self,
+        names: &'a [&'a str],
+        expression: &'a Expression<'a>,
+        statement: &'a Statement<'a>,
+    ) -> Result<(), Vec<ProgramError<'a>>> {
+        if self.scopes.len() != 1 {
+            for name in names {
+                self.declare(name, &statement.location)
+                    .map_err(|e| vec![e])?;
+            }
+        }
+        self.pass_expression(expression)?;
+        if self.scopes.len() != 1 {
+            for name in names {
+                self.define(name);
+            }
+        }
+        Ok(())
+    }
+
     fn pass_class_declaration(
         &mut self,
         name: &'a str,
@@ -246,6 +322,7 @@ impl<'a> Pass<'a, HashMap<usize, usize>> for Resolver<'a> {
             if let ExpressionType::VariableLiteral { identifier } = &e.expression_type {
                 if *identifier == name {
                     return Err(vec![ProgramError {
+                        code: ErrorCode::TypeError,
                         message: "A class cannot inherit from itself.".to_owned(),
                         location: statement.location.clone(),
                     }]);
@@ -257,10 +334,12 @@ impl<'a> Pass<'a, HashMap<usize, usize>> for Resolver<'a> {
             }
         }
         self.push_scope(HashMap::default());
+        self.class_scopes.push(self.scopes.len());
         self.resolve_functions(methods, true)?;
         self.resolve_functions(getters, false)?;
         self.resolve_functions(setters, false)?;
         self.resolve_functions(static_methods, true)?;
+        self.class_scopes.pop();
         self.pop_scope()?;
         self.define(&name);
         Ok(())
@@ -277,6 +356,18 @@ impl<'a> Pass<'a, HashMap<usize, usize>> for Resolver<'a> {
         Ok(())
     }
 
+    fn pass_enum_declaration(
+        &mut self,
+        name: &'a str,
+        _variants: &'a [EnumVariant<'a>],
+        statement: &'a Statement<'a>,
+    ) -> Result<(), Vec<ProgramError<'a>>> {
+        self.declare(name, &statement.location)
+            .map_err(|e| vec![e])?;
+        self.define(name);
+        Ok(())
+    }
+
     fn pass_trait_implementation(
         &mut self,
         class_name: &'a Expression<'a>,
@@ -290,10 +381,12 @@ impl<'a> Pass<'a, HashMap<usize, usize>> for Resolver<'a> {
         self.pass_expression(class_name)?;
         self.pass_expression(trait_name)?;
         self.push_scope(HashMap::default());
+        self.class_scopes.push(self.scopes.len());
         self.resolve_functions(methods, true)?;
         self.resolve_functions(getters, false)?;
         self.resolve_functions(setters, false)?;
         self.resolve_functions(static_methods, true)?;
+        self.class_scopes.pop();
         self.pop_scope()
     }
 
@@ -308,9 +401,13 @@ impl<'a> Pass<'a, HashMap<usize, usize>> for Resolver<'a> {
         self.declare(name, &statement.location)
             .map_err(|e| vec![e])?;
         self.define(name);
+        let keyword_arguments = match &statement.statement_type {
+            StatementType::FunctionDeclaration { keyword_arguments, .. } => *keyword_arguments,
+            _ => None,
+        };
         let body = body.iter().map(|s| &(**s)).collect::<Vec<&Statement>>();
         self.resolve_function(
-            arguments, Some(context_variables), &body, &statement.location
+            arguments, keyword_arguments, Some(context_variables), &body, &statement.location
         )
     }
 
@@ -343,6 +440,22 @@ impl<'a> Pass<'a, HashMap<usize, usize>> for Resolver<'a> {
         self.resolve_local(expression, identifier).map_err(|e| vec![e])
     }
 
+    fn pass_tuple_assignment(
+        &mut self,
+        targets: &'a [Box<Expression<'a>>],
+        value: &'a Expression<'a>,
+        _expression: &'a Expression<'a>,
+    ) -> Result<(), Vec<ProgramError<'a>>> {
+        self.pass_expression(value)?;
+        for target in targets {
+            if let ExpressionType::VariableLiteral { identifier } = &target.expression_type {
+                self.define(identifier);
+                self.resolve_local(target, identifier).map_err(|e| vec![e])?;
+            }
+        }
+        Ok(())
+    }
+
     fn pass_anonymous_function(
         &mut self,
         arguments: &'a [&'a str],
@@ -350,6 +463,6 @@ impl<'a> Pass<'a, HashMap<usize, usize>> for Resolver<'a> {
         expression: &'a Expression<'a>,
     ) -> Result<(), Vec<ProgramError<'a>>> {
         let body = body.iter().collect::<Vec<&'a Statement>>();
-        self.resolve_function(arguments, None, &body, &expression.location)
+        self.resolve_function(arguments, None, None, &body, &expression.location)
     }
 }
@@ -38,6 +38,7 @@ pub enum TokenType<'a> {
     Semicolon,
     Slash,
     Star,
+    Percent,
     Bang,
     BangEqual,
     Equal,
@@ -56,6 +57,8 @@ pub enum TokenType<'a> {
     Or,
     Print,
     Question,
+    QuestionDot,
+    DoubleQuestion,
     Return,
     Var,
     While,
@@ -79,6 +82,10 @@ pub enum TokenType<'a> {
     Array,
     Module,
     Mod,
+    Enum,
+    Final,
+    In,
+    At,
     Bar,
     Identifier { name: &'a str },
     TokenLiteral { value: Literal<'a> },
@@ -90,8 +97,25 @@ pub struct Token<'a> {
     pub location: SourceCodeLocation<'a>,
 }
 
+/// Kind of a `ProgramError`, so that embedders can branch on it instead of on the message.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ErrorCode {
+    SyntaxError,
+    NameError,
+    TypeError,
+    IndexError,
+    DivisionByZero,
+    ArgumentError,
+    InvalidValue,
+    ImportError,
+    OverflowError,
+    LimitExceeded,
+    RuntimeError,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct ProgramError<'a> {
+    pub code: ErrorCode,
     pub location: SourceCodeLocation<'a>,
     pub message: String,
 }
@@ -115,12 +139,23 @@ pub struct Expression<'a> {
     pub expression_type: ExpressionType<'a>,
     pub location: SourceCodeLocation<'a>,
     id: usize,
+    loop_invariant: bool,
 }
 
 impl<'a> Expression<'a> {
     pub fn id(&self) -> usize {
         self.id
     }
+
+    /// Whether the expression gives the same value on every iteration of the loop that
+    /// contains it, as found by the interpreter before running the program.
+    pub fn is_loop_invariant(&self) -> bool {
+        self.loop_invariant
+    }
+
+    pub fn mark_loop_invariant(&mut self) {
+        self.loop_invariant = true;
+    }
 }
 
 #[derive(Clone)]
@@ -145,6 +180,7 @@ impl ExpressionFactory {
             expression_type,
             location,
             id: self.counter,
+            loop_invariant: false,
         };
         self.counter += 1;
         result
@@ -182,6 +218,10 @@ pub enum ExpressionType<'a> {
         callee: Box<Expression<'a>>,
         arguments: Vec<Box<Expression<'a>>>,
     },
+    NamedArgument {
+        name: &'a str,
+        value: Box<Expression<'a>>,
+    },
     Unary {
         operator: TokenType<'a>,
         operand: Box<Expression<'a>>,
@@ -203,6 +243,10 @@ pub enum ExpressionType<'a> {
         identifier: &'a str,
         expression: Box<Expression<'a>>,
     },
+    TupleAssignment {
+        targets: Vec<Box<Expression<'a>>>,
+        value: Box<Expression<'a>>,
+    },
     AnonymousFunction {
         arguments: Vec<&'a str>,
         body: Vec<Statement<'a>>,
@@ -211,6 +255,10 @@ pub enum ExpressionType<'a> {
         callee: Box<Expression<'a>>,
         property: &'a str,
     },
+    OptionalGet {
+        callee: Box<Expression<'a>>,
+        property: &'a str,
+    },
     Set {
         callee: Box<Expression<'a>>,
         property: &'a str,
@@ -219,10 +267,16 @@ pub enum ExpressionType<'a> {
     Array {
         elements: Vec<Box<Expression<'a>>>,
     },
+    Tuple {
+        elements: Vec<Box<Expression<'a>>>,
+    },
     RepeatedElementArray {
         element: Box<Expression<'a>>,
         length: Box<Expression<'a>>,
     },
+    Map {
+        entries: Vec<(Box<Expression<'a>>, Box<Expression<'a>>)>,
+    },
     ArrayElement {
         array: Box<Expression<'a>>,
         index: Box<Expression<'a>>,
@@ -241,8 +295,9 @@ pub enum ExpressionType<'a> {
 }
 
 impl<'a> Expression<'a> {
-    pub fn create_program_error(&self, message: &str) -> ProgramError<'a> {
+    pub fn create_program_error(&self, code: ErrorCode, message: &str) -> ProgramError<'a> {
         ProgramError {
+            code,
             location: self.location.clone(),
             message: message.to_owned(),
         }
@@ -290,6 +345,26 @@ pub struct FunctionHeader<'a> {
     pub arity: usize,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnumVariant<'a> {
+    pub name: &'a str,
+    pub fields: Vec<&'a str>,
+}
+
+/// Metadata attached to a function or class declaration with `@doc(value)` or
+/// `@attr(key, value)`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Annotation<'a> {
+    pub key: &'a str,
+    pub value: Literal<'a>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClassField<'a> {
+    pub name: &'a str,
+    pub field_type: Option<Type<'a>>,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum StatementType<'a> {
     Module {
@@ -320,20 +395,33 @@ pub enum StatementType<'a> {
     ClassDeclaration {
         name: &'a str,
         superclass: Option<Expression<'a>>,
+        fields: Vec<ClassField<'a>>,
+        is_final: bool,
+        annotations: Vec<Annotation<'a>>,
         methods: Vec<Box<Statement<'a>>>,
         static_methods: Vec<Box<Statement<'a>>>,
         getters: Vec<Box<Statement<'a>>>,
         setters: Vec<Box<Statement<'a>>>,
     },
+    EnumDeclaration {
+        name: &'a str,
+        variants: Vec<EnumVariant<'a>>,
+    },
     VariableDeclaration {
         expression: Option<Expression<'a>>,
         name: &'a str,
     },
+    TupleDeclaration {
+        names: Vec<&'a str>,
+        expression: Expression<'a>,
+    },
     FunctionDeclaration {
         name: &'a str,
         arguments: Vec<&'a str>,
+        keyword_arguments: Option<&'a str>,
         body: Vec<Box<Statement<'a>>>,
         context_variables: Vec<&'a str>,
+        annotations: Vec<Annotation<'a>>,
     },
     Block {
         body: Vec<Box<Statement<'a>>>,
@@ -347,6 +435,11 @@ pub enum StatementType<'a> {
         condition: Expression<'a>,
         action: Box<Statement<'a>>,
     },
+    ForIn {
+        variable: &'a str,
+        iterable: Expression<'a>,
+        body: Box<Statement<'a>>,
+    },
     Return {
         value: Option<Expression<'a>>,
     },
@@ -354,12 +447,15 @@ pub enum StatementType<'a> {
         name: &'a str,
     },
     Break,
+    /// Inserted by the `for` desugaring so every iteration gets fresh loop variables.
+    RebindLoopVariables,
     EOF,
 }
 
 impl<'a> Statement<'a> {
-    pub fn create_program_error(&self, message: &str) -> ProgramError<'a> {
+    pub fn create_program_error(&self, code: ErrorCode, message: &str) -> ProgramError<'a> {
         ProgramError {
+            code,
             location: self.location.clone(),
             message: message.to_owned(),
         }
@@ -412,6 +508,8 @@ pub trait MutPass<'a, R> {
             StatementType::Block { body } => self.pass_block(body)?,
             StatementType::VariableDeclaration { expression, name } =>
                 self.pass_variable_declaration(name, expression)?,
+            StatementType::TupleDeclaration { names, expression } =>
+                self.pass_tuple_declaration(names, expression)?,
             StatementType::ClassDeclaration {
                 name,
                 methods,
@@ -419,9 +517,16 @@ pub trait MutPass<'a, R> {
                 setters,
                 getters,
                 superclass,
-            } => self.pass_class_declaration(name, methods, static_methods, setters, getters, superclass)?,
+                fields,
+                ..
+            } => {
+                self.pass_class_declaration(name, methods, static_methods, setters, getters, superclass)?;
+                self.pass_class_fields(fields)?;
+            }
             StatementType::TraitDeclaration { name, .. } =>
                 self.pass_trait_declaration(name)?,
+            StatementType::EnumDeclaration { name, variants } =>
+                self.pass_enum_declaration(name, variants)?,
             StatementType::TraitImplementation {
                 class_name,
                 trait_name,
@@ -447,7 +552,10 @@ pub trait MutPass<'a, R> {
             StatementType::Return { value } => self.pass_return(value)?,
             StatementType::While { condition, action } =>
                 self.pass_while(condition, action)?,
+            StatementType::ForIn { variable, iterable, body } =>
+                self.pass_for_in(variable, iterable, body)?,
             StatementType::Break => {}
+            StatementType::RebindLoopVariables => {}
             StatementType::EOF => {}
         };
         Ok(())
@@ -462,7 +570,8 @@ pub trait MutPass<'a, R> {
             ExpressionType::ModuleLiteral {
                 module, field,
             } => self.pass_module_literal(module, field)?,
-            ExpressionType::Get { callee, .. } => self.pass_get(callee)?,
+            ExpressionType::Get { callee, .. } | ExpressionType::OptionalGet { callee, .. } =>
+                self.pass_get(callee)?,
             ExpressionType::Set { callee, value, .. } =>
                 self.pass_set(callee, value)?,
             ExpressionType::VariableLiteral { identifier } => self.pass_variable_literal(identifier, expression_id)?,
@@ -470,10 +579,13 @@ pub trait MutPass<'a, R> {
                 identifier,
                 expression: expression_value,
             } => self.pass_variable_assignment(identifier, expression_value, expression_id)?,
+            ExpressionType::TupleAssignment { targets, value } =>
+                self.pass_tuple_assignment(targets, value, expression_id)?,
             ExpressionType::Binary { left, right, operator } =>
                 self.pass_binary(left, right, operator)?,
             ExpressionType::Call { callee, arguments } =>
                 self.pass_call(callee, arguments, expression_id)?,
+            ExpressionType::NamedArgument { name, value } => self.pass_named_argument(name, value)?,
             ExpressionType::Grouping { expression } => self.pass_grouping(expression)?,
             ExpressionType::Conditional {
                 condition,
@@ -488,7 +600,9 @@ pub trait MutPass<'a, R> {
                 self.pass_anonymous_function(arguments, body, expression_id)?,
             ExpressionType::RepeatedElementArray { element, length } =>
                 self.pass_repeated_element_array(element, length)?,
-            ExpressionType::Array { elements } => self.pass_array(elements)?,
+            ExpressionType::Array { elements } | ExpressionType::Tuple { elements } =>
+                self.pass_array(elements)?,
+            ExpressionType::Map { entries } => self.pass_map(entries)?,
             ExpressionType::ArrayElement { array, index } =>
                 self.pass_array_element(array, index)?,
             ExpressionType::ArrayElementSet {
@@ -559,6 +673,14 @@ pub trait MutPass<'a, R> {
         Ok(())
     }
 
+    fn pass_tuple_declaration(
+        &mut self,
+        _names: &'a mut [&'a str],
+        expression: &'a mut Expression<'a>,
+    ) -> Result<(), Vec<ProgramError<'a>>> {
+        self.pass_expression(expression)
+    }
+
     fn pass_class_declaration(
         &mut self,
         _name: &'a str,
@@ -579,6 +701,15 @@ pub trait MutPass<'a, R> {
         Ok(())
     }
 
+    fn pass_class_fields(&mut self, fields: &'a mut [ClassField<'a>]) -> Result<(), Vec<ProgramError<'a>>> {
+        for field in fields.iter_mut() {
+            if let Some(Type::UserDefined(checked_type)) = &mut field.field_type {
+                self.pass_expression(checked_type)?;
+            }
+        }
+        Ok(())
+    }
+
     fn pass_trait_declaration(
         &mut self,
         _name: &'a str,
@@ -586,6 +717,14 @@ pub trait MutPass<'a, R> {
         Ok(())
     }
 
+    fn pass_enum_declaration(
+        &mut self,
+        _name: &'a str,
+        _variants: &'a mut [EnumVariant<'a>],
+    ) -> Result<(), Vec<ProgramError<'a>>> {
+        Ok(())
+    }
+
     fn pass_trait_implementation(
         &mut self,
         class_name: &'a mut Expression<'a>,
@@ -652,6 +791,16 @@ pub trait MutPass<'a, R> {
         self.pass(action)
     }
 
+    fn pass_for_in(
+        &mut self,
+        _variable: &'a str,
+        iterable: &'a mut Expression<'a>,
+        body: &'a mut Statement<'a>,
+    ) -> Result<(), Vec<ProgramError<'a>>> {
+        self.pass_expression(iterable)?;
+        self.pass(body)
+    }
+
     fn pass_checked_type(
         &mut self,
         value: &'a mut Expression<'a>,
@@ -702,6 +851,19 @@ pub trait MutPass<'a, R> {
         self.pass_expression(expression)
     }
 
+    fn pass_tuple_assignment(
+        &mut self,
+        targets: &'a mut [Box<Expression<'a>>],
+        value: &'a mut Expression<'a>,
+        _expression_id: usize,
+    ) -> Result<(), Vec<ProgramError<'a>>> {
+        self.pass_expression(value)?;
+        for target in targets {
+            self.pass_expression(target)?;
+        }
+        Ok(())
+    }
+
     fn pass_binary(
         &mut self,
         left: &'a mut Expression<'a>,
@@ -726,6 +888,10 @@ pub trait MutPass<'a, R> {
         Ok(())
     }
 
+    fn pass_named_argument(&mut self, _name: &'a mut &'a str, value: &'a mut Expression<'a>) -> Result<(), Vec<ProgramError<'a>>> {
+        self.pass_expression(value)
+    }
+
     fn pass_grouping(&mut self, expression: &'a mut Expression<'a>) -> Result<(), Vec<ProgramError<'a>>> {
         self.pass_expression(expression)
     }
@@ -773,6 +939,14 @@ pub trait MutPass<'a, R> {
         Ok(())
     }
 
+    fn pass_map(&mut self, entries: &'a mut [(Box<Expression<'a>>, Box<Expression<'a>>)]) -> Result<(), Vec<ProgramError<'a>>> {
+        for (key, value) in entries {
+            self.pass_expression(key)?;
+            self.pass_expression(value)?;
+        }
+        Ok(())
+    }
+
     fn pass_array_element(&mut self, array: &'a mut Expression<'a>, index: &'a mut Expression<'a>) -> Result<(), Vec<ProgramError<'a>>> {
         self.pass_expression(array)?;
         self.pass_expression(index)
@@ -799,6 +973,8 @@ pub trait Pass<'a, R> {
             StatementType::Block { body } => self.pass_block(body, statement.id)?,
             StatementType::VariableDeclaration { expression, name } =>
                 self.pass_variable_declaration(name, expression, statement)?,
+            StatementType::TupleDeclaration { names, expression } =>
+                self.pass_tuple_declaration(names, expression, statement)?,
             StatementType::ClassDeclaration {
                 name,
                 methods,
@@ -806,9 +982,16 @@ pub trait Pass<'a, R> {
                 setters,
                 getters,
                 superclass,
-            } => self.pass_class_declaration(name, methods, static_methods, setters, getters, superclass, statement)?,
+                fields,
+                ..
+            } => {
+                self.pass_class_declaration(name, methods, static_methods, setters, getters, superclass, statement)?;
+                self.pass_class_fields(fields)?;
+            }
             StatementType::TraitDeclaration { name, .. } =>
                 self.pass_trait_declaration(name, statement)?,
+            StatementType::EnumDeclaration { name, variants } =>
+                self.pass_enum_declaration(name, variants, statement)?,
             StatementType::TraitImplementation {
                 class_name,
                 trait_name,
@@ -823,6 +1006,7 @@ pub trait Pass<'a, R> {
                 arguments,
                 body,
                 context_variables,
+                ..
             } => self.pass_function_declaration(name, arguments, body, statement, context_variables)?,
             StatementType::Expression { expression } => self.pass_expression_statement(expression)?,
             StatementType::If {
@@ -834,7 +1018,10 @@ pub trait Pass<'a, R> {
             StatementType::Return { value } => self.pass_return(value)?,
             StatementType::While { condition, action } =>
                 self.pass_while(condition, action)?,
+            StatementType::ForIn { variable, iterable, body } =>
+                self.pass_for_in(variable, iterable, body, statement)?,
             StatementType::Break => {}
+            StatementType::RebindLoopVariables => {}
             StatementType::EOF => {}
         };
         Ok(())
@@ -849,7 +1036,8 @@ pub trait Pass<'a, R> {
             ExpressionType::ModuleLiteral {
                 module, field,
             } => self.pass_module_literal(module, field, expression)?,
-            ExpressionType::Get { callee, .. } => self.pass_get(callee)?,
+            ExpressionType::Get { callee, .. } | ExpressionType::OptionalGet { callee, .. } =>
+                self.pass_get(callee)?,
             ExpressionType::Set { callee, value, .. } =>
                 self.pass_set(callee, value)?,
             ExpressionType::VariableLiteral { identifier } => self.pass_variable_literal(identifier, expression)?,
@@ -857,10 +1045,13 @@ pub trait Pass<'a, R> {
                 identifier,
                 expression: expression_value,
             } => self.pass_variable_assignment(identifier, expression_value, expression)?,
+            ExpressionType::TupleAssignment { targets, value } =>
+                self.pass_tuple_assignment(targets, value, expression)?,
             ExpressionType::Binary { left, right, operator } =>
                 self.pass_binary(left, right, operator)?,
             ExpressionType::Call { callee, arguments } =>
                 self.pass_call(callee, arguments, expression.id())?,
+            ExpressionType::NamedArgument { name, value } => self.pass_named_argument(name, value)?,
             ExpressionType::Grouping { expression } => self.pass_grouping(expression)?,
             ExpressionType::Conditional {
                 condition,
@@ -875,7 +1066,9 @@ pub trait Pass<'a, R> {
                 self.pass_anonymous_function(arguments, body, expression)?,
             ExpressionType::RepeatedElementArray { element, length } =>
                 self.pass_repeated_element_array(element, length)?,
-            ExpressionType::Array { elements } => self.pass_array(elements)?,
+            ExpressionType::Array { elements } | ExpressionType::Tuple { elements } =>
+                self.pass_array(elements)?,
+            ExpressionType::Map { entries } => self.pass_map(entries)?,
             ExpressionType::ArrayElement { array, index } =>
                 self.pass_array_element(array, index)?,
             ExpressionType::ArrayElementSet {
@@ -949,6 +1142,15 @@ pub trait Pass<'a, R> {
         Ok(())
     }
 
+    fn pass_tuple_declaration(
+        &mut self,
+        _names: &'a [&'a str],
+        expression: &'a Expression<'a>,
+        _statement: &'a Statement<'a>,
+    ) -> Result<(), Vec<ProgramError<'a>>> {
+        self.pass_expression(expression)
+    }
+
     fn pass_class_declaration(
         &mut self,
         _name: &'a str,
@@ -970,6 +1172,15 @@ pub trait Pass<'a, R> {
         Ok(())
     }
 
+    fn pass_class_fields(&mut self, fields: &'a [ClassField<'a>]) -> Result<(), Vec<ProgramError<'a>>> {
+        for field in fields {
+            if let Some(Type::UserDefined(checked_type)) = &field.field_type {
+                self.pass_expression(checked_type)?;
+            }
+        }
+        Ok(())
+    }
+
     fn pass_trait_declaration(
         &mut self,
         _name: &'a str,
@@ -978,6 +1189,15 @@ pub trait Pass<'a, R> {
         Ok(())
     }
 
+    fn pass_enum_declaration(
+        &mut self,
+        _name: &'a str,
+        _variants: &'a [EnumVariant<'a>],
+        _statement: &'a Statement<'a>,
+    ) -> Result<(), Vec<ProgramError<'a>>> {
+        Ok(())
+    }
+
     fn pass_trait_implementation(
         &mut self,
         class_name: &'a Expression<'a>,
@@ -1047,6 +1267,17 @@ pub trait Pass<'a, R> {
         self.pass(action)
     }
 
+    fn pass_for_in(
+        &mut self,
+        _variable: &'a str,
+        iterable: &'a Expression<'a>,
+        body: &'a Statement<'a>,
+        _statement: &'a Statement<'a>,
+    ) -> Result<(), Vec<ProgramError<'a>>> {
+        self.pass_expression(iterable)?;
+        self.pass(body)
+    }
+
     fn pass_checked_type(
         &mut self,
         value: &'a Expression<'a>,
@@ -1098,6 +1329,19 @@ pub trait Pass<'a, R> {
         self.pass_expression(expression)
     }
 
+    fn pass_tuple_assignment(
+        &mut self,
+        targets: &'a [Box<Expression<'a>>],
+        value: &'a Expression<'a>,
+        _expression: &'a Expression<'a>,
+    ) -> Result<(), Vec<ProgramError<'a>>> {
+        self.pass_expression(value)?;
+        for target in targets {
+            self.pass_expression(target)?;
+        }
+        Ok(())
+    }
+
     fn pass_binary(
         &mut self,
         left: &'a Expression<'a>,
@@ -1122,6 +1366,10 @@ pub trait Pass<'a, R> {
         Ok(())
     }
 
+    fn pass_named_argument(&mut self, _name: &'a str, value: &'a Expression<'a>) -> Result<(), Vec<ProgramError<'a>>> {
+        self.pass_expression(value)
+    }
+
     fn pass_grouping(&mut self, expression: &'a Expression<'a>) -> Result<(), Vec<ProgramError<'a>>> {
         self.pass_expression(expression)
     }
@@ -1169,6 +1417,14 @@ pub trait Pass<'a, R> {
         Ok(())
     }
 
+    fn pass_map(&mut self, entries: &'a [(Box<Expression<'a>>, Box<Expression<'a>>)]) -> Result<(), Vec<ProgramError<'a>>> {
+        for (key, value) in entries {
+            self.pass_expression(key)?;
+            self.pass_expression(value)?;
+        }
+        Ok(())
+    }
+
     fn pass_array_element(&mut self, array: &'a Expression<'a>, index: &'a Expression<'a>) -> Result<(), Vec<ProgramError<'a>>> {
         self.pass_expression(array)?;
         self.pass_expression(index)
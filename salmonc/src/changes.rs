@@ -35,6 +35,8 @@ impl<'a> MutPass<'a, ()> for Changes<'a> {
             StatementType::Block { body } => self.pass_block(body)?,
             StatementType::VariableDeclaration { expression, name } =>
                 self.pass_variable_declaration(name, expression)?,
+            StatementType::TupleDeclaration { names, expression } =>
+                self.pass_tuple_declaration(names, expression)?,
             StatementType::ClassDeclaration {
                 name,
                 methods,
@@ -42,9 +44,16 @@ impl<'a> MutPass<'a, ()> for Changes<'a> {
                 setters,
                 getters,
                 superclass,
-            } => self.pass_class_declaration(name, methods, static_methods, setters, getters, superclass)?,
+                fields,
+                ..
+            } => {
+                self.pass_class_declaration(name, methods, static_methods, setters, getters, superclass)?;
+                self.pass_class_fields(fields)?;
+            }
             StatementType::TraitDeclaration { name, .. } =>
                 self.pass_trait_declaration(name)?,
+            StatementType::EnumDeclaration { name, variants } =>
+                self.pass_enum_declaration(name, variants)?,
             StatementType::TraitImplementation {
                 class_name,
                 trait_name,
@@ -70,7 +79,10 @@ impl<'a> MutPass<'a, ()> for Changes<'a> {
             StatementType::Return { value } => self.pass_return(value)?,
             StatementType::While { condition, action } =>
                 self.pass_while(condition, action)?,
+            StatementType::ForIn { variable, iterable, body } =>
+                self.pass_for_in(variable, iterable, body)?,
             StatementType::Break => {}
+            StatementType::RebindLoopVariables => {}
             StatementType::EOF => {}
         };
         Ok(())
@@ -89,7 +101,8 @@ impl<'a> MutPass<'a, ()> for Changes<'a> {
             ExpressionType::ModuleLiteral {
                 module, field,
             } => self.pass_module_literal(module, field)?,
-            ExpressionType::Get { callee, .. } => self.pass_get(callee)?,
+            ExpressionType::Get { callee, .. } | ExpressionType::OptionalGet { callee, .. } =>
+                self.pass_get(callee)?,
             ExpressionType::Set { callee, value, .. } =>
                 self.pass_set(callee, value)?,
             ExpressionType::VariableLiteral { identifier } => self.pass_variable_literal(identifier, expression_id)?,
@@ -97,10 +110,13 @@ impl<'a> MutPass<'a, ()> for Changes<'a> {
                 identifier,
                 expression: expression_value,
             } => self.pass_variable_assignment(identifier, expression_value, expression_id)?,
+            ExpressionType::TupleAssignment { targets, value } =>
+                self.pass_tuple_assignment(targets, value, expression_id)?,
             ExpressionType::Binary { left, right, operator } =>
                 self.pass_binary(left, right, operator)?,
             ExpressionType::Call { callee, arguments } =>
                 self.pass_call(callee, arguments, expression_id)?,
+            ExpressionType::NamedArgument { name, value } => self.pass_named_argument(name, value)?,
             ExpressionType::Grouping { expression } => self.pass_grouping(expression)?,
             ExpressionType::Conditional {
                 condition,
@@ -115,7 +131,9 @@ impl<'a> MutPass<'a, ()> for Changes<'a> {
                 self.pass_anonymous_function(arguments, body, expression_id)?,
             ExpressionType::RepeatedElementArray { element, length } =>
                 self.pass_repeated_element_array(element, length)?,
-            ExpressionType::Array { elements } => self.pass_array(elements)?,
+            ExpressionType::Array { elements } | ExpressionType::Tuple { elements } =>
+                self.pass_array(elements)?,
+            ExpressionType::Map { entries } => self.pass_map(entries)?,
             ExpressionType::ArrayElement { array, index } =>
                 self.pass_array_element(array, index)?,
             ExpressionType::ArrayElementSet {
@@ -1,5 +1,5 @@
 use ahash::{AHashMap as HashMap};
-use parser::types::{Pass, ProgramError, Statement, Expression, Literal, SourceCodeLocation, TokenType, DataKeyword, Type, StatementType, ExpressionType};
+use parser::types::{ErrorCode, Pass, ProgramError, Statement, Expression, Literal, SourceCodeLocation, TokenType, DataKeyword, Type, StatementType, ExpressionType};
 use smoked::instruction::{Instruction, InstructionType};
 
 #[derive(Debug, PartialEq)]
@@ -178,10 +178,10 @@ impl<'a> Compiler<'a> {
                     ));
                     members.push(*identifier);
                 } else {
-                    return Err(self.create_single_error("Expected variable literal on class member".to_string()))
+                    return Err(self.create_single_error(ErrorCode::SyntaxError, "Expected variable literal on class member".to_string()))
                 }
             } else {
-                return Err(self.create_single_error("Expected expression statement on class member".to_string()))
+                return Err(self.create_single_error(ErrorCode::SyntaxError, "Expected expression statement on class member".to_string()))
             }
         }
         Ok(members)
@@ -204,8 +204,9 @@ impl<'a> Compiler<'a> {
         Ok(())
     }
 
-    fn create_single_error(&self, message: String) -> Vec<ProgramError<'a>> {
+    fn create_single_error(&self, code: ErrorCode, message: String) -> Vec<ProgramError<'a>> {
         vec![ProgramError {
+            code,
             location: self.locations.last().unwrap().clone(),
             message
         }]
@@ -306,7 +307,7 @@ impl<'a> Pass<'a, Vec<Instruction>> for Compiler<'a> {
 
     fn pass_uplift_class_variables(&mut self, name: &'a str) -> Result<(), Vec<ProgramError<'a>>> {
         let nil_constant = self.constant_from_literal(ConstantValues::Literal(Literal::Keyword(DataKeyword::Nil)));
-        let members = self.class_members.get(name).ok_or(self.create_single_error("Class not declared yet".to_string()))?;
+        let members = self.class_members.get(name).ok_or(self.create_single_error(ErrorCode::NameError, "Class not declared yet".to_string()))?;
         let mut names = vec![];
         for ms in vec![&members.methods, &members.setters, &members.getters, &members.static_methods] {
             for m in ms {
@@ -364,6 +365,7 @@ impl<'a> Pass<'a, Vec<Instruction>> for Compiler<'a> {
             *context_variables
         } else {
             return Err(vec![ProgramError {
+                code: ErrorCode::TypeError,
                 location: self.locations.last().unwrap().clone(),
                 message: "Constant is not a function".to_string()
             }]);
@@ -380,6 +382,7 @@ impl<'a> Pass<'a, Vec<Instruction>> for Compiler<'a> {
                 });
             } else {
                 return Err(vec![ProgramError {
+                    code: ErrorCode::NameError,
                     location: self.locations.last().unwrap().clone(),
                     message: format!("Context variable {} was not found", context_variable),
                 }]);
@@ -586,6 +589,7 @@ impl<'a> Pass<'a, Vec<Instruction>> for Compiler<'a> {
             Type::Array => 6,
             _ => {
                 return Err(vec![ProgramError {
+                    code: ErrorCode::TypeError,
                     location: self.locations.last().unwrap().clone(),
                     message: format!("Type checking for {:?} not implemented yet", checked_type),
                 }]);
@@ -619,6 +623,7 @@ impl<'a> Pass<'a, Vec<Instruction>> for Compiler<'a> {
             Ok(())
         } else {
             Err(vec![ProgramError {
+                code: ErrorCode::NameError,
                 message: format!("Variable literal {} not declared", identifier),
                 location: self.locations.last().unwrap().clone(),
             }])
@@ -645,6 +650,7 @@ impl<'a> Pass<'a, Vec<Instruction>> for Compiler<'a> {
             Ok(())
         } else {
             Err(vec![ProgramError {
+                code: ErrorCode::NameError,
                 message: format!("Variable {} not declared on assignment", identifier),
                 location: self.locations.last().unwrap().clone(),
             }])
@@ -719,6 +725,7 @@ impl<'a> Pass<'a, Vec<Instruction>> for Compiler<'a> {
                 });
             },
             t => Err(vec![ProgramError {
+                code: ErrorCode::SyntaxError,
                 message: format!("Invalid operator {:?}", t),
                 location: self.locations.last().unwrap().clone(),
             }])?,
@@ -798,6 +805,7 @@ impl<'a> Pass<'a, Vec<Instruction>> for Compiler<'a> {
                 location: self.locations.len() - 1,
             }),
             t => Err(vec![ProgramError {
+                code: ErrorCode::SyntaxError,
                 message: format!("Invalid unary operator {:?}", t),
                 location: self.locations.last().unwrap().clone(),
             }])?,
@@ -54,8 +54,10 @@ impl<'a> LambdaLifting<'a> {
             StatementType::FunctionDeclaration {
                 name: new_function_name,
                 arguments: arguments.map(|a| a.to_vec()).unwrap_or(vec![]),
+                keyword_arguments: None,
                 context_variables: vec![],
                 body,
+                annotations: vec![],
             },
         );
         /*
@@ -165,6 +167,10 @@ impl<'a> Pass<'a, LambdaLiftingResult<'a>> for LambdaLifting<'a> {
         statement: &'a Statement<'a>,
         _context_variables: &'a [&'a str],
     ) -> Result<(), Vec<ProgramError<'a>>> {
+        let keyword_arguments = match &statement.statement_type {
+            StatementType::FunctionDeclaration { keyword_arguments, .. } => *keyword_arguments,
+            _ => None,
+        };
         let new_function_name = if self.change_name {
             let new_function_name = leak_reference(format!("@function{}", self.function_counter));
             self.function_counter += 1;
@@ -206,8 +212,10 @@ impl<'a> Pass<'a, LambdaLiftingResult<'a>> for LambdaLifting<'a> {
             StatementType::FunctionDeclaration {
                 name: new_function_name,
                 arguments: arguments.to_vec(),
+                keyword_arguments,
                 body: new_body,
                 context_variables: missed_locals,
+                annotations: vec![],
             },
         ));
         Ok(())
@@ -242,6 +250,9 @@ impl<'a> Pass<'a, LambdaLiftingResult<'a>> for LambdaLifting<'a> {
                 static_methods: new_static_methods,
                 getters: new_getters,
                 setters: new_setters,
+                fields: vec![],
+                is_final: false,
+                annotations: vec![],
             },
         ));
         Ok(())
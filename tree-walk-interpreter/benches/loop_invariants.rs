@@ -0,0 +1,46 @@
+use tree_walk_interpreter::interpreter::Interpreter;
+use tree_walk_interpreter::invariants::hoist_loop_invariants;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use parser::lexer::Lexer;
+use parser::parser::Parser;
+use parser::resolver::Resolver;
+use parser::types::Pass;
+
+const PROGRAM: &str = "
+var width = 640;
+var height = 480;
+var total = 0;
+for (var i = 0; i < 10000; i = i + 1) {
+    total = total + (width * height) / (width + height) + i % 7;
+}";
+
+fn tight_loop(hoist: bool) {
+    let mut lexer = Lexer::new(PROGRAM, "stdin");
+    let mut ss = lexer
+        .parse()
+        .and_then(|ts| {
+            let parser = Parser::new(ts.into_iter().peekable());
+            parser.parse()
+        }).unwrap().0;
+    let mut resolver = Resolver::new();
+    let locals = resolver.run(&ss).unwrap();
+    if hoist {
+        hoist_loop_invariants(&mut ss);
+    }
+    let paths = vec![];
+    let interpreter = Interpreter::new(&paths, "");
+    interpreter.locals.replace(locals);
+    interpreter.run(&ss).unwrap();
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    c.bench_function("tight loop", |b| {
+        b.iter(|| tight_loop(black_box(false)))
+    });
+    c.bench_function("tight loop with hoisted invariants", |b| {
+        b.iter(|| tight_loop(black_box(true)))
+    });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
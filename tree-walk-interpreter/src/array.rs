@@ -0,0 +1,351 @@
+use crate::interpreter::{EvaluationResult, Interpreter};
+use crate::native::NativeFunction;
+use crate::value::{LoxArray, LoxMap, MapKey, OrderedMap, Value};
+use parser::types::{ErrorCode, ProgramError, SourceCodeLocation};
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+fn receiver<'a, 'b>(values: &'b [Value<'a>]) -> &'b Rc<RefCell<LoxArray<'a>>> {
+    match values.first() {
+        Some(Value::Array(a)) => a,
+        _ => unreachable!("Array methods are only bound to arrays"),
+    }
+}
+
+fn compare<'a>(
+    interpreter: &'a Interpreter<'a>,
+    comparator: Option<&Value<'a>>,
+    left: &Value<'a>,
+    right: &Value<'a>,
+    location: &SourceCodeLocation<'a>,
+) -> Result<Ordering, ProgramError<'a>> {
+    match comparator {
+        Some(comparator) => {
+            let result = interpreter.call_value(
+                comparator.clone(),
+                vec![left.clone(), right.clone()],
+                location,
+            )?;
+            match result {
+                Value::Integer { value } => Ok(value.cmp(&0)),
+                Value::Float { value } if !value.is_nan() => Ok(value.partial_cmp(&0.0).unwrap()),
+                _ => Err(ProgramError {
+                    code: ErrorCode::TypeError,
+                    location: location.clone(),
+                    message: "Comparator must return a number".to_owned(),
+                }),
+            }
+        }
+        None => interpreter.compare_values(left, right).ok_or_else(|| ProgramError {
+            code: ErrorCode::TypeError,
+            location: location.clone(),
+            message: format!("Can't compare {} with {}", left, right),
+        }),
+    }
+}
+
+/// Looks for `values[1]` in a sorted array, comparing with the optional comparator in
+/// `values[2]`. Returns the index of a match or `-(insertion point + 1)` when absent. The
+/// result is unspecified if the array isn't sorted or the comparator changes it.
+fn binary_search<'a>(
+    interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    let array = receiver(values);
+    let (mut low, mut high) = (0, array.borrow().elements.len());
+    while low < high {
+        let middle = low + (high - low) / 2;
+        // The comparator can use the array, so it isn't borrowed while comparing.
+        let element = match array.borrow().elements.get(middle) {
+            Some(element) => *element.clone(),
+            None => break,
+        };
+        match compare(interpreter, values.get(2), &element, &values[1], location)? {
+            Ordering::Less => low = middle + 1,
+            Ordering::Greater => high = middle,
+            Ordering::Equal => return Ok(Value::Integer { value: middle as _ }),
+        }
+    }
+    Ok(Value::Integer {
+        value: -(low as i64 + 1),
+    })
+}
+
+/// Appends `values[1]` to the array and returns the new length.
+fn push<'a>(
+    interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    let mut array = receiver(values).borrow_mut();
+    interpreter.check_collection_size(array.capacity + 1, location)?;
+    array.elements.push(Box::new(values[1].clone()));
+    array.capacity += 1;
+    Ok(Value::Integer {
+        value: array.capacity as _,
+    })
+}
+
+/// Removes the first element of the array and returns it, `nil` if the array is empty.
+fn shift<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    _location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    let mut array = receiver(values).borrow_mut();
+    if array.elements.is_empty() {
+        return Ok(Value::Nil);
+    }
+    array.capacity -= 1;
+    Ok(*array.elements.remove(0))
+}
+
+/// Removes the last element of the array and returns it, `nil` if the array is empty.
+fn pop<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    _location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    let mut array = receiver(values).borrow_mut();
+    match array.elements.pop() {
+        Some(element) => {
+            array.capacity -= 1;
+            Ok(*element)
+        }
+        None => Ok(Value::Nil),
+    }
+}
+
+/// Returns the last element of the array without removing it, `nil` if the array is empty.
+fn peek<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    _location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    Ok(receiver(values).borrow().elements.last().map(|e| *e.clone()).unwrap_or(Value::Nil))
+}
+
+/// Returns the first element of the array without removing it, `nil` if the array is empty.
+fn peek_front<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    _location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    Ok(receiver(values).borrow().elements.first().map(|e| *e.clone()).unwrap_or(Value::Nil))
+}
+
+/// Inserts `values[1]` at the front of the array and returns the new length.
+fn unshift<'a>(
+    interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    let mut array = receiver(values).borrow_mut();
+    interpreter.check_collection_size(array.capacity + 1, location)?;
+    array.elements.insert(0, Box::new(values[1].clone()));
+    array.capacity += 1;
+    Ok(Value::Integer {
+        value: array.capacity as _,
+    })
+}
+
+/// Rotates the array in place `values[1]` positions to the left, or to the right when
+/// negative.
+fn rotate<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    let positions = match values[1] {
+        Value::Integer { value } => value,
+        _ => {
+            return Err(ProgramError {
+                code: ErrorCode::TypeError,
+                location: location.clone(),
+                message: "Rotation should be an integer".to_owned(),
+            })
+        }
+    };
+    let mut array = receiver(values).borrow_mut();
+    let length = array.elements.len();
+    if length > 0 {
+        let positions = positions.rem_euclid(length as i64) as usize;
+        array.elements.rotate_left(positions);
+    }
+    Ok(Value::Nil)
+}
+
+/// Changes the length of the array, truncating it or filling the new positions with the
+/// optional second argument (`nil` by default).
+fn resize<'a>(
+    interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    let length = match values[1] {
+        Value::Integer { value } if value >= 0 => value as usize,
+        _ => {
+            return Err(ProgramError {
+                code: ErrorCode::InvalidValue,
+                location: location.clone(),
+                message: "Array length should be a positive integer".to_owned(),
+            })
+        }
+    };
+    interpreter.check_collection_size(length, location)?;
+    let fill = values.get(2).cloned().unwrap_or(Value::Nil);
+    let mut array = receiver(values).borrow_mut();
+    array.elements.resize(length, Box::new(fill));
+    array.capacity = length;
+    Ok(Value::Nil)
+}
+
+/// Returns a new array with the first occurrence of every element, compared with `==`.
+fn unique<'a>(
+    interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    let mut elements: Vec<Value<'a>> = vec![];
+    for e in receiver(values).borrow().elements.iter() {
+        if !elements.iter().any(|u| interpreter.values_equal(u, e)) {
+            elements.push(*e.clone());
+        }
+    }
+    interpreter.check_collection_size(elements.len(), location)?;
+    Ok(Value::new_array(elements))
+}
+
+/// Removes consecutive repeated elements in place, compared with `==`.
+fn dedup<'a>(
+    interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    _location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    // Elements can hold the array itself, so they're compared without holding the borrow.
+    let mut elements = receiver(values).borrow().elements.clone();
+    elements.dedup_by(|e, previous| interpreter.values_equal(previous, e));
+    let mut array = receiver(values).borrow_mut();
+    array.capacity = elements.len();
+    array.elements = elements;
+    Ok(Value::Nil)
+}
+
+fn expect_function<'a>(
+    value: &Value<'a>,
+    name: &str,
+    location: &SourceCodeLocation<'a>,
+) -> Result<(), ProgramError<'a>> {
+    if matches!(
+        value,
+        Value::Function(_) | Value::NativeFunction(_) | Value::NativeMethod(..) | Value::Method(..) | Value::WeakMethod(..)
+    ) {
+        Ok(())
+    } else {
+        Err(ProgramError {
+            code: ErrorCode::TypeError,
+            location: location.clone(),
+            message: format!("{} should be a function", name),
+        })
+    }
+}
+
+fn elements<'a>(values: &[Value<'a>]) -> Vec<Value<'a>> {
+    receiver(values)
+        .borrow()
+        .elements
+        .iter()
+        .map(|e| *e.clone())
+        .collect()
+}
+
+/// Length of the leading run of elements for which the predicate in `values[1]` returns a
+/// truthy value.
+fn prefix_length<'a>(
+    interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> Result<(Vec<Value<'a>>, usize), ProgramError<'a>> {
+    let predicate = &values[1];
+    expect_function(predicate, "Predicate", location)?;
+    let elements = elements(values);
+    let mut length = 0;
+    for element in elements.iter() {
+        let result = interpreter.call_value(predicate.clone(), vec![element.clone()], location)?;
+        if !interpreter.is_truthy(&result, location)? {
+            break;
+        }
+        length += 1;
+    }
+    Ok((elements, length))
+}
+
+/// Returns a new array with the leading elements for which the predicate holds.
+fn take_while<'a>(
+    interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    let (mut elements, length) = prefix_length(interpreter, values, location)?;
+    elements.truncate(length);
+    Ok(Value::new_array(elements))
+}
+
+/// Returns a new array without the leading elements for which the predicate holds.
+fn drop_while<'a>(
+    interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    let (mut elements, length) = prefix_length(interpreter, values, location)?;
+    Ok(Value::new_array(elements.split_off(length)))
+}
+
+/// Returns a map from every key produced by the function in `values[1]` to the array of
+/// elements that produced it, in the order they appear.
+fn group_by<'a>(
+    interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    let key_function = &values[1];
+    expect_function(key_function, "Key function", location)?;
+    let mut groups: OrderedMap<MapKey, Vec<Value<'a>>> = OrderedMap::default();
+    for element in elements(values) {
+        let key = interpreter.call_value(key_function.clone(), vec![element.clone()], location)?;
+        let key = MapKey::try_from(&key).map_err(|e| e.into_program_error(location))?;
+        match groups.get_mut(&key) {
+            Some(group) => group.push(element),
+            None => groups.insert(key, vec![element]),
+        }
+    }
+    Ok(Value::new_map(
+        groups
+            .into_iter()
+            .map(|(key, group)| (key, Value::new_array(group)))
+            .collect::<LoxMap<'a>>(),
+    ))
+}
+
+pub fn array_method<'a>(name: &str) -> Option<NativeFunction<'a>> {
+    match name {
+        "binary_search" => Some(NativeFunction { name: "binary_search", arity: 1..=2, function: binary_search }),
+        "push" => Some(NativeFunction { name: "push", arity: 1..=1, function: push }),
+        "pop" => Some(NativeFunction { name: "pop", arity: 0..=0, function: pop }),
+        "peek" => Some(NativeFunction { name: "peek", arity: 0..=0, function: peek }),
+        "peek_front" => Some(NativeFunction { name: "peek_front", arity: 0..=0, function: peek_front }),
+        "shift" => Some(NativeFunction { name: "shift", arity: 0..=0, function: shift }),
+        "unshift" => Some(NativeFunction { name: "unshift", arity: 1..=1, function: unshift }),
+        "rotate" => Some(NativeFunction { name: "rotate", arity: 1..=1, function: rotate }),
+        "resize" => Some(NativeFunction { name: "resize", arity: 1..=2, function: resize }),
+        "unique" => Some(NativeFunction { name: "unique", arity: 0..=0, function: unique }),
+        "dedup" => Some(NativeFunction { name: "dedup", arity: 0..=0, function: dedup }),
+        "take_while" => Some(NativeFunction { name: "take_while", arity: 1..=1, function: take_while }),
+        "drop_while" => Some(NativeFunction { name: "drop_while", arity: 1..=1, function: drop_while }),
+        "group_by" => Some(NativeFunction { name: "group_by", arity: 1..=1, function: group_by }),
+        _ => None,
+    }
+}
@@ -0,0 +1,699 @@
+use crate::class::LoxObject;
+use crate::interpreter::{EvaluationResult, Interpreter};
+use crate::template;
+use crate::value::{LoxMap, MapKey, Value};
+use parser::types::{ErrorCode, ProgramError, SourceCodeLocation};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn string_argument<'a>(value: &Value<'a>, location: &SourceCodeLocation<'a>) -> Result<String, ProgramError<'a>> {
+    value.clone().coerce_string().map_err(|e| e.into_program_error(location))
+}
+
+fn bytes_to_string<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    let type_error = || ProgramError {
+        code: ErrorCode::TypeError,
+        location: location.clone(),
+        message: "Type error! Expecting an array of bytes!".to_owned(),
+    };
+    let bytes = match &values[0] {
+        Value::Array(a) => a
+            .borrow()
+            .elements
+            .iter()
+            .map(|e| match **e {
+                Value::Integer { value } if (0..=255).contains(&value) => Ok(value as u8),
+                _ => Err(type_error()),
+            })
+            .collect::<Result<Vec<u8>, ProgramError<'a>>>()?,
+        _ => return Err(type_error()),
+    };
+    String::from_utf8(bytes)
+        .map(|value| Value::String { value })
+        .map_err(|e| ProgramError {
+            code: ErrorCode::InvalidValue,
+            location: location.clone(),
+            message: format!("Invalid UTF-8 sequence: {}", e.utf8_error()),
+        })
+}
+
+fn base64_encode<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    let input = string_argument(&values[0], location)?;
+    let mut value = String::with_capacity(input.len().div_ceil(3) * 4);
+    for chunk in input.as_bytes().chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |acc, (i, b)| acc | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                value.push(BASE64_ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                value.push('=');
+            }
+        }
+    }
+    Ok(Value::String { value })
+}
+
+fn base64_decode<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    let input = string_argument(&values[0], location)?;
+    let invalid = || ProgramError {
+        code: ErrorCode::InvalidValue,
+        location: location.clone(),
+        message: "Invalid base64 input".to_owned(),
+    };
+    if input.len() % 4 != 0 {
+        return Err(invalid());
+    }
+    let mut bytes = Vec::with_capacity(input.len() / 4 * 3);
+    let chunks = input.as_bytes().chunks(4).collect::<Vec<_>>();
+    for (n, chunk) in chunks.iter().enumerate() {
+        let padding = chunk.iter().rev().take_while(|c| **c == b'=').count();
+        if padding > 2 || (padding > 0 && n != chunks.len() - 1) {
+            return Err(invalid());
+        }
+        let mut bits = 0u32;
+        for (i, c) in chunk[..4 - padding].iter().enumerate() {
+            let sextet = BASE64_ALPHABET.iter().position(|a| a == c).ok_or_else(invalid)?;
+            bits |= (sextet as u32) << (18 - 6 * i);
+        }
+        for i in 0..3 - padding {
+            bytes.push((bits >> (16 - 8 * i)) as u8);
+        }
+    }
+    String::from_utf8(bytes)
+        .map(|value| Value::String { value })
+        .map_err(|e| ProgramError {
+            code: ErrorCode::InvalidValue,
+            location: location.clone(),
+            message: format!("Invalid UTF-8 sequence: {}", e.utf8_error()),
+        })
+}
+
+type MergedMaps<'a> = Vec<((*const RefCell<LoxMap<'a>>, *const RefCell<LoxMap<'a>>), Rc<RefCell<LoxMap<'a>>>)>;
+
+fn merge_maps<'a>(
+    interpreter: &'a Interpreter<'a>,
+    left: &Rc<RefCell<LoxMap<'a>>>,
+    right: &Rc<RefCell<LoxMap<'a>>>,
+    concat_arrays: bool,
+    merged: &mut MergedMaps<'a>,
+    location: &SourceCodeLocation<'a>,
+) -> Result<Rc<RefCell<LoxMap<'a>>>, ProgramError<'a>> {
+    let pair = (Rc::as_ptr(left), Rc::as_ptr(right));
+    if let Some((_, result)) = merged.iter().find(|(p, _)| *p == pair) {
+        return Ok(result.clone());
+    }
+    let result = Rc::new(RefCell::new(left.borrow().clone()));
+    merged.push((pair, result.clone()));
+    let entries: Vec<_> = right.borrow().iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+    for (key, value) in entries {
+        let current = result.borrow().get(&key).cloned();
+        let value = match (current, value) {
+            (Some(Value::Map(l)), Value::Map(r)) =>
+                Value::Map(merge_maps(interpreter, &l, &r, concat_arrays, merged, location)?),
+            (Some(Value::Array(l)), Value::Array(r)) if concat_arrays => {
+                let mut elements: Vec<Value<'a>> = l.borrow().elements.iter().map(|e| *e.clone()).collect();
+                elements.extend(r.borrow().elements.iter().map(|e| *e.clone()));
+                interpreter.check_collection_size(elements.len(), location)?;
+                Value::new_array(elements)
+            }
+            (_, value) => value,
+        };
+        result.borrow_mut().insert(key, value);
+        interpreter.check_collection_size(result.borrow().len(), location)?;
+    }
+    Ok(result)
+}
+
+/// Merges `values[1]` into a copy of `values[0]`, recursing into maps present in both. The
+/// right side wins on any other conflict, except arrays, which are concatenated when the
+/// optional third argument is true. Maps already being merged are reused, so cycles in the
+/// inputs become cycles in the result.
+fn deep_merge<'a>(
+    interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    let concat_arrays = matches!(values.get(2), Some(v) if v.is_truthy());
+    match (&values[0], &values[1]) {
+        (Value::Map(left), Value::Map(right)) =>
+            Ok(Value::Map(merge_maps(interpreter, left, right, concat_arrays, &mut vec![], location)?)),
+        _ => Err(ProgramError {
+            code: ErrorCode::TypeError,
+            location: location.clone(),
+            message: "Type error! Expecting two maps!".to_owned(),
+        }),
+    }
+}
+
+fn with<'a>(
+    interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    let object = match &values[0] {
+        Value::Object(object) => object.duplicate(),
+        _ => return Err(ProgramError {
+            code: ErrorCode::TypeError,
+            location: location.clone(),
+            message: "Type error! Expecting an object!".to_owned(),
+        }),
+    };
+    let field = interpreter.intern(&string_argument(&values[1], location)?);
+    match object.get_setter(field) {
+        Some(setter) => {
+            setter.eval(&[Value::Object(object.clone()), values[2].clone()], interpreter)?;
+        }
+        None => {
+            interpreter.check_known_field(&object, field, location)?;
+            interpreter.check_field_type(&object, field, &values[2], location)?;
+            object.set(field, values[2].clone());
+        }
+    }
+    Ok(Value::Object(object))
+}
+
+/// Returns a map with the fields of an object, methods left out.
+fn to_map<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    match &values[0] {
+        Value::Object(object) => Ok(Value::new_map(
+            object
+                .fields()
+                .into_iter()
+                .map(|(name, value)| (MapKey::String(name.to_owned()), value))
+                .collect(),
+        )),
+        _ => Err(ProgramError {
+            code: ErrorCode::TypeError,
+            location: location.clone(),
+            message: "Type error! Expecting an object!".to_owned(),
+        }),
+    }
+}
+
+/// Creates an instance of the class in `values[0]` with the fields in the map in `values[1]`,
+/// without calling `init`. Entries named like a method or getter are skipped, and the ones
+/// named like a setter go through it. In `strict_fields` mode, entries that aren't declared
+/// fields of the class are an error.
+fn from_map<'a>(
+    interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    let (class, entries) = match (&values[0], &values[1]) {
+        (Value::Class(class), Value::Map(map)) => (
+            class.clone(),
+            map.borrow().iter().map(|(k, v)| (k.clone(), v.clone())).collect::<Vec<_>>(),
+        ),
+        _ => return Err(ProgramError {
+            code: ErrorCode::TypeError,
+            location: location.clone(),
+            message: "Type error! Expecting a class and a map!".to_owned(),
+        }),
+    };
+    let object = LoxObject::new(class.clone(), interpreter.config.weak_method_binding);
+    for (key, value) in entries {
+        let name = match &key {
+            MapKey::String(name) => interpreter.intern(name),
+            _ => return Err(ProgramError {
+                code: ErrorCode::TypeError,
+                location: location.clone(),
+                message: "Type error! Field names should be strings!".to_owned(),
+            }),
+        };
+        if let Some(setter) = object.get_setter(name) {
+            setter.eval(&[Value::Object(object.clone()), value], interpreter)?;
+        } else if class.has_method(name) {
+            continue;
+        } else {
+            interpreter.check_known_field(&object, name, location)?;
+            let owner = class.field_owner(&object, name);
+            interpreter.check_field_type(&owner, name, &value, location)?;
+            owner.set(name, value);
+        }
+    }
+    Ok(Value::Object(object))
+}
+
+/// Returns the value of the `@attr` or `@doc` annotation named `values[1]` on a function
+/// or class, or nil if there's none.
+fn get_attr<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    let annotations = match &values[0] {
+        Value::Function(f) | Value::Method(f, _) | Value::WeakMethod(f, _) => f.annotations,
+        Value::Class(c) => c.annotations,
+        _ => return Err(ProgramError {
+            code: ErrorCode::TypeError,
+            location: location.clone(),
+            message: "Type error! Expecting a function or a class!".to_owned(),
+        }),
+    };
+    let key = string_argument(&values[1], location)?;
+    Ok(annotations
+        .iter()
+        .rev()
+        .find(|a| a.key == key)
+        .map_or(Value::Nil, |a| (&a.value).into()))
+}
+
+fn describe(value: &Value) -> String {
+    match value {
+        Value::String { value } => format!("{:?}", value),
+        Value::Float { value } => format!("{:?}", value),
+        // Containers may hold themselves, so only say what they are.
+        Value::Array(a) => format!("array of length {}", a.borrow().elements.len()),
+        Value::Tuple(t) => format!("tuple of length {}", t.len()),
+        Value::Map(m) => format!("map of length {}", m.borrow().len()),
+        Value::EnumInstance(v) => format!("{}.{}", v.lox_enum.name, v.variant),
+        _ => value.to_string(),
+    }
+}
+
+fn container_pointer(value: &Value) -> Option<*const ()> {
+    match value {
+        Value::Array(a) => Some(Rc::as_ptr(a) as *const ()),
+        Value::Map(m) => Some(Rc::as_ptr(m) as *const ()),
+        Value::Object(o) => Some(Rc::as_ptr(o) as *const ()),
+        Value::Tuple(t) => Some(Rc::as_ptr(t) as *const ()),
+        _ => None,
+    }
+}
+
+/// Describes the first place where `left` and `right` differ, or returns `None` if they're
+/// equal. Pairs of containers already being compared are assumed equal, so cycles end.
+fn first_difference<'a>(
+    interpreter: &'a Interpreter<'a>,
+    path: &str,
+    left: &Value<'a>,
+    right: &Value<'a>,
+    visited: &mut Vec<(*const (), *const ())>,
+) -> Option<String> {
+    if let (Some(l), Some(r)) = (container_pointer(left), container_pointer(right)) {
+        if visited.contains(&(l, r)) {
+            return None;
+        }
+        visited.push((l, r));
+    }
+    let sequences = |left: Vec<Value<'a>>, right: Vec<Value<'a>>, visited: &mut Vec<_>| {
+        if left.len() != right.len() {
+            return Some(format!("{}: length {} != {}", path, left.len(), right.len()));
+        }
+        left.iter().zip(right.iter()).enumerate().find_map(|(i, (l, r))| {
+            first_difference(interpreter, &format!("{}[{}]", path, i), l, r, visited)
+        })
+    };
+    match (left, right) {
+        (Value::Array(l), Value::Array(r)) => sequences(
+            l.borrow().elements.iter().map(|e| *e.clone()).collect(),
+            r.borrow().elements.iter().map(|e| *e.clone()).collect(),
+            visited,
+        ),
+        (Value::Tuple(l), Value::Tuple(r)) => sequences(l.as_ref().clone(), r.as_ref().clone(), visited),
+        (Value::Map(l), Value::Map(r)) => {
+            let (l, r) = (l.borrow().clone(), r.borrow().clone());
+            let key_path = |key: &MapKey| match key {
+                MapKey::String(key) => format!("{}[{:?}]", path, key),
+                _ => format!("{}[{}]", path, key),
+            };
+            let difference = l.iter()
+                .find_map(|(key, value)| match r.get(key) {
+                    Some(other) => first_difference(interpreter, &key_path(key), value, other, visited),
+                    None => Some(format!("{}: missing on the right", key_path(key))),
+                })
+                .or_else(|| {
+                    r.iter()
+                        .find(|(key, _)| l.get(*key).is_none())
+                        .map(|(key, _)| format!("{}: missing on the left", key_path(key)))
+                });
+            difference
+        }
+        (Value::Object(l), Value::Object(r)) if l.class_name == r.class_name => {
+            let fields = |o: &LoxObject<'a>| -> Vec<(&'a str, Value<'a>)> {
+                o.properties()
+                    .into_iter()
+                    .filter(|(_, v)| !matches!(v, Value::Method(..) | Value::WeakMethod(..)))
+                    .collect()
+            };
+            let (l, r) = (fields(l), fields(r));
+            l.iter()
+                .find_map(|(name, value)| match r.iter().find(|(other, _)| other == name) {
+                    Some((_, other)) =>
+                        first_difference(interpreter, &format!("{}.{}", path, name), value, other, visited),
+                    None => Some(format!("{}.{}: missing on the right", path, name)),
+                })
+                .or_else(|| {
+                    r.iter()
+                        .find(|(name, _)| l.iter().all(|(other, _)| other != name))
+                        .map(|(name, _)| format!("{}.{}: missing on the left", path, name))
+                })
+        }
+        (Value::Object(_), Value::Object(_)) => Some(format!("{}: {} != {}", path, left, right)),
+        _ if interpreter.values_equal(left, right) => None,
+        _ => Some(format!("{}: {} != {}", path, describe(left), describe(right))),
+    }
+}
+
+/// Describes where two values differ, recursing into arrays, tuples, maps and objects, or
+/// returns nil when they're equal. Paths start at `$`, like `$.users[1]["name"]`.
+fn diff<'a>(
+    interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    _location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    Ok(match first_difference(interpreter, "$", &values[0], &values[1], &mut vec![]) {
+        Some(difference) => Value::String { value: difference },
+        None => Value::Nil,
+    })
+}
+
+/// Returns a map from the names of the global variables to their values. Natives are only
+/// listed when the optional argument is true.
+fn globals<'a>(
+    interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    _location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    let include_natives = matches!(values.first(), Some(v) if v.is_truthy());
+    Ok(Value::new_map(
+        interpreter
+            .globals(include_natives)
+            .into_iter()
+            .map(|(name, value)| (MapKey::String(name.to_owned()), value))
+            .collect(),
+    ))
+}
+
+/// Returns the quotient and the remainder of dividing two integers as a 2-tuple.
+fn divmod<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    match (&values[0], &values[1]) {
+        (Value::Integer { .. }, Value::Integer { value: 0 }) => Err(ProgramError {
+            code: ErrorCode::DivisionByZero,
+            location: location.clone(),
+            message: "Division by zero!".to_owned(),
+        }),
+        (Value::Integer { value: dividend }, Value::Integer { value: divisor }) => {
+            match (dividend.checked_div(*divisor), dividend.checked_rem(*divisor)) {
+                (Some(quotient), Some(remainder)) => Ok(Value::new_tuple(vec![
+                    Value::Integer { value: quotient },
+                    Value::Integer { value: remainder },
+                ])),
+                _ => Err(ProgramError {
+                    code: ErrorCode::OverflowError,
+                    location: location.clone(),
+                    message: "Integer overflow!".to_owned(),
+                }),
+            }
+        }
+        _ => Err(ProgramError {
+            code: ErrorCode::TypeError,
+            location: location.clone(),
+            message: "Type error! Expecting two integers!".to_owned(),
+        }),
+    }
+}
+
+/// Compares two strings in time that only depends on their lengths, so the position of the
+/// first mismatch can't be learned by timing the comparison.
+fn constant_time_equals<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    let left = string_argument(&values[0], location)?;
+    let right = string_argument(&values[1], location)?;
+    let (left, right) = (left.as_bytes(), right.as_bytes());
+    let length = left.len().max(right.len());
+    let mut difference = (left.len() != right.len()) as u8;
+    for i in 0..length {
+        let l = left.get(i).copied().unwrap_or(0);
+        let r = right.get(i).copied().unwrap_or(0);
+        difference |= l ^ r;
+    }
+    Ok(Value::Boolean { value: difference == 0 })
+}
+
+/// Folds an array of numbers, starting from `initial`. Integers are combined with checked
+/// arithmetic, and the accumulator turns into a float after the first float element.
+fn accumulate<'a>(
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+    initial: i64,
+    i64_op: fn(i64, i64) -> Option<i64>,
+    f32_op: fn(f32, f32) -> f32,
+) -> EvaluationResult<'a> {
+    let error = |code: ErrorCode, message: &str| ProgramError {
+        code,
+        location: location.clone(),
+        message: message.to_owned(),
+    };
+    let elements = match &values[0] {
+        Value::Array(a) => a.borrow().elements.iter().map(|e| *e.clone()).collect::<Vec<Value<'a>>>(),
+        _ => return Err(error(ErrorCode::TypeError, "Type error! Expecting an array of numbers!")),
+    };
+    let mut accumulator = Value::Integer { value: initial };
+    for element in elements {
+        accumulator = match (accumulator, element) {
+            (Value::Integer { value: l }, Value::Integer { value: r }) => Value::Integer {
+                value: i64_op(l, r).ok_or_else(|| error(ErrorCode::OverflowError, "Integer overflow!"))?,
+            },
+            (Value::Integer { value: l }, Value::Float { value: r }) => Value::Float { value: f32_op(l as f32, r) },
+            (Value::Float { value: l }, Value::Integer { value: r }) => Value::Float { value: f32_op(l, r as f32) },
+            (Value::Float { value: l }, Value::Float { value: r }) => Value::Float { value: f32_op(l, r) },
+            _ => return Err(error(ErrorCode::TypeError, "Type error! Expecting an array of numbers!")),
+        };
+    }
+    Ok(accumulator)
+}
+
+fn sum<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    accumulate(values, location, 0, i64::checked_add, std::ops::Add::add)
+}
+
+fn product<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    accumulate(values, location, 1, i64::checked_mul, std::ops::Mul::mul)
+}
+
+/// Numbers in the array in `values[0]`, as floats. `statistic` names what they're for in
+/// the error for an empty array.
+fn statistics_sample<'a>(
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+    statistic: &str,
+) -> Result<Vec<f64>, ProgramError<'a>> {
+    let type_error = || ProgramError {
+        code: ErrorCode::TypeError,
+        location: location.clone(),
+        message: "Type error! Expecting an array of numbers!".to_owned(),
+    };
+    let sample = match &values[0] {
+        Value::Array(a) => a
+            .borrow()
+            .elements
+            .iter()
+            .map(|e| match **e {
+                Value::Integer { value } => Ok(value as f64),
+                Value::Float { value } => Ok(value as f64),
+                _ => Err(type_error()),
+            })
+            .collect::<Result<Vec<f64>, ProgramError<'a>>>()?,
+        _ => return Err(type_error()),
+    };
+    if sample.is_empty() {
+        return Err(ProgramError {
+            code: ErrorCode::InvalidValue,
+            location: location.clone(),
+            message: format!("Can't compute the {} of an empty array", statistic),
+        });
+    }
+    Ok(sample)
+}
+
+fn mean_of(sample: &[f64]) -> f64 {
+    sample.iter().sum::<f64>() / sample.len() as f64
+}
+
+fn mean<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    let sample = statistics_sample(values, location, "mean")?;
+    Ok(Value::Float { value: mean_of(&sample) as f32 })
+}
+
+/// Middle element of a sorted copy of the array, or the mean of the two middle ones when
+/// its length is even. The array itself isn't reordered.
+fn median<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    let mut sample = statistics_sample(values, location, "median")?;
+    sample.sort_by(|a, b| a.total_cmp(b));
+    let middle = sample.len() / 2;
+    let median = if sample.len() % 2 == 0 {
+        (sample[middle - 1] + sample[middle]) / 2.0
+    } else {
+        sample[middle]
+    };
+    Ok(Value::Float { value: median as f32 })
+}
+
+/// Population standard deviation of the array.
+fn stddev<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    let sample = statistics_sample(values, location, "standard deviation")?;
+    let mean = mean_of(&sample);
+    let variance = sample.iter().map(|x| (x - mean) * (x - mean)).sum::<f64>() / sample.len() as f64;
+    Ok(Value::Float { value: variance.sqrt() as f32 })
+}
+
+fn number_argument<'a>(value: &Value<'a>, location: &SourceCodeLocation<'a>) -> Result<f32, ProgramError<'a>> {
+    value.clone().coerce_number().map_err(|e| e.into_program_error(location))
+}
+
+/// Limits `values[0]` to the range between `values[1]` and `values[2]`. The result is an
+/// integer when the three arguments are, a float otherwise.
+fn clamp<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    let bounds_error = || ProgramError {
+        code: ErrorCode::InvalidValue,
+        location: location.clone(),
+        message: format!("Lower bound {} is greater than upper bound {}", values[1], values[2]),
+    };
+    match (&values[0], &values[1], &values[2]) {
+        (Value::Integer { value }, Value::Integer { value: low }, Value::Integer { value: high }) => {
+            if low > high {
+                return Err(bounds_error());
+            }
+            Ok(Value::Integer { value: *value.max(low).min(high) })
+        }
+        (value, low, high) => {
+            let (value, low, high) = (
+                number_argument(value, location)?,
+                number_argument(low, location)?,
+                number_argument(high, location)?,
+            );
+            if low > high {
+                return Err(bounds_error());
+            }
+            Ok(Value::Float { value: value.max(low).min(high) })
+        }
+    }
+}
+
+/// Interpolates linearly between `values[0]` and `values[1]`, `values[2]` being the
+/// fraction of the way from the first to the second. Always returns a float.
+fn lerp<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    let from = number_argument(&values[0], location)?;
+    let to = number_argument(&values[1], location)?;
+    let t = number_argument(&values[2], location)?;
+    Ok(Value::Float { value: from + (to - from) * t })
+}
+
+/// Returns -1, 0 or 1 depending on the sign of a number, as an integer or a float like the
+/// argument.
+fn sign<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    match &values[0] {
+        Value::Integer { value } => Ok(Value::Integer { value: value.signum() }),
+        value => {
+            let value = number_argument(value, location)?;
+            Ok(Value::Float { value: if value == 0.0 || value.is_nan() { value } else { value.signum() } })
+        }
+    }
+}
+
+/// Returns the interned symbol with the given name.
+fn symbol<'a>(
+    interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    Ok(Value::Symbol(interpreter.symbol(&string_argument(&values[0], location)?)))
+}
+
+/// Creates a mutable string buffer, optionally starting with the string in `values[0]`.
+fn string_buffer<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    let initial = match values.first() {
+        Some(value) => string_argument(value, location)?,
+        None => String::new(),
+    };
+    Ok(Value::new_string_buffer(initial))
+}
+
+pub fn register_builtins(interpreter: &Interpreter) {
+    interpreter.register_native("bytes_to_string", 1..=1, bytes_to_string);
+    interpreter.register_native("base64_encode", 1..=1, base64_encode);
+    interpreter.register_native("base64_decode", 1..=1, base64_decode);
+    interpreter.register_native("deep_merge", 2..=3, deep_merge);
+    interpreter.register_native("with", 3..=3, with);
+    interpreter.register_native("globals", 0..=1, globals);
+    interpreter.register_native("divmod", 2..=2, divmod);
+    interpreter.register_native("constant_time_equals", 2..=2, constant_time_equals);
+    interpreter.register_native("sum", 1..=1, sum);
+    interpreter.register_native("product", 1..=1, product);
+    interpreter.register_native("mean", 1..=1, mean);
+    interpreter.register_native("median", 1..=1, median);
+    interpreter.register_native("stddev", 1..=1, stddev);
+    interpreter.register_native("clamp", 3..=3, clamp);
+    interpreter.register_native("lerp", 3..=3, lerp);
+    interpreter.register_native("sign", 1..=1, sign);
+    interpreter.register_native("symbol", 1..=1, symbol);
+    interpreter.register_native("string_buffer", 0..=1, string_buffer);
+    interpreter.register_native("diff", 2..=2, diff);
+    interpreter.register_native("to_map", 1..=1, to_map);
+    interpreter.register_native("from_map", 2..=2, from_map);
+    interpreter.register_native("get_attr", 2..=2, get_attr);
+    interpreter.register_native_namespace("Template", vec![("compile", 1..=1, template::compile)]);
+}
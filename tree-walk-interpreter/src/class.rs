@@ -1,45 +1,56 @@
 use ahash::{AHashMap as HashMap, AHashSet as HashSet};
 use crate::function::LoxFunction;
-use crate::value::Value;
-use parser::types::{ProgramError, SourceCodeLocation, Statement, StatementType};
+use crate::value::{OrderedMap, Value};
+use parser::types::{Annotation, ClassField, ErrorCode, ProgramError, SourceCodeLocation, Statement, StatementType, Type};
 use std::cell::RefCell;
-use std::rc::Rc;
+use std::rc::{Rc, Weak};
 use crate::interpreter::Interpreter;
 
 fn function_declaration_to_lox_funxtion<'a>(
+    name: &'a str,
     arguments: &[&'a str],
+    keyword_arguments: Option<&'a str>,
     body: Vec<&'a Statement<'a>>,
     location: &SourceCodeLocation<'a>,
     environments: &[Rc<RefCell<HashMap<&'a str, Value<'a>>>>],
+    annotations: &'a [Annotation<'a>],
 ) -> LoxFunction<'a> {
     LoxFunction {
+        annotations,
+        name: Some(name),
         arguments: arguments.to_vec(),
+        keyword_arguments,
         body,
         environments: environments.to_vec(),
         location: location.clone(),
     }
 }
 
-fn statement_list_to_function_hash_map<'a>(
+fn statement_list_to_function_map<'a>(
     statements: &[&'a Statement<'a>],
     environments: &[Rc<RefCell<HashMap<&'a str, Value<'a>>>>],
-) -> HashMap<&'a str, Rc<LoxFunction<'a>>> {
-    let mut functions = HashMap::default();
+) -> OrderedMap<&'a str, Rc<LoxFunction<'a>>> {
+    let mut functions = OrderedMap::default();
     for s in statements {
         match &s.statement_type {
             StatementType::FunctionDeclaration {
                 arguments,
+                keyword_arguments,
                 body,
                 name,
+                annotations,
                 ..
             } => {
                 functions.insert(
                     name.clone(),
                     Rc::new(function_declaration_to_lox_funxtion(
+                        name,
                         arguments,
+                        *keyword_arguments,
                         body.iter().map(AsRef::as_ref).collect(),
                         &s.location,
                         &environments,
+                        annotations,
                     )),
                 );
             }
@@ -51,12 +62,16 @@ fn statement_list_to_function_hash_map<'a>(
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct LoxClass<'a> {
-    methods: Rc<RefCell<HashMap<&'a str, Rc<LoxFunction<'a>>>>>,
-    getters: Rc<RefCell<HashMap<&'a str, Rc<LoxFunction<'a>>>>>,
-    setters: Rc<RefCell<HashMap<&'a str, Rc<LoxFunction<'a>>>>>,
+    methods: Rc<RefCell<OrderedMap<&'a str, Rc<LoxFunction<'a>>>>>,
+    getters: Rc<RefCell<OrderedMap<&'a str, Rc<LoxFunction<'a>>>>>,
+    setters: Rc<RefCell<OrderedMap<&'a str, Rc<LoxFunction<'a>>>>>,
     traits: Rc<RefCell<HashSet<&'a str>>>,
+    field_types: Rc<HashMap<&'a str, &'a Type<'a>>>,
+    fields: Rc<Vec<&'a str>>,
     pub superclass: Option<Rc<LoxClass<'a>>>,
     pub name: &'a str,
+    pub is_final: bool,
+    pub annotations: &'a [Annotation<'a>],
     pub static_instance: Rc<LoxObject<'a>>,
 }
 
@@ -67,19 +82,22 @@ impl<'a> LoxClass<'a> {
         method_list: &[&'a Statement<'a>],
         getters: &[&'a Statement<'a>],
         setters: &[&'a Statement<'a>],
+        fields: &'a [ClassField<'a>],
+        is_final: bool,
+        annotations: &'a [Annotation<'a>],
         superclass: Option<Rc<LoxClass<'a>>>,
         mut environments: Vec<Rc<RefCell<HashMap<&'a str, Value<'a>>>>>,
     ) -> LoxClass<'a> {
         environments.push(Rc::new(RefCell::new(HashMap::default())));
-        let methods = Rc::new(RefCell::new(statement_list_to_function_hash_map(
+        let methods = Rc::new(RefCell::new(statement_list_to_function_map(
             method_list,
             &environments,
         )));
-        let getters = Rc::new(RefCell::new(statement_list_to_function_hash_map(
+        let getters = Rc::new(RefCell::new(statement_list_to_function_map(
             getters,
             &environments,
         )));
-        let setters = Rc::new(RefCell::new(statement_list_to_function_hash_map(
+        let setters = Rc::new(RefCell::new(statement_list_to_function_map(
             setters,
             &environments,
         )));
@@ -88,17 +106,22 @@ impl<'a> LoxClass<'a> {
             match &ms.statement_type {
                 StatementType::FunctionDeclaration {
                     arguments,
+                    keyword_arguments,
                     body,
                     name,
+                    annotations,
                     ..
                 } => {
                     static_methods.push((
                         name.clone(),
                         Rc::new(function_declaration_to_lox_funxtion(
+                            name,
                             arguments,
+                            *keyword_arguments,
                             body.iter().map(AsRef::as_ref).collect(),
                             &ms.location,
                             &environments,
+                            annotations,
                         )),
                     ));
                 }
@@ -107,8 +130,16 @@ impl<'a> LoxClass<'a> {
         }
         let static_instance =
             Rc::new(LoxObject::new_static(name, &static_methods, superclass.clone()));
+        let field_types = fields
+            .iter()
+            .filter_map(|f| f.field_type.as_ref().map(|t| (f.name, t)))
+            .collect();
         LoxClass {
+            annotations,
+            field_types: Rc::new(field_types),
+            fields: Rc::new(fields.iter().map(|f| f.name).collect()),
             getters,
+            is_final,
             methods,
             name,
             setters,
@@ -126,13 +157,38 @@ impl<'a> LoxClass<'a> {
         self.traits.borrow().contains(trait_name)
     }
 
+    /// Whether the field was declared with `var` in the class or one of its superclasses.
+    pub fn declares_field(&self, name: &str) -> bool {
+        self.fields.contains(&name)
+            || self.superclass.as_ref().map_or(false, |s| s.declares_field(name))
+    }
+
+    /// The part of `object`, an instance of the class, that holds the field `name`: the one
+    /// of the superclass that declares it, so its methods can see it. Fields that aren't
+    /// declared belong to `object` itself.
+    pub fn field_owner(&self, object: &Rc<LoxObject<'a>>, name: &str) -> Rc<LoxObject<'a>> {
+        match (&self.superclass, &object.superclass) {
+            (Some(class), Some(parent)) if !self.fields.contains(&name) && class.declares_field(name) =>
+                class.field_owner(parent, name),
+            _ => object.clone(),
+        }
+    }
+
+    /// Whether instances of the class get a method, getter or setter called `name`.
+    pub fn has_method(&self, name: &str) -> bool {
+        self.methods.borrow().get(name).is_some()
+            || self.getters.borrow().get(name).is_some()
+            || self.setters.borrow().get(name).is_some()
+            || self.superclass.as_ref().map_or(false, |s| s.has_method(name))
+    }
+
     pub fn append_methods(
         &self,
         method_list: &[&'a Statement<'a>],
         mut environments: Vec<Rc<RefCell<HashMap<&'a str, Value<'a>>>>>,
     ) {
         environments.push(Rc::new(RefCell::new(HashMap::default())));
-        let methods = statement_list_to_function_hash_map(method_list, &environments);
+        let methods = statement_list_to_function_map(method_list, &environments);
         self.methods.borrow_mut().extend(methods);
     }
 
@@ -152,7 +208,7 @@ impl<'a> LoxClass<'a> {
         mut environments: Vec<Rc<RefCell<HashMap<&'a str, Value<'a>>>>>,
     ) {
         environments.push(Rc::new(RefCell::new(HashMap::default())));
-        let getters = statement_list_to_function_hash_map(method_list, &environments);
+        let getters = statement_list_to_function_map(method_list, &environments);
         self.getters.borrow_mut().extend(getters);
     }
 
@@ -162,30 +218,44 @@ impl<'a> LoxClass<'a> {
         mut environments: Vec<Rc<RefCell<HashMap<&'a str, Value<'a>>>>>,
     ) {
         environments.push(Rc::new(RefCell::new(HashMap::default())));
-        let setters = statement_list_to_function_hash_map(method_list, &environments);
+        let setters = statement_list_to_function_map(method_list, &environments);
         self.setters.borrow_mut().extend(setters);
     }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct LoxObject<'a> {
-    properties: Rc<RefCell<HashMap<&'a str, Value<'a>>>>,
+    properties: Rc<RefCell<OrderedMap<&'a str, Value<'a>>>>,
     getters: HashMap<&'a str, Rc<LoxFunction<'a>>>,
     setters: HashMap<&'a str, Rc<LoxFunction<'a>>>,
+    field_types: Rc<HashMap<&'a str, &'a Type<'a>>>,
+    fields: Rc<Vec<&'a str>>,
     pub superclass: Option<Rc<LoxObject<'a>>>,
     pub class_name: &'a str,
     pub traits: HashSet<&'a str>,
 }
 
+/// A reference to an object that doesn't keep it alive, held by weakly bound methods.
+#[derive(Clone, Debug)]
+pub struct WeakObject<'a>(pub Weak<LoxObject<'a>>);
+
+impl<'a> PartialEq for WeakObject<'a> {
+    fn eq(&self, other: &WeakObject<'a>) -> bool {
+        self.0.ptr_eq(&other.0)
+    }
+}
+
 impl<'a> LoxObject<'a> {
-    pub fn new(class: Rc<LoxClass<'a>>) -> Rc<LoxObject<'a>> {
-        let properties = Rc::new(RefCell::new(HashMap::default()));
+    pub fn new(class: Rc<LoxClass<'a>>, weak_methods: bool) -> Rc<LoxObject<'a>> {
+        let properties = Rc::new(RefCell::new(OrderedMap::default()));
         let superclass = class
             .superclass
             .clone()
-            .map(LoxObject::new);
+            .map(|c| LoxObject::new(c, weak_methods));
         let mut object = LoxObject {
             class_name: class.name.clone(),
+            field_types: class.field_types.clone(),
+            fields: class.fields.clone(),
             getters: HashMap::default(),
             properties: properties.clone(),
             setters: HashMap::default(),
@@ -203,9 +273,12 @@ impl<'a> LoxObject<'a> {
         }
         let object = Rc::new(object);
         for (name, f) in class.methods.borrow().iter() {
-            properties
-                .borrow_mut()
-                .insert(name.clone(), Value::Method(f.clone(), object.clone()));
+            let method = if weak_methods {
+                Value::WeakMethod(f.clone(), WeakObject(Rc::downgrade(&object)))
+            } else {
+                Value::Method(f.clone(), object.clone())
+            };
+            properties.borrow_mut().insert(name.clone(), method);
         }
         object
     }
@@ -215,7 +288,7 @@ impl<'a> LoxObject<'a> {
         methods: &[(&'a str, Rc<LoxFunction<'a>>)],
         superclass: Option<Rc<LoxClass<'a>>>,
     ) -> LoxObject<'a> {
-        let properties = Rc::new(RefCell::new(HashMap::default()));
+        let properties = Rc::new(RefCell::new(OrderedMap::default()));
         for (name, function) in methods {
             properties
                 .borrow_mut()
@@ -235,6 +308,8 @@ impl<'a> LoxObject<'a> {
             })
             .map(Rc::new);
         LoxObject {
+            field_types: Rc::new(HashMap::new()),
+            fields: Rc::new(vec![]),
             getters: HashMap::new(),
             setters: HashMap::new(),
             traits: HashSet::new(),
@@ -244,25 +319,53 @@ impl<'a> LoxObject<'a> {
         }
     }
 
+    /// Creates an object without a class holding `members`, used to group natives.
+    pub(crate) fn new_namespace(name: &'a str, members: Vec<(&'a str, Value<'a>)>) -> Rc<LoxObject<'a>> {
+        Rc::new(LoxObject {
+            field_types: Rc::new(HashMap::new()),
+            fields: Rc::new(vec![]),
+            getters: HashMap::new(),
+            setters: HashMap::new(),
+            traits: HashSet::new(),
+            class_name: name,
+            properties: Rc::new(RefCell::new(members.into_iter().collect())),
+            superclass: None,
+        })
+    }
+
     pub fn init(
         &self,
         values: &[Value<'a>],
+        named_values: &[(&'a str, Value<'a>)],
         interpreter: &'a Interpreter<'a>,
         location: &SourceCodeLocation<'a>,
     ) -> Result<(), ProgramError<'a>> {
         if let Some(s) = &self.superclass {
-            s.init(values, interpreter, location)?;
+            s.init(values, named_values, interpreter, location)?;
         }
-        if let Some(Value::Method(f, obj)) = self.properties.borrow().get("init") {
+        let init = match self.properties.borrow().get("init") {
+            Some(Value::Method(f, obj)) => Some((f.clone(), obj.clone())),
+            Some(Value::WeakMethod(f, obj)) => obj.0.upgrade().map(|obj| (f.clone(), obj)),
+            _ => None,
+        };
+        if let Some((f, obj)) = init {
             let mut arguments: Vec<Value<'a>> = vec![Value::Object(obj.clone())];
             arguments.extend_from_slice(values);
+            let arguments = f
+                .bind_arguments(arguments, named_values.to_vec())
+                .map_err(|message| ProgramError {
+                    code: ErrorCode::ArgumentError,
+                    message,
+                    location: location.clone(),
+                })?;
             f.eval(&arguments, interpreter)?;
             Ok(())
-        } else if values.len() != 0 {
+        } else if values.len() + named_values.len() != 0 {
             Err(ProgramError {
+                code: ErrorCode::ArgumentError,
                 message: format!(
                     "Wrong number of arguments: Received {}, expected {}",
-                    values.len(),
+                    values.len() + named_values.len(),
                     0,
                 ),
                 location: location.clone(),
@@ -280,6 +383,20 @@ impl<'a> LoxObject<'a> {
         self.getters.get(name).cloned()
     }
 
+    /// Declared type of the field, looked up through the superclasses too.
+    pub fn get_field_type(&self, name: &str) -> Option<&'a Type<'a>> {
+        self.field_types.get(name).copied()
+            .or_else(|| self.superclass.as_ref().and_then(|s| s.get_field_type(name)))
+    }
+
+    /// Whether `name` was declared as a field of the object's class or one of its
+    /// superclasses, or is already set on the object.
+    pub fn has_field(&self, name: &str) -> bool {
+        self.fields.contains(&name)
+            || self.properties.borrow().get(name).is_some()
+            || self.superclass.as_ref().map_or(false, |s| s.has_field(name))
+    }
+
     pub fn get(&self, name: &str) -> Option<Value<'a>> {
         let v = self.properties.borrow().get(name).cloned();
         if v.is_some() {
@@ -293,12 +410,65 @@ impl<'a> LoxObject<'a> {
         self.properties.borrow_mut().insert(name, value);
     }
 
+    /// Own properties of the object in insertion order, superclass objects not included.
+    /// Methods come first, in declaration order, followed by fields as they're assigned.
+    pub fn properties(&self) -> Vec<(&'a str, Value<'a>)> {
+        self.properties
+            .borrow()
+            .iter()
+            .map(|(name, value)| (*name, value.clone()))
+            .collect()
+    }
+
+    /// Fields of the object and its superclass objects, without methods or the `super`
+    /// link. A field set on the object hides the one with the same name in a superclass.
+    pub fn fields(&self) -> OrderedMap<&'a str, Value<'a>> {
+        let mut fields = self.superclass.as_ref().map(|s| s.fields()).unwrap_or_default();
+        for (name, value) in self.properties.borrow().iter() {
+            match value {
+                Value::Method(_, o) if std::ptr::eq(o.as_ref(), self) => {}
+                Value::WeakMethod(_, o) if std::ptr::eq(o.0.as_ptr(), self) => {}
+                Value::Object(_) if *name == "super" && self.superclass.is_some() => {}
+                _ => fields.insert(*name, value.clone()),
+            }
+        }
+        fields
+    }
+
+    /// Creates a shallow copy of the object, superclass objects included. Methods are bound
+    /// to the copy instead of the original.
+    pub fn duplicate(&self) -> Rc<LoxObject<'a>> {
+        let superclass = self.superclass.as_ref().map(|s| s.duplicate());
+        let object = Rc::new(LoxObject {
+            properties: Rc::new(RefCell::new(OrderedMap::default())),
+            getters: self.getters.clone(),
+            setters: self.setters.clone(),
+            field_types: self.field_types.clone(),
+            fields: self.fields.clone(),
+            superclass: superclass.clone(),
+            class_name: self.class_name,
+            traits: self.traits.clone(),
+        });
+        for (name, value) in self.properties.borrow().iter() {
+            let value = match (value, &superclass) {
+                (Value::Method(f, o), _) if std::ptr::eq(o.as_ref(), self) =>
+                    Value::Method(f.clone(), object.clone()),
+                (Value::WeakMethod(f, o), _) if std::ptr::eq(o.0.as_ptr(), self) =>
+                    Value::WeakMethod(f.clone(), WeakObject(Rc::downgrade(&object))),
+                (Value::Object(_), Some(s)) if *name == "super" => Value::Object(s.clone()),
+                (v, _) => v.clone(),
+            };
+            object.properties.borrow_mut().insert(name, value);
+        }
+        object
+    }
+
     pub fn append_methods(
         &self,
         method_list: &[&'a Statement<'a>],
         environments: Vec<Rc<RefCell<HashMap<&'a str, Value<'a>>>>>,
     ) {
-        let methods = statement_list_to_function_hash_map(method_list, &environments);
+        let methods = statement_list_to_function_map(method_list, &environments);
         self.properties
             .borrow_mut()
             .extend(methods.into_iter().map(|(s, f)| (s, Value::Function(f))));
@@ -0,0 +1,55 @@
+/// How `==` and `!=` compare an integer against a float.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum NumericEquality {
+    /// Values of different numeric types are never equal, so `1 == 1.0` is false.
+    #[default]
+    Strict,
+    /// The integer is promoted to a float before comparing, so `1 == 1.0` is true.
+    Promoting,
+}
+
+/// Text `print` uses for nil and booleans.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrintFormat {
+    pub nil: String,
+    pub true_text: String,
+    pub false_text: String,
+}
+
+impl PrintFormat {
+    pub fn boolean(&self, value: bool) -> &str {
+        if value {
+            &self.true_text
+        } else {
+            &self.false_text
+        }
+    }
+}
+
+impl Default for PrintFormat {
+    fn default() -> PrintFormat {
+        PrintFormat {
+            nil: "nil".to_owned(),
+            true_text: "true".to_owned(),
+            false_text: "false".to_owned(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InterpreterConfig {
+    pub numeric_equality: NumericEquality,
+    /// Maximum number of elements in an array or entries in a map. Unbounded when `None`.
+    pub max_collection_size: Option<usize>,
+    /// Bind the methods of new instances without keeping the instance alive, so storing a
+    /// bound method in a field doesn't create a reference cycle.
+    pub weak_method_binding: bool,
+    /// Run function calls on a new thread with a stack of its own once the current stack
+    /// runs low, so recursion depth is only bounded by memory. Calls get a bit slower.
+    pub heap_call_stack: bool,
+    /// Only allow assigning fields declared by the class or already set on the object, so
+    /// a misspelled name is an error instead of a new field.
+    pub strict_fields: bool,
+    /// How `print` writes nil and booleans.
+    pub print_format: PrintFormat,
+}
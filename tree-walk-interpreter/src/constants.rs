@@ -0,0 +1,299 @@
+use crate::rewrite::rewrite_expressions;
+use ahash::{AHashMap as HashMap, AHashSet as HashSet};
+use parser::types::{
+    DataKeyword, EnumVariant, Expression, ExpressionType, Literal, Pass, ProgramError, Statement,
+    StatementType,
+};
+
+/// Finds the reads of global variables that behave as constants: declared once at the top
+/// level with a literal, never assigned and never shadowed. Reads that come before the
+/// declaration in the program are left alone, as they'd fail at runtime. Maps the id of
+/// every such read to the position of the declaration in the program.
+struct ConstantPropagation<'a> {
+    current: usize,
+    candidates: HashMap<&'a str, usize>,
+    declarations: HashMap<&'a str, usize>,
+    assigned: HashSet<&'a str>,
+    uses: Vec<(usize, &'a str, usize)>,
+}
+
+impl<'a> ConstantPropagation<'a> {
+    fn new() -> ConstantPropagation<'a> {
+        ConstantPropagation {
+            current: 0,
+            candidates: HashMap::default(),
+            declarations: HashMap::default(),
+            assigned: HashSet::default(),
+            uses: vec![],
+        }
+    }
+
+    fn declare(&mut self, name: &'a str) {
+        *self.declarations.entry(name).or_insert(0) += 1;
+    }
+}
+
+impl<'a> Pass<'a, HashMap<usize, usize>> for ConstantPropagation<'a> {
+    fn run(&mut self, ss: &'a [Statement<'a>]) -> Result<HashMap<usize, usize>, Vec<ProgramError<'a>>> {
+        for (i, s) in ss.iter().enumerate() {
+            self.current = i;
+            if let StatementType::VariableDeclaration { name, .. } = &s.statement_type {
+                if literal_declaration(s).is_some() {
+                    self.candidates.insert(name, i);
+                }
+            }
+            self.pass(s)?;
+        }
+        let mut constants = HashMap::default();
+        for (position, name, id) in self.uses.iter() {
+            match self.candidates.get(name) {
+                Some(declared)
+                    if declared < position
+                        && self.declarations.get(name) == Some(&1)
+                        && !self.assigned.contains(name) =>
+                {
+                    constants.insert(*id, *declared);
+                }
+                _ => {}
+            }
+        }
+        Ok(constants)
+    }
+
+    fn pass_import(&mut self, name: &'a str, _statement: &'a Statement<'a>) -> Result<(), Vec<ProgramError<'a>>> {
+        self.declare(name);
+        Ok(())
+    }
+
+    fn pass_variable_declaration(
+        &mut self,
+        name: &'a str,
+        expression: &'a Option<Expression<'a>>,
+        _statement: &'a Statement<'a>,
+    ) -> Result<(), Vec<ProgramError<'a>>> {
+        self.declare(name);
+        if let Some(e) = expression {
+            self.pass_expression(e)?;
+        }
+        Ok(())
+    }
+
+    fn pass_tuple_declaration(
+        &mut self,
+        names: &'a [&'a str],
+        expression: &'a Expression<'a>,
+        _statement: &'a Statement<'a>,
+    ) -> Result<(), Vec<ProgramError<'a>>> {
+        for name in names {
+            self.declare(name);
+        }
+        self.pass_expression(expression)
+    }
+
+    fn pass_for_in(
+        &mut self,
+        variable: &'a str,
+        iterable: &'a Expression<'a>,
+        body: &'a Statement<'a>,
+        _statement: &'a Statement<'a>,
+    ) -> Result<(), Vec<ProgramError<'a>>> {
+        self.declare(variable);
+        self.pass_expression(iterable)?;
+        self.pass(body)
+    }
+
+    fn pass_class_declaration(
+        &mut self,
+        name: &'a str,
+        methods: &'a [Box<Statement<'a>>],
+        static_methods: &'a [Box<Statement<'a>>],
+        setters: &'a [Box<Statement<'a>>],
+        getters: &'a [Box<Statement<'a>>],
+        superclass: &'a Option<Expression<'a>>,
+        _statement: &'a Statement<'a>,
+    ) -> Result<(), Vec<ProgramError<'a>>> {
+        self.declare(name);
+        for ss in [methods, static_methods, setters, getters] {
+            for s in ss {
+                self.pass(s)?;
+            }
+        }
+        if let Some(e) = superclass {
+            self.pass_expression(e)?;
+        }
+        Ok(())
+    }
+
+    fn pass_trait_declaration(&mut self, name: &'a str, _statement: &'a Statement<'a>) -> Result<(), Vec<ProgramError<'a>>> {
+        self.declare(name);
+        Ok(())
+    }
+
+    fn pass_enum_declaration(
+        &mut self,
+        name: &'a str,
+        _variants: &'a [EnumVariant<'a>],
+        _statement: &'a Statement<'a>,
+    ) -> Result<(), Vec<ProgramError<'a>>> {
+        self.declare(name);
+        Ok(())
+    }
+
+    fn pass_function_declaration(
+        &mut self,
+        name: &'a str,
+        arguments: &'a [&'a str],
+        body: &'a [Box<Statement<'a>>],
+        statement: &'a Statement<'a>,
+        _context_variables: &'a [&'a str],
+    ) -> Result<(), Vec<ProgramError<'a>>> {
+        self.declare(name);
+        for argument in arguments {
+            self.declare(argument);
+        }
+        if let StatementType::FunctionDeclaration {
+            keyword_arguments: Some(keyword_arguments),
+            ..
+        } = &statement.statement_type
+        {
+            self.declare(keyword_arguments);
+        }
+        for s in body {
+            self.pass(s)?;
+        }
+        Ok(())
+    }
+
+    fn pass_anonymous_function(
+        &mut self,
+        arguments: &'a [&'a str],
+        body: &'a [Statement<'a>],
+        _expression: &'a Expression<'a>,
+    ) -> Result<(), Vec<ProgramError<'a>>> {
+        for argument in arguments {
+            self.declare(argument);
+        }
+        for s in body {
+            self.pass(s)?;
+        }
+        Ok(())
+    }
+
+    fn pass_module_literal(
+        &mut self,
+        _module: &'a str,
+        _field: &'a Expression<'a>,
+        _expression: &'a Expression<'a>,
+    ) -> Result<(), Vec<ProgramError<'a>>> {
+        Ok(())
+    }
+
+    fn pass_variable_literal(
+        &mut self,
+        identifier: &'a str,
+        expression: &'a Expression<'a>,
+    ) -> Result<(), Vec<ProgramError<'a>>> {
+        self.uses.push((self.current, identifier, expression.id()));
+        Ok(())
+    }
+
+    fn pass_variable_assignment(
+        &mut self,
+        identifier: &'a str,
+        value: &'a Expression<'a>,
+        _expression: &'a Expression<'a>,
+    ) -> Result<(), Vec<ProgramError<'a>>> {
+        self.assigned.insert(identifier);
+        self.pass_expression(value)
+    }
+
+    fn pass_tuple_assignment(
+        &mut self,
+        targets: &'a [Box<Expression<'a>>],
+        value: &'a Expression<'a>,
+        _expression: &'a Expression<'a>,
+    ) -> Result<(), Vec<ProgramError<'a>>> {
+        for target in targets {
+            if let ExpressionType::VariableLiteral { identifier } = &target.expression_type {
+                self.assigned.insert(identifier);
+            }
+        }
+        self.pass_expression(value)
+    }
+}
+
+/// The literal a variable is declared with. Nil is left out, as reading a property of a
+/// nil variable reports the variable's name.
+fn literal_declaration<'a, 'b>(statement: &'b Statement<'a>) -> Option<&'b Literal<'a>> {
+    match &statement.statement_type {
+        StatementType::VariableDeclaration {
+            expression: Some(Expression {
+                expression_type: ExpressionType::ExpressionLiteral { value },
+                ..
+            }),
+            ..
+        } if *value != Literal::Keyword(DataKeyword::Nil) => Some(value),
+        _ => None,
+    }
+}
+
+/// Replaces the reads of global constants in `ss` by the literal they were declared with,
+/// so evaluating them doesn't go through the environments. Meant to run after resolution.
+pub fn propagate_constants(ss: &mut [Statement<'_>]) {
+    let uses = match ConstantPropagation::new().run(ss) {
+        Ok(uses) => uses,
+        Err(_) => return,
+    };
+    let mut literals = HashMap::default();
+    for (id, position) in uses {
+        if let Some(value) = literal_declaration(&ss[position]) {
+            literals.insert(id, value.clone());
+        }
+    }
+    if !literals.is_empty() {
+        rewrite_expressions(ss, &mut |expression| match literals.remove(&expression.id()) {
+            Some(value) => {
+                expression.expression_type = ExpressionType::ExpressionLiteral { value };
+                false
+            }
+            None => true,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::constants::propagate_constants;
+    use crate::interpreter::Interpreter;
+    use crate::interpreter::common_test::parse;
+    use parser::resolver::Resolver;
+    use parser::types::Pass;
+
+    #[test]
+    fn test_propagate_constants() {
+        let source = "fun early() { return rate; } var rate = 3; var total = rate * 2; \
+             fun later() { return rate + 1; } var result = later() + early(); \
+             var counter = 0; counter = counter + 1; \
+             var shadowed = 1; { var shadowed = 2; print shadowed; } var copy = shadowed;";
+        let plain_statements = parse(source);
+        let locals = Resolver::new().run(&plain_statements).unwrap();
+        let plain = Interpreter::new(&[], "");
+        plain.locals.replace(locals.clone());
+        plain.run(&plain_statements).unwrap();
+
+        let mut folded = parse(source);
+        propagate_constants(&mut folded);
+        let dump = format!("{:?}", folded);
+        assert_eq!(dump.matches("VariableLiteral { identifier: \"rate\" }").count(), 1);
+        assert_eq!(dump.matches("ExpressionLiteral { value: Integer(3) }").count(), 3);
+        assert_eq!(dump.matches("VariableLiteral { identifier: \"counter\" }").count(), 1);
+        assert_eq!(dump.matches("VariableLiteral { identifier: \"shadowed\" }").count(), 2);
+
+        let propagated = Interpreter::new(&[], "");
+        propagated.locals.replace(locals);
+        propagated.run(&folded).unwrap();
+        for name in ["rate", "total", "result", "counter", "copy"].iter() {
+            assert_eq!(propagated.state.borrow().get_global(name), plain.state.borrow().get_global(name));
+        }
+    }
+}
@@ -1,48 +1,131 @@
 use ahash::{AHashMap as HashMap};
-use crate::value::Value;
-use parser::types::{ProgramError, SourceCodeLocation, Statement};
+use crate::value::{LoxMap, MapKey, Value};
+use parser::types::{Annotation, ErrorCode, Expression, ExpressionType, ProgramError, SourceCodeLocation, Statement, StatementType};
 use std::cell::RefCell;
 use std::fmt::{Debug, Error, Formatter};
 use std::rc::Rc;
 use crate::interpreter::Interpreter;
+use crate::stack;
+
+/// The call in a `return f(...);` statement, whose value can go straight back to the caller
+/// instead of through `State::return_value`.
+fn tail_call<'a>(statement: &'a Statement<'a>) -> Option<&'a Expression<'a>> {
+    match &statement.statement_type {
+        StatementType::Return {
+            value: Some(expression @ Expression { expression_type: ExpressionType::Call { .. }, .. }),
+        } => Some(expression),
+        _ => None,
+    }
+}
 
 #[derive(Clone, PartialEq)]
 pub struct LoxFunction<'a> {
+    pub name: Option<&'a str>,
     pub arguments: Vec<&'a str>,
+    pub keyword_arguments: Option<&'a str>,
     pub environments: Vec<Rc<RefCell<HashMap<&'a str, Value<'a>>>>>,
     pub body: Vec<&'a Statement<'a>>,
     pub location: SourceCodeLocation<'a>,
+    pub annotations: &'a [Annotation<'a>],
 }
 
 impl<'a> LoxFunction<'a> {
+    /// Orders the positional and named arguments of a call by parameter. Named arguments
+    /// that don't match a parameter are collected into a map for the `**` parameter.
+    pub fn bind_arguments(
+        &self,
+        values: Vec<Value<'a>>,
+        named_values: Vec<(&'a str, Value<'a>)>,
+    ) -> Result<Vec<Value<'a>>, String> {
+        if named_values.is_empty() && self.keyword_arguments.is_none() {
+            return Ok(values);
+        }
+        if values.len() > self.arguments.len() {
+            return Err(format!(
+                "Wrong number of arguments: Received {}, expected {}",
+                values.len(),
+                self.arguments.len(),
+            ));
+        }
+        let mut slots: Vec<Option<Value<'a>>> = values.into_iter().map(Some).collect();
+        slots.resize(self.arguments.len(), None);
+        let mut rest = LoxMap::default();
+        for (name, value) in named_values {
+            let key = MapKey::String(name.to_owned());
+            match self.arguments.iter().position(|a| *a == name) {
+                Some(i) if slots[i].is_some() => return Err(format!("Argument `{}` given more than once!", name)),
+                Some(i) => slots[i] = Some(value),
+                None if self.keyword_arguments.is_none() => {
+                    return Err(format!("Unexpected named argument `{}`!", name))
+                }
+                None if rest.get(&key).is_some() => return Err(format!("Argument `{}` given more than once!", name)),
+                None => rest.insert(key, value),
+            }
+        }
+        let mut arguments = slots
+            .into_iter()
+            .zip(self.arguments.iter())
+            .map(|(value, name)| value.ok_or_else(|| format!("Missing argument `{}`!", name)))
+            .collect::<Result<Vec<Value<'a>>, String>>()?;
+        if self.keyword_arguments.is_some() {
+            arguments.push(Value::new_map(rest));
+        }
+        Ok(arguments)
+    }
+
     pub fn eval(
         &self,
         values: &[Value<'a>],
         interpreter: &'a Interpreter<'a>,
     ) -> Result<Value<'a>, ProgramError<'a>> {
-        if self.arguments.len() != values.len() {
+        interpreter.hooks.function_enter(self.name, &self.location);
+        let result = if interpreter.config.heap_call_stack {
+            stack::grow(|| self.eval_body(values, interpreter))
+        } else {
+            self.eval_body(values, interpreter)
+        };
+        interpreter.hooks.function_exit(self.name, &self.location);
+        result
+    }
+
+    fn eval_body(
+        &self,
+        values: &[Value<'a>],
+        interpreter: &'a Interpreter<'a>,
+    ) -> Result<Value<'a>, ProgramError<'a>> {
+        let expected = self.arguments.len() + self.keyword_arguments.iter().count();
+        if expected != values.len() {
             return Err(ProgramError {
+                code: ErrorCode::ArgumentError,
                 message: format!(
                     "Wrong number of arguments: Received {}, expected {}",
                     values.len(),
-                    self.arguments.len(),
+                    expected,
                 ),
                 location: self.location.clone(),
             });
         }
         let prev_margin = interpreter.state.borrow().view_margin;
+        let prev_in_function = interpreter.state.borrow().in_function;
         {
             let mut s = interpreter.state.borrow_mut();
             s.environments.extend_from_slice(&self.environments);
             s.view_margin = s.environments.len() - self.environments.len();
             s.push();
-            for (name, value) in self.arguments.iter().zip(values.iter().cloned()) {
+            let names = self.arguments.iter().chain(self.keyword_arguments.iter());
+            for (name, value) in names.zip(values.iter().cloned()) {
                 s.insert_top(name, value);
             }
             s.in_function = true;
         }
         let mut value = Value::Nil;
-        for st in self.body.iter() {
+        for (i, st) in self.body.iter().enumerate() {
+            let tail = if i + 1 == self.body.len() { tail_call(st) } else { None };
+            if let Some(call) = tail {
+                interpreter.hooks.statement(&st.location);
+                value = interpreter.evaluate_expression(call)?;
+                break;
+            }
             interpreter.evaluate(st)?;
             if let Some(box return_value) = &interpreter.state.borrow().return_value {
                 value = return_value.clone();
@@ -52,7 +135,7 @@ impl<'a> LoxFunction<'a> {
         {
             let mut s = interpreter.state.borrow_mut();
             s.return_value = None;
-            s.in_function = false;
+            s.in_function = prev_in_function;
             s.pop();
             let current_len = s.environments.len();
             s.view_margin = prev_margin;
@@ -79,3 +162,4 @@ impl<'a> Debug for LoxFunction<'a> {
         )
     }
 }
+
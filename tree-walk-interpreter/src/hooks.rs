@@ -0,0 +1,84 @@
+use parser::types::SourceCodeLocation;
+use std::rc::Rc;
+
+/// Called with the name of the function, `None` for anonymous ones, and where it's declared.
+pub type FunctionHook = Rc<dyn Fn(Option<&str>, &SourceCodeLocation)>;
+
+/// Called with the location of the statement about to be evaluated.
+pub type StatementHook = Rc<dyn Fn(&SourceCodeLocation)>;
+
+/// Callbacks fired from the evaluation loop, meant for profilers and debuggers. Hooks that
+/// aren't installed cost a branch.
+#[derive(Clone, Default)]
+pub struct Hooks {
+    pub on_function_enter: Option<FunctionHook>,
+    pub on_function_exit: Option<FunctionHook>,
+    pub on_statement: Option<StatementHook>,
+}
+
+impl Hooks {
+    pub(crate) fn function_enter(&self, name: Option<&str>, location: &SourceCodeLocation) {
+        if let Some(hook) = &self.on_function_enter {
+            hook(name, location);
+        }
+    }
+
+    pub(crate) fn function_exit(&self, name: Option<&str>, location: &SourceCodeLocation) {
+        if let Some(hook) = &self.on_function_exit {
+            hook(name, location);
+        }
+    }
+
+    pub(crate) fn statement(&self, location: &SourceCodeLocation) {
+        if let Some(hook) = &self.on_statement {
+            hook(location);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::interpreter::Interpreter;
+    use crate::interpreter::common_test::parse;
+    use parser::resolver::Resolver;
+    use parser::types::Pass;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_function_hooks_record_call_sequence() {
+        let ss = parse(
+            "fun inner() { return 1; }\n\
+             fun outer() { return inner() + 1; }\n\
+             var result = outer();\n\
+             var anonymous = fun () { return 2; };\n\
+             anonymous();"
+        );
+        let mut interpreter = Interpreter::new(&[], "");
+        interpreter.locals.replace(Resolver::new().run(&ss).unwrap());
+        let calls = Rc::new(RefCell::new(vec![]));
+        let lines = Rc::new(RefCell::new(vec![]));
+        let (enter_calls, exit_calls, statement_lines) = (calls.clone(), calls.clone(), lines.clone());
+        interpreter.hooks.on_function_enter = Some(Rc::new(move |name, location| {
+            enter_calls.borrow_mut().push(format!("enter {} {}", name.unwrap_or("<anonymous>"), location.line));
+        }));
+        interpreter.hooks.on_function_exit = Some(Rc::new(move |name, _| {
+            exit_calls.borrow_mut().push(format!("exit {}", name.unwrap_or("<anonymous>")));
+        }));
+        interpreter.hooks.on_statement = Some(Rc::new(move |location| {
+            statement_lines.borrow_mut().push(location.line);
+        }));
+        interpreter.run(&ss).unwrap();
+
+        assert_eq!(*calls.borrow(), vec![
+            "enter outer 1",
+            "enter inner 0",
+            "exit inner",
+            "exit outer",
+            "enter <anonymous> 3",
+            "exit <anonymous>",
+        ]);
+        assert_eq!(lines.borrow().first(), Some(&0));
+        assert!(lines.borrow().contains(&4));
+    }
+}
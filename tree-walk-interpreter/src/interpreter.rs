@@ -1,49 +1,66 @@
 use ahash::{AHashMap as HashMap, AHashSet as HashSet};
+use crate::array::array_method;
+use crate::builtins::register_builtins;
+use crate::config::{InterpreterConfig, NumericEquality};
+use crate::hooks::Hooks;
 use crate::function::LoxFunction;
+use crate::native::{NativeCallback, NativeFunction};
 use crate::class::{LoxObject, LoxClass};
+use crate::stack;
 use crate::state::State;
-use crate::value::{Value, ValueError, LoxTrait, LoxArray};
-use parser::types::{Expression, ExpressionType, FunctionHeader, ProgramError, SourceCodeLocation, Statement, StatementType, TokenType, Type};
+use crate::string::{string_buffer_method, string_method};
+use crate::template::template_method;
+use crate::value::{Coerce, Symbol, Value, ValueError, LoxTrait, LoxArray, LoxEnum, LoxEnumInstance, LoxMap, MapKey};
+use parser::types::{ErrorCode, Expression, ExpressionType, FunctionHeader, ProgramError, SourceCodeLocation, Statement, StatementType, TokenType, Type};
 use std::cell::{Cell, RefCell};
-use std::convert::{TryInto, TryFrom};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
 use std::iter::FromIterator;
-use std::ops::{Add, Div, Mul, Sub};
-use std::rc::Rc;
-use std::path::Path;
+use std::ops::{Add, Div, Mul, RangeInclusive, Rem, Sub};
+use std::rc::{Rc, Weak};
+use std::path::{Path, PathBuf};
 use std::fs::File;
 use std::io::Read;
+use std::thread;
 use parser::lexer::Lexer;
 use parser::parser::Parser;
 
 pub type EvaluationResult<'a> = Result<Value<'a>, ProgramError<'a>>;
 
-fn operation<'a, R, T: TryFrom<Value<'a>, Error=ValueError>>(l: Value<'a>, r: Value<'a>, op: fn(T, T) -> R) -> Result<R, ValueError> {
-    let l_number = l.try_into()?;
-    let r_number = r.try_into()?;
+type RetiredModules<'a> = (
+    HashMap<&'a str, Vec<Box<Statement<'a>>>>,
+    HashMap<&'a str, String>,
+    HashMap<&'a str, Rc<Interpreter<'a>>>,
+);
+
+fn operation<'a, R, T: Coerce<'a>>(l: Value<'a>, r: Value<'a>, op: fn(T, T) -> R) -> Result<R, ValueError> {
+    let l_number = T::coerce(l)?;
+    let r_number = T::coerce(r)?;
     Ok(op(l_number, r_number))
 }
 
-fn f32_math_operation<'a>(l: Value<'a>, r: Value<'a>, op: fn(f32, f32) -> f32) -> Result<Value<'a>, ValueError> {
-    Ok(Value::Float {
-        value: operation(l, r, op)?,
-    })
+/// Operands of an arithmetic operation after promotion.
+enum NumericOperands {
+    Integers(i64, i64),
+    Floats(f32, f32),
 }
 
-fn i64_math_operation<'a>(l: Value<'a>, r: Value<'a>, op: fn(i64, i64) -> i64) -> Result<Value<'a>, ValueError> {
-    Ok(Value::Integer {
-        value: operation(l, r, op)?,
-    })
+/// Promotion rule shared by every arithmetic operator: two integers stay integers, and a
+/// float on either side turns both operands into floats, the same way `Value::coerce_number`
+/// converts integers.
+fn promote<'a>(l: Value<'a>, r: Value<'a>) -> Result<NumericOperands, ValueError> {
+    match (l, r) {
+        (Value::Integer { value: l }, Value::Integer { value: r }) => Ok(NumericOperands::Integers(l, r)),
+        (l, r) if l.is_number() && r.is_number() => Ok(NumericOperands::Floats(l.coerce_number()?, r.coerce_number()?)),
+        _ => Err(ValueError::ExpectingNumber),
+    }
 }
 
 fn math_operation<'a>(l: Value<'a>, r: Value<'a>, i64_op: fn(i64, i64) -> i64, f32_op: fn(f32, f32) -> f32) -> Result<Value<'a>, ValueError> {
-    match (&l, &r) {
-        (Value::Float { .. }, Value::Float { .. }) => f32_math_operation(l, r, f32_op),
-        (Value::Float { .. }, Value::Integer { value }) => f32_math_operation(l, Value::Float { value: *value as _ }, f32_op),
-        (Value::Integer { value }, Value::Float { .. }) => f32_math_operation(Value::Float { value: *value as _ }, r, f32_op),
-        (Value::Integer { .. }, Value::Integer { .. }) => i64_math_operation(l, r, i64_op),
-        _ => Err(ValueError::ExpectingNumber),
-
-    }
+    Ok(match promote(l, r)? {
+        NumericOperands::Integers(l, r) => Value::Integer { value: i64_op(l, r) },
+        NumericOperands::Floats(l, r) => Value::Float { value: f32_op(l, r) },
+    })
 }
 
 fn comparison_operation<'a>(l: Value<'a>, r: Value<'a>, op: fn(f32, f32) -> bool) -> Result<Value<'a>, ValueError> {
@@ -52,6 +69,21 @@ fn comparison_operation<'a>(l: Value<'a>, r: Value<'a>, op: fn(f32, f32) -> bool
     })
 }
 
+/// Lexes and parses `content`, failing with the first error found.
+pub fn parse_module<'a>(content: &'a str, name: &'a str) -> Result<Vec<Statement<'a>>, ProgramError<'a>> {
+    let mut lexer = Lexer::new(content, name);
+    lexer.parse()
+        .and_then(|tt| {
+            let parser = Parser::new(tt.into_iter().peekable());
+            parser.parse().map(|t| t.0)
+        })
+        .map_err(|ee| ee[0].clone())
+}
+
+fn is_module_import(statement: &Statement) -> bool {
+    matches!(statement.statement_type, StatementType::Import { .. } | StatementType::Module { .. })
+}
+
 fn statements_to_hash_set<'a>(statements: &[&Statement<'a>]) -> HashSet<FunctionHeader<'a>> {
     let mut map = HashSet::new();
     for s in statements {
@@ -82,6 +114,7 @@ fn check_trait_methods<'a>(
     let mut errors = vec![];
     for missed_method in missed_methods {
         errors.push(ProgramError {
+            code: ErrorCode::TypeError,
             location: location.clone(),
             message: format!(
                 "Missing method {} of arity {}, in trait implementation",
@@ -91,6 +124,7 @@ fn check_trait_methods<'a>(
     }
     for extra_method in extra_methods {
         errors.push(ProgramError {
+            code: ErrorCode::TypeError,
             location: location.clone(),
             message: format!(
                 "Method {} of arity {} is not in trait declaration",
@@ -131,28 +165,173 @@ fn is_class<'a>(lox_class: &LoxClass<'a>, obj: &LoxObject<'a>) -> bool {
 
 pub struct Interpreter<'a> {
     pub blacklist: RefCell<Vec<&'a str>>,
-    pub locals: HashMap<usize, usize>,
+    pub config: InterpreterConfig,
+    pub hooks: Hooks,
+    pub locals: RefCell<HashMap<usize, usize>>,
+    pub(crate) loop_caches: RefCell<Vec<HashMap<usize, Option<Value<'a>>>>>,
+    natives: RefCell<HashMap<&'a str, Value<'a>>>,
     modules: Cell<HashMap<&'a str, Vec<Box<Statement<'a>>>>>,
     module_contents: Cell<HashMap<&'a str, String>>,
-    module_interpreters: Cell<HashMap<&'a str, Box<Interpreter<'a>>>>,
+    module_interpreters: Cell<HashMap<&'a str, Rc<Interpreter<'a>>>>,
+    /// Statements, sources and interpreters of the modules `reset` forgot. Values from the
+    /// runs before can still point into them, so they live as long as the interpreter.
+    retired_modules: RefCell<Vec<RetiredModules<'a>>>,
+    /// Interpreters of the modules imported from files, by canonical path, shared with the
+    /// interpreters of those modules so a file imported from several places is loaded once.
+    /// The importers own the interpreters; holding them strongly here too would make every
+    /// module interpreter keep itself alive through its own copy of the cache.
+    module_cache: Rc<RefCell<HashMap<PathBuf, Weak<Interpreter<'a>>>>>,
+    /// Top level code of the module this interpreter runs, until its first use runs it.
+    pending_body: Cell<Option<&'a [Box<Statement<'a>>]>>,
+    interned_strings: Cell<HashSet<Box<str>>>,
+    symbols: Rc<RefCell<HashSet<Rc<str>>>>,
+    preloaded_modules: RefCell<HashMap<&'a str, Result<Vec<Statement<'a>>, ProgramError<'a>>>>,
     paths: &'a [String],
     pub state: RefCell<State<'a>>,
 }
 
 impl<'a> Interpreter<'a> {
     pub fn new(paths: &'a [String], file: &'a str,) -> Interpreter<'a> {
-        Interpreter {
+        let interpreter = Interpreter {
             blacklist: RefCell::new(vec![file]),
-            locals: HashMap::default(),
+            config: InterpreterConfig::default(),
+            hooks: Hooks::default(),
+            locals: RefCell::new(HashMap::default()),
+            loop_caches: RefCell::new(vec![]),
+            natives: RefCell::new(HashMap::default()),
             modules: Cell::new(HashMap::default()),
             module_contents: Cell::new(HashMap::default()),
             module_interpreters: Cell::new(HashMap::default()),
+            retired_modules: RefCell::new(vec![]),
+            module_cache: Rc::new(RefCell::new(HashMap::default())),
+            pending_body: Cell::new(None),
+            interned_strings: Cell::new(HashSet::default()),
+            symbols: Rc::new(RefCell::new(HashSet::default())),
+            preloaded_modules: RefCell::new(HashMap::default()),
             state: RefCell::new(State::default()),
             paths,
+        };
+        register_builtins(&interpreter);
+        interpreter
+    }
+
+    pub fn register_native(
+        &self,
+        name: &'a str,
+        arity: RangeInclusive<usize>,
+        function: NativeCallback<'a>,
+    ) {
+        self.natives.borrow_mut().insert(
+            name,
+            Value::NativeFunction(Rc::new(NativeFunction {
+                name,
+                arity,
+                function,
+            })),
+        );
+    }
+
+    /// Registers an object called `name` whose properties are the given native functions,
+    /// so they're called like `name.member(...)`.
+    pub fn register_native_namespace(
+        &self,
+        name: &'a str,
+        members: Vec<(&'a str, RangeInclusive<usize>, NativeCallback<'a>)>,
+    ) {
+        let members = members
+            .into_iter()
+            .map(|(member, arity, function)| {
+                (member, Value::NativeFunction(Rc::new(NativeFunction { name: member, arity, function })))
+            })
+            .collect();
+        self.natives.borrow_mut().insert(name, Value::Object(LoxObject::new_namespace(name, members)));
+    }
+
+    /// Names of the registered natives, which every program can use without declaring them.
+    pub fn builtin_names(&self) -> Vec<&'a str> {
+        self.natives.borrow().keys().copied().collect()
+    }
+
+    /// Returns a copy of `s` that lives as long as the interpreter, for runtime strings that
+    /// have to be used as names.
+    pub fn intern(&'a self, s: &str) -> &'a str {
+        let strings = unsafe { self.interned_strings.as_ptr().as_mut() }.unwrap();
+        if !strings.contains(s) {
+            strings.insert(s.into());
+        }
+        strings.get(s).unwrap()
+    }
+
+    /// Returns the symbol named `name`. The symbol table is shared with the interpreters of
+    /// imported modules, so a name maps to the same symbol everywhere in the program.
+    pub fn symbol(&self, name: &str) -> Symbol {
+        let mut symbols = self.symbols.borrow_mut();
+        if let Some(symbol) = symbols.get(name) {
+            return Symbol(symbol.clone());
+        }
+        let symbol: Rc<str> = name.into();
+        symbols.insert(symbol.clone());
+        Symbol(symbol)
+    }
+
+    /// Global variables sorted by name. Natives that aren't shadowed by a global are included
+    /// when `include_natives` is true.
+    pub fn globals(&self, include_natives: bool) -> Vec<(&'a str, Value<'a>)> {
+        let state = self.state.borrow();
+        let mut globals: Vec<(&'a str, Value<'a>)> = state.environments[0]
+            .borrow()
+            .iter()
+            .map(|(name, value)| (*name, value.clone()))
+            .collect();
+        if include_natives {
+            let natives = self.natives.borrow();
+            for (name, value) in natives.iter() {
+                if state.environments[0].borrow().get(name).is_none() {
+                    globals.push((*name, value.clone()));
+                }
+            }
+        }
+        globals.sort_unstable_by_key(|(name, _)| *name);
+        globals
+    }
+
+    pub fn check_collection_size(
+        &self,
+        size: usize,
+        location: &SourceCodeLocation<'a>,
+    ) -> Result<(), ProgramError<'a>> {
+        match self.config.max_collection_size {
+            Some(max) if size > max => Err(ProgramError {
+                code: ErrorCode::LimitExceeded,
+                location: location.clone(),
+                message: "Collection size limit exceeded".to_owned(),
+            }),
+            _ => Ok(()),
         }
     }
 
+    pub fn reset(&self) {
+        self.state.replace(State::default());
+        self.locals.borrow_mut().clear();
+        self.loop_caches.borrow_mut().clear();
+        self.module_cache.borrow_mut().clear();
+        self.retired_modules.borrow_mut().push((
+            self.modules.take(),
+            self.module_contents.take(),
+            self.module_interpreters.take(),
+        ));
+    }
+
     pub fn run(&'a self, content: &'a [Statement<'a>]) -> Result<(), ProgramError<'a>> {
+        if self.config.heap_call_stack {
+            // Moving once up front keeps every call made from the top level from moving
+            // to a stack of its own.
+            return stack::grow(|| self.run_statements(content).map(|_| Value::Nil)).map(|_| ());
+        }
+        self.run_statements(content)
+    }
+
+    fn run_statements(&'a self, content: &'a [Statement<'a>]) -> Result<(), ProgramError<'a>> {
         for s in content {
             self.evaluate(s)?;
         }
@@ -160,6 +339,14 @@ impl<'a> Interpreter<'a> {
     }
 
     pub fn evaluate_expression(&'a self, expression: &'a Expression<'a>) -> EvaluationResult<'a> {
+        if expression.is_loop_invariant() {
+            self.evaluate_loop_invariant(expression)
+        } else {
+            self.evaluate_expression_uncached(expression)
+        }
+    }
+
+    pub(crate) fn evaluate_expression_uncached(&'a self, expression: &'a Expression<'a>) -> EvaluationResult<'a> {
         match &expression.expression_type {
             ExpressionType::IsType {
                 value, checked_type
@@ -173,12 +360,12 @@ impl<'a> Interpreter<'a> {
             } => {
                 let value = self.look_up_variable(expression.id(), module)
                     .ok_or_else(|| {
-                        expression.create_program_error(&format!("Module `{}` not found!", module))
+                        expression.create_program_error(ErrorCode::NameError, &format!("Module `{}` not found!", module))
                     })?;
                 if let Value::Module(module) = value {
-                    self.get_module_interpreter(module).evaluate_expression(field)
+                    self.load_module(module)?.evaluate_expression(field)
                 } else {
-                    Err(expression.create_program_error(&format!("Variable `{}` is not a module", module)))
+                    Err(expression.create_program_error(ErrorCode::TypeError, &format!("Variable `{}` is not a module", module)))
                 }
             }
             ExpressionType::ArrayElementSet {
@@ -186,21 +373,19 @@ impl<'a> Interpreter<'a> {
                 index,
                 value,
             } => self.array_element_expression_set(array, index, value),
-            ExpressionType::ArrayElement { array, index } => {
-                self.array_element_expression(array, index)
-            }
-            ExpressionType::RepeatedElementArray { element, length } => {
-                let element = self.evaluate_expression(element)?;
-                let length = self.evaluate_expression(length)?;
-                if let Value::Integer { value: length } = length {
-                    let elements = vec![Box::new(element); length as _];
-                    Ok(Value::Array(Rc::new(RefCell::new(LoxArray {
-                        elements,
-                        capacity: length as _,
-                    }))))
-                } else {
-                    Err(expression.create_program_error("Array length should be an integer"))
-                }
+            ExpressionType::ArrayElement { .. } |
+            ExpressionType::Get { .. } |
+            ExpressionType::OptionalGet { .. } |
+            ExpressionType::Call { .. } => self.evaluate_chain(expression).map(|v| v.unwrap_or(Value::Nil)),
+            ExpressionType::RepeatedElementArray { element, length } =>
+                self.repeated_element_array(expression, element, length),
+            ExpressionType::Tuple { elements } => {
+                let elements = elements
+                    .iter()
+                    .map(|e| self.evaluate_expression(e))
+                    .collect::<Result<Vec<Value<'a>>, ProgramError<'a>>>()?;
+                self.check_collection_size(elements.len(), &expression.location)?;
+                Ok(Value::new_tuple(elements))
             }
             ExpressionType::Array { elements } => {
                 let elements =
@@ -211,44 +396,53 @@ impl<'a> Interpreter<'a> {
                             elements.push(Box::new(e));
                             Ok(elements)
                         })?;
+                self.check_collection_size(elements.len(), &expression.location)?;
                 Ok(Value::Array(Rc::new(RefCell::new(LoxArray {
                     capacity: elements.len(),
                     elements,
                 }))),
                 )
             }
+            ExpressionType::Map { entries } => {
+                let mut map = LoxMap::default();
+                for (key, value) in entries {
+                    let key_value = self.evaluate_expression(key)?;
+                    let map_key = MapKey::try_from(&key_value)
+                        .map_err(|e| e.into_program_error(&key.location))?;
+                    map.insert(map_key, self.evaluate_expression(value)?);
+                }
+                self.check_collection_size(map.len(), &expression.location)?;
+                Ok(Value::new_map(map))
+            }
             ExpressionType::Set {
                 callee,
                 property,
                 value,
             } => self.set_property(callee, property, value),
-            ExpressionType::Get { callee, property } => {
-                self.get_property(callee, property)
-            }
             ExpressionType::ExpressionLiteral { value } => Ok(value.into()),
             ExpressionType::VariableLiteral { identifier } =>
                 self.look_up_variable(expression.id(), identifier)
                     .ok_or_else(|| {
-                        expression.create_program_error(&format!("Variable `{}` not found!", identifier))
+                        expression.create_program_error(ErrorCode::NameError, &format!("Variable `{}` not found!", identifier))
                     }),
             ExpressionType::Grouping { expression } => self.evaluate_expression(expression),
             ExpressionType::Unary {
                 operand,
                 operator: TokenType::Minus,
             } => {
-                let v = self.evaluate_expression(operand)?;
-                if v.is_number() {
-                    Ok(-v)
-                } else {
-                    Err(expression.create_program_error("Can only negate numbers"))
-                }
+                self.evaluate_expression(operand)?
+                    .negate()
+                    .map_err(|e| match e {
+                        ValueError::IntegerOverflow => e.into_program_error(&expression.location),
+                        _ => operand.create_program_error(ErrorCode::TypeError, "Can only negate numbers"),
+                    })
             }
             ExpressionType::Unary {
                 operand,
                 operator: TokenType::Bang,
-            } => self.evaluate_expression(operand).map(|v| !v),
+            } => self.evaluate_condition(operand).map(|truthy| Value::Boolean { value: !truthy }),
             ExpressionType::Unary { .. } => {
-                Err(expression.create_program_error("Invalid unary operator"))
+                Err(expression.create_program_error(ErrorCode::RuntimeError, "Invalid unary operator"))
             }
             ExpressionType::Binary {
                 left,
@@ -264,7 +458,12 @@ impl<'a> Interpreter<'a> {
                 left,
                 right,
                 operator: TokenType::Slash,
-            } => self.div_expressions(left, right, &expression.location),
+            } => self.div_expressions(left, right, &expression.location, i64::checked_div, f32::div),
+            ExpressionType::Binary {
+                left,
+                right,
+                operator: TokenType::Percent,
+            } => self.div_expressions(left, right, &expression.location, i64::checked_rem, f32::rem),
             ExpressionType::Binary {
                 left,
                 right,
@@ -315,6 +514,14 @@ impl<'a> Interpreter<'a> {
                 right,
                 operator: TokenType::EqualEqual,
             } => self.eq_expressions(left, right),
+            ExpressionType::Binary {
+                left,
+                right,
+                operator: TokenType::DoubleQuestion,
+            } => match self.evaluate_expression(left)? {
+                Value::Nil => self.evaluate_expression(right),
+                value => Ok(value),
+            },
             ExpressionType::Binary {
                 left,
                 right,
@@ -327,8 +534,8 @@ impl<'a> Interpreter<'a> {
             } => self.boolean_expression(
                 left,
                 right,
-                |left_value, right_value| {
-                    if left_value.is_truthy() {
+                |left_truthy, left_value, right_value| {
+                    if left_truthy {
                         right_value
                     } else {
                         left_value
@@ -342,8 +549,8 @@ impl<'a> Interpreter<'a> {
             } => self.boolean_expression(
                 left,
                 right,
-                |left_value, right_value| {
-                    if left_value.is_truthy() {
+                |left_truthy, left_value, right_value| {
+                    if left_truthy {
                         left_value
                     } else {
                         right_value
@@ -351,7 +558,7 @@ impl<'a> Interpreter<'a> {
                 },
             ),
             ExpressionType::Binary { .. } => {
-                Err(expression.create_program_error("Invalid binary operator"))
+                Err(expression.create_program_error(ErrorCode::RuntimeError, "Invalid binary operator"))
             }
             ExpressionType::Conditional {
                 condition,
@@ -367,15 +574,19 @@ impl<'a> Interpreter<'a> {
                 value,
                 &expression.location,
             ),
-            ExpressionType::Call { callee, arguments } => {
-                self.call_expression(callee, arguments)
-            }
+            ExpressionType::TupleAssignment { targets, value } =>
+                self.tuple_assignment(targets, value, &expression.location),
+            ExpressionType::NamedArgument { .. } =>
+                Err(expression.create_program_error(ErrorCode::SyntaxError, "Named arguments are only allowed in calls!")),
             ExpressionType::AnonymousFunction { arguments, body } => {
                 let f = Value::Function(Rc::new(LoxFunction {
+                    name: None,
                     arguments: arguments.to_vec(),
+                    keyword_arguments: None,
                     body: body.iter().collect(),
                     environments: self.state.borrow().get_environments(),
                     location: expression.location.clone(),
+                    annotations: &[],
                 }));
                 Ok(f)
             }
@@ -385,6 +596,7 @@ impl<'a> Interpreter<'a> {
         &'a self,
         statement: &'a Statement<'a>,
     ) -> EvaluationResult<'a> {
+        self.hooks.statement(&statement.location);
         match &statement.statement_type {
             StatementType::EOF => {},
             StatementType::Module {
@@ -394,20 +606,35 @@ impl<'a> Interpreter<'a> {
                 self.process_module(name)?;
             },
             StatementType::Import { name, } => {
-                let statements = self.resolve_import(name, &statement.location)?
+                let path = self.find_import(name);
+                let cached = path.as_ref().and_then(|path| self.module_cache.borrow().get(path).and_then(Weak::upgrade));
+                if let Some(module) = cached {
+                    self.preloaded_modules.borrow_mut().remove(name);
+                    unsafe { self.module_interpreters.as_ptr().as_mut() }.unwrap().insert(*name, module);
+                    self.state.borrow_mut().insert_top(name, Value::Module(name));
+                    return Ok(Value::Nil);
+                }
+                let preloaded = self.preloaded_modules.borrow_mut().remove(name);
+                let statements = match preloaded {
+                    Some(statements) => statements?,
+                    None => self.resolve_import(name, &statement.location)?,
+                };
+                let statements = statements
                     .into_iter()
                     .map(Box::new)
                     .collect();
                 unsafe { self.modules.as_ptr().as_mut() }.unwrap().insert(*name, statements);
                 self.process_module(name)?;
+                if let Some(path) = path {
+                    self.module_cache.borrow_mut().insert(path, Rc::downgrade(self.get_module_interpreter(name)));
+                }
             }
             StatementType::If {
                 condition,
                 then,
                 otherwise,
             } => {
-                let cond_value = self.evaluate_expression(condition)?;
-                if cond_value.is_truthy() {
+                if self.evaluate_condition(condition)? {
                     self.evaluate(then)?;
                 } else if let Some(o) = otherwise {
                     self.evaluate(o)?;
@@ -434,9 +661,31 @@ impl<'a> Interpreter<'a> {
                 };
                 self.state.borrow_mut().insert_top(name, v);
             }
+            StatementType::TupleDeclaration { names, expression } => {
+                let values = match self.evaluate_expression(expression)? {
+                    Value::Tuple(elements) => elements.as_ref().clone(),
+                    Value::Array(a) => a.borrow().elements.iter().map(|e| *e.clone()).collect(),
+                    Value::EnumInstance(v) => v.values.clone(),
+                    _ => return Err(statement.create_program_error(ErrorCode::TypeError, "Only tuples, arrays and enum variants can be destructured")),
+                };
+                if values.len() != names.len() {
+                    return Err(statement.create_program_error(
+                        ErrorCode::InvalidValue,
+                        format!(
+                            "Can't destructure {} values into {} variables",
+                            values.len(),
+                            names.len()
+                        )
+                            .as_str(),
+                    ));
+                }
+                for (name, value) in names.iter().zip(values) {
+                    self.state.borrow_mut().insert_top(name, value);
+                }
+            }
             StatementType::PrintStatement { expression } => {
                 let v = self.evaluate_expression(expression)?;
-                println!("{}", v);
+                println!("{}", v.display(&self.config.print_format));
             }
             StatementType::TraitDeclaration {
                 name,
@@ -469,6 +718,7 @@ impl<'a> Interpreter<'a> {
                     {
                         if class.implements(t.name) {
                             return Err(statement.create_program_error(
+                                ErrorCode::TypeError,
                                 format!("{} already implements {}", class.name, t.name).as_str(),
                             ));
                         } else {
@@ -496,14 +746,23 @@ impl<'a> Interpreter<'a> {
                         class.append_setters(setters, envs.clone());
                     } else {
                         return Err(class_name
-                            .create_program_error("You can implement traits only on classes"));
+                            .create_program_error(ErrorCode::TypeError, "You can implement traits only on classes"));
                     }
                 } else {
                     return Err(
-                        class_name.create_program_error("You can implement traits only on classes")
+                        class_name.create_program_error(ErrorCode::TypeError, "You can implement traits only on classes")
                     );
                 }
             }
+            StatementType::EnumDeclaration { name, variants } => {
+                self.state.borrow_mut().insert_top(
+                    name,
+                    Value::Enum(Rc::new(LoxEnum {
+                        name,
+                        variants: variants.clone(),
+                    })),
+                );
+            }
             StatementType::ClassDeclaration {
                 getters,
                 name,
@@ -511,13 +770,22 @@ impl<'a> Interpreter<'a> {
                 setters,
                 static_methods,
                 superclass,
+                fields,
+                is_final,
+                annotations,
             } => {
                 let superclass = if let Some(e) = superclass {
                     let superclass = self.evaluate_expression(e)?;
                     if let Value::Class(c) = superclass {
+                        if c.is_final {
+                            return Err(statement.create_program_error(
+                                ErrorCode::TypeError,
+                                format!("Cannot inherit from final class '{}'", c.name).as_str(),
+                            ));
+                        }
                         Some(c)
                     } else {
-                        return Err(statement.create_program_error("Superclass must be a class"));
+                        return Err(statement.create_program_error(ErrorCode::TypeError, "Superclass must be a class"));
                     }
                 } else {
                     None
@@ -543,6 +811,9 @@ impl<'a> Interpreter<'a> {
                             .iter()
                             .map(|s| s.as_ref())
                             .collect::<Vec<&Statement>>(),
+                        fields,
+                        *is_final,
+                        annotations,
                         superclass,
                         environments,
                     ))),
@@ -551,14 +822,19 @@ impl<'a> Interpreter<'a> {
             StatementType::FunctionDeclaration {
                 name,
                 arguments,
+                keyword_arguments,
                 body,
+                annotations,
                 ..
             } => {
                 let environments = self.state.borrow().get_environments();
                 self.state.borrow_mut().insert(
                     name,
                     Value::Function(Rc::new(LoxFunction {
+                        annotations,
+                        name: Some(name),
                         arguments: arguments.clone(),
+                        keyword_arguments: *keyword_arguments,
                         body: body.into_iter().map(AsRef::as_ref).collect(),
                         location: statement.location.clone(),
                         environments,
@@ -573,31 +849,87 @@ impl<'a> Interpreter<'a> {
                 }
             },
             StatementType::Return { .. } =>
-                return Err(statement.create_program_error("Return outside function")),
+                return Err(statement.create_program_error(ErrorCode::RuntimeError, "Return outside function")),
             StatementType::While { condition, action } => {
                 self.state.borrow_mut().loop_count += 1;
-                while {
-                    let v = self.evaluate_expression(condition)?;
-                    self.state.borrow().loop_count > 0 && v.is_truthy()
-                } {
-                    self.evaluate(action)?;
-                    if self.state.borrow().broke_loop {
-                        break;
-                    }
-                }
+                self.push_loop_cache();
+                let result = self.evaluate_loop(condition, action);
+                self.pop_loop_cache();
                 self.state.borrow_mut().loop_count -= 1;
                 self.state.borrow_mut().broke_loop = false;
+                result?;
+            }
+            StatementType::ForIn { variable, iterable, body } => {
+                let items = match self.evaluate_expression(iterable)? {
+                    Value::Array(a) => a.borrow().elements.iter().map(|e| *e.clone()).collect(),
+                    Value::Tuple(elements) => elements.as_ref().clone(),
+                    Value::Map(m) => m
+                        .borrow()
+                        .iter()
+                        .map(|(key, value)| Value::new_array(vec![key.into(), value.clone()]))
+                        .collect(),
+                    _ => return Err(iterable.create_program_error(
+                        ErrorCode::TypeError,
+                        "Only arrays, tuples and maps can be iterated",
+                    )),
+                };
+                self.state.borrow_mut().loop_count += 1;
+                self.push_loop_cache();
+                let result = self.evaluate_for_in(variable, items, body);
+                self.pop_loop_cache();
+                self.state.borrow_mut().loop_count -= 1;
+                self.state.borrow_mut().broke_loop = false;
+                result?;
             }
             StatementType::Break if self.state.borrow().loop_count > 0 => {
                 self.state.borrow_mut().broke_loop = true;
             }
+            StatementType::RebindLoopVariables => {
+                // The loop variables live right outside the block that runs each iteration.
+                self.state.borrow_mut().copy_environment(1);
+            }
             StatementType::UpliftFunctionVariables(_) => return Ok(Value::Nil),
             StatementType::Break =>
-                return Err(statement.create_program_error("Break outside loop")),
+                return Err(statement.create_program_error(ErrorCode::RuntimeError, "Break outside loop")),
         };
         Ok(Value::Nil)
     }
 
+    fn evaluate_loop(&'a self, condition: &'a Expression<'a>, action: &'a Statement<'a>) -> Result<(), ProgramError<'a>> {
+        while {
+            let truthy = self.evaluate_condition(condition)?;
+            self.state.borrow().loop_count > 0 && truthy
+        } {
+            self.evaluate(action)?;
+            if self.state.borrow().broke_loop {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs `body` once per item, each time in a new environment holding the item in
+    /// `variable`.
+    fn evaluate_for_in(
+        &'a self,
+        variable: &'a str,
+        items: Vec<Value<'a>>,
+        body: &'a Statement<'a>,
+    ) -> Result<(), ProgramError<'a>> {
+        for item in items {
+            self.state.borrow_mut().push();
+            self.state.borrow_mut().insert_top(variable, item);
+            let result = self.evaluate(body);
+            self.state.borrow_mut().pop();
+            result?;
+            let state = self.state.borrow();
+            if state.broke_loop || state.return_value.is_some() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
     fn get_module_content(&'a self, name: &'a str) -> &'a str {
         unsafe { self.module_contents.as_ptr().as_ref() }.unwrap().get(name).unwrap()
     }
@@ -613,24 +945,32 @@ impl<'a> Interpreter<'a> {
     fn get_module_interpreter(
         &'a self,
         name: &'a str,
-    ) -> &'a Box<Interpreter<'a>> {
+    ) -> &'a Rc<Interpreter<'a>> {
         unsafe { self.module_interpreters.as_ptr().as_ref() }.unwrap().get(name).unwrap()
     }
 
+    /// Canonical path of the file `import name` reads: the first `name.sa` in the search
+    /// paths.
+    fn find_import(&self, name: &str) -> Option<PathBuf> {
+        self.paths
+            .iter()
+            .map(|path| Path::new(path).join(format!("{}.sa", name)))
+            .find(|path| path.exists())
+            .and_then(|path| path.canonicalize().ok())
+    }
+
     fn open_import(
         &'a self,
         name: &'a str,
         location: &SourceCodeLocation<'a>,
     ) -> Result<String, ProgramError<'a>> {
-        for path in self.paths {
-            let path = Path::new(path).join(format!("{}.sa", name));
-            if path.exists() {
-                let mut buffer = String::new();
-                File::open(path).unwrap().read_to_string(&mut buffer).unwrap();
-                return Ok(buffer);
-            }
+        if let Some(path) = self.find_import(name) {
+            let mut buffer = String::new();
+            File::open(path).unwrap().read_to_string(&mut buffer).unwrap();
+            return Ok(buffer);
         }
         Err(ProgramError {
+            code: ErrorCode::ImportError,
             location: location.clone(),
             message: format!("Can't find file {}.sa", name),
         })
@@ -643,6 +983,7 @@ impl<'a> Interpreter<'a> {
     ) -> Result<Vec<Statement<'a>>, ProgramError<'a>> {
         if self.blacklist.borrow().contains(&name) {
             return Err(ProgramError {
+                code: ErrorCode::ImportError,
                 message: format!("Circular import of {}", name),
                 location: location.clone(),
             });
@@ -650,13 +991,47 @@ impl<'a> Interpreter<'a> {
         let content = self.open_import(name, location)?;
         unsafe { self.module_contents.as_ptr().as_mut() }.unwrap()
             .insert(name, content);
-        let mut lexer = Lexer::new(self.get_module_content(name), name);
-        lexer.parse()
-            .and_then(|tt| {
-                let parser = Parser::new(tt.into_iter().peekable());
-                parser.parse().map(|t| t.0)
-            })
-            .map_err(|ee| ee[0].clone())
+        parse_module(self.get_module_content(name), name)
+    }
+
+    /// Reads and parses the modules imported at the top level of `content` in parallel, so
+    /// that evaluating their `import` statements later doesn't have to. Modules that can't be
+    /// read are left for the import statement to report.
+    pub fn preload_imports(&'a self, content: &'a [Statement<'a>]) {
+        let mut names = vec![];
+        for statement in content {
+            if let StatementType::Import { name } = &statement.statement_type {
+                if self.blacklist.borrow().contains(name) || names.contains(name) {
+                    continue;
+                }
+                if let Ok(module_content) = self.open_import(name, &statement.location) {
+                    unsafe { self.module_contents.as_ptr().as_mut() }.unwrap()
+                        .insert(name, module_content);
+                    names.push(*name);
+                }
+            }
+        }
+        if names.is_empty() {
+            return;
+        }
+        let workers = thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk_size = (names.len() + workers - 1) / workers;
+        let sources: Vec<(&'a str, &'a str)> = names.into_iter()
+            .map(|name| (name, self.get_module_content(name)))
+            .collect();
+        let parsed: Vec<(&'a str, Result<Vec<Statement<'a>>, ProgramError<'a>>)> = thread::scope(|scope| {
+            let handles: Vec<_> = sources.chunks(chunk_size)
+                .map(|chunk| scope.spawn(move || {
+                    chunk.iter()
+                        .map(|(name, source)| (*name, parse_module(source, name)))
+                        .collect::<Vec<_>>()
+                }))
+                .collect();
+            handles.into_iter()
+                .flat_map(|handle| handle.join().expect("Module parser thread panicked"))
+                .collect()
+        });
+        self.preloaded_modules.borrow_mut().extend(parsed);
     }
 
     fn process_module<'b>(
@@ -666,16 +1041,38 @@ impl<'a> Interpreter<'a> {
         let statements = self.get_module_statements(name);
         let mut interpreter = Interpreter::new(&self.paths, name);
         interpreter.locals = self.locals.clone();
+        interpreter.natives = self.natives.clone();
+        interpreter.config = self.config.clone();
+        interpreter.hooks = self.hooks.clone();
+        interpreter.symbols = self.symbols.clone();
+        interpreter.module_cache = self.module_cache.clone();
         interpreter.blacklist.borrow_mut().extend(&*self.blacklist.borrow());
-        unsafe { self.module_interpreters.as_ptr().as_mut() }.unwrap().insert(name, Box::new(interpreter));
-        for statement in statements {
+        unsafe { self.module_interpreters.as_ptr().as_mut() }.unwrap().insert(name, Rc::new(interpreter));
+        for statement in statements.iter().filter(|s| is_module_import(s)) {
             self.get_module_interpreter(name)
                 .evaluate(statement)?;
         }
+        self.get_module_interpreter(name).pending_body.set(Some(statements));
         self.state.borrow_mut().insert_top(name, Value::Module(name));
         Ok(())
     }
 
+    /// Returns the interpreter of the module, running its top level code first if this is the
+    /// first time one of its members is used. Imports were already processed by
+    /// `process_module`.
+    fn load_module(
+        &'a self,
+        name: &'a str,
+    ) -> Result<&'a Rc<Interpreter<'a>>, ProgramError<'a>> {
+        let interpreter = self.get_module_interpreter(name);
+        if let Some(statements) = interpreter.pending_body.take() {
+            for statement in statements.iter().filter(|s| !is_module_import(s)) {
+                interpreter.evaluate(statement)?;
+            }
+        }
+        Ok(interpreter)
+    }
+
     fn variable_assignment(
         &'a self,
         name: &'a str,
@@ -683,38 +1080,127 @@ impl<'a> Interpreter<'a> {
         expression: &'a Expression<'a>,
         location: &SourceCodeLocation<'a>,
     ) -> EvaluationResult<'a> {
-        match self.locals.get(&id) {
+        let env = self.locals.borrow().get(&id).copied();
+        match env {
             Some(env) => {
                 let value = self.evaluate_expression(expression)?;
-                self.state.borrow_mut().assign_at(*env, name, &value);
+                self.state.borrow_mut().assign_at(env, name, &value);
                 Ok(value)
             }
             None => Err(ProgramError {
+                code: ErrorCode::NameError,
                 location: location.clone(),
                 message: format!("Variable `{}` not found!", name),
             }),
         }
     }
 
+    /// Assigns the elements of a tuple, array or enum variant to several variables. All of them are
+    /// evaluated before the first assignment, so `(a, b) = (b, a)` swaps `a` and `b`.
+    fn tuple_assignment(
+        &'a self,
+        targets: &'a [Box<Expression<'a>>],
+        value: &'a Expression<'a>,
+        location: &SourceCodeLocation<'a>,
+    ) -> EvaluationResult<'a> {
+        let result = self.evaluate_expression(value)?;
+        let values = match &result {
+            Value::Tuple(elements) => elements.as_ref().clone(),
+            Value::Array(a) => a.borrow().elements.iter().map(|e| *e.clone()).collect(),
+            Value::EnumInstance(v) => v.values.clone(),
+            _ => return Err(value.create_program_error(ErrorCode::TypeError, "Only tuples, arrays and enum variants can be destructured")),
+        };
+        if values.len() != targets.len() {
+            return Err(ProgramError {
+                code: ErrorCode::InvalidValue,
+                location: location.clone(),
+                message: format!("Can't assign {} values to {} variables", values.len(), targets.len()),
+            });
+        }
+        for (target, value) in targets.iter().zip(values) {
+            if let ExpressionType::VariableLiteral { identifier } = &target.expression_type {
+                let env = self.locals.borrow().get(&target.id()).copied();
+                match env {
+                    Some(env) => self.state.borrow_mut().assign_at(env, identifier, &value),
+                    None => return Err(target.create_program_error(
+                        ErrorCode::NameError,
+                        &format!("Variable `{}` not found!", identifier),
+                    )),
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Evaluates a chain of property accesses, calls and indexing. Returns `None` when an
+    /// optional property access (`?.`) finds a nil receiver, skipping the rest of the chain.
+    fn evaluate_chain(
+        &'a self,
+        expression: &'a Expression<'a>,
+    ) -> Result<Option<Value<'a>>, ProgramError<'a>> {
+        let value = match &expression.expression_type {
+            ExpressionType::Get { callee, property } => match self.evaluate_chain(callee)? {
+                Some(object) => self.get_property(callee, object, property)?,
+                None => return Ok(None),
+            },
+            ExpressionType::OptionalGet { callee, property } => match self.evaluate_chain(callee)? {
+                Some(Value::Nil) | None => return Ok(None),
+                Some(object) => self.get_property(callee, object, property)?,
+            },
+            ExpressionType::Call { callee, arguments } => match self.evaluate_callee(callee)? {
+                Some(function_value) => self.call_expression(callee, function_value, arguments)?,
+                None => return Ok(None),
+            },
+            ExpressionType::ArrayElement { array, index } => match self.evaluate_chain(array)? {
+                Some(array_value) => self.array_element_expression(array, array_value, index)?,
+                None => return Ok(None),
+            },
+            _ => self.evaluate_expression(expression)?,
+        };
+        Ok(Some(value))
+    }
+
+    /// Like `evaluate_chain`, but a weakly bound method read from an object is bound
+    /// strongly, so a receiver nothing else holds, as in `A().f()`, lives through the call.
+    fn evaluate_callee(
+        &'a self,
+        callee: &'a Expression<'a>,
+    ) -> Result<Option<Value<'a>>, ProgramError<'a>> {
+        let (object_expression, property) = match &callee.expression_type {
+            ExpressionType::Get { callee, property } | ExpressionType::OptionalGet { callee, property } =>
+                (callee, property),
+            _ => return self.evaluate_chain(callee),
+        };
+        let object = match self.evaluate_chain(object_expression)? {
+            Some(Value::Nil) if matches!(callee.expression_type, ExpressionType::OptionalGet { .. }) =>
+                return Ok(None),
+            Some(object) => object,
+            None => return Ok(None),
+        };
+        // `object` keeps the receiver alive until the method is bound to it.
+        Ok(Some(match self.get_property(object_expression, object.clone(), property)? {
+            Value::WeakMethod(f, this) => match this.0.upgrade() {
+                Some(this) => Value::Method(f, this),
+                None => Value::WeakMethod(f, this),
+            },
+            value => value,
+        }))
+    }
+
     fn call_expression(
         &'a self,
         callee: &'a Expression<'a>,
+        function_value: Value<'a>,
         arguments: &'a [Box<Expression<'a>>],
     ) -> EvaluationResult<'a> {
-        let function_value = self.evaluate_expression(callee)?;
-        match function_value {
-            Value::Class(c) => {
-                let instance = LoxObject::new(c);
-                let mut values = vec![];
-                for e in arguments {
-                    let value = self.evaluate_expression(e)?;
-                    values.push(value);
-                }
-                instance.init(&values, &self, &callee.location)?;
-                Ok(Value::Object(instance))
-            }
-            Value::Function(f) if f.arguments.len() != arguments.len() => Err(callee
+        let has_named_arguments = arguments
+            .iter()
+            .any(|a| matches!(a.expression_type, ExpressionType::NamedArgument { .. }));
+        let positional_only = |f: &LoxFunction| !has_named_arguments && f.keyword_arguments.is_none();
+        match &function_value {
+            Value::Function(f) if positional_only(f) && f.arguments.len() != arguments.len() => return Err(callee
                 .create_program_error(
+                    ErrorCode::ArgumentError,
                     format!(
                         "Wrong number of arguments! Expected: {} Got: {}",
                         f.arguments.len(),
@@ -722,8 +1208,10 @@ impl<'a> Interpreter<'a> {
                     )
                         .as_str(),
                 )),
-            Value::Method(f, _) if f.arguments.len() != arguments.len() + 1 => Err(callee
+            Value::Method(f, _) | Value::WeakMethod(f, _)
+                if positional_only(f) && f.arguments.len() != arguments.len() + 1 => return Err(callee
                 .create_program_error(
+                    ErrorCode::ArgumentError,
                     format!(
                         "Wrong number of arguments in method! Expected: {} Got: {}",
                         f.arguments.len(),
@@ -731,23 +1219,98 @@ impl<'a> Interpreter<'a> {
                     )
                         .as_str(),
                 )),
+            Value::Class(_) | Value::Function(_) | Value::Method(..) | Value::WeakMethod(..) | Value::NativeFunction(_) |
+            Value::NativeMethod(..) | Value::EnumVariant(..) => {}
+            _ => return Err(callee.create_program_error(ErrorCode::TypeError, "Only functions or classes can be called!")),
+        }
+        let mut values = vec![];
+        let mut named_values = vec![];
+        for e in arguments {
+            match &e.expression_type {
+                ExpressionType::NamedArgument { name, value } =>
+                    named_values.push((*name, self.evaluate_expression(value)?)),
+                _ => values.push(self.evaluate_expression(e)?),
+            }
+        }
+        self.call_value_with_named(function_value, values, named_values, &callee.location)
+    }
+
+    pub fn call_value(
+        &'a self,
+        function_value: Value<'a>,
+        values: Vec<Value<'a>>,
+        location: &SourceCodeLocation<'a>,
+    ) -> EvaluationResult<'a> {
+        self.call_value_with_named(function_value, values, vec![], location)
+    }
+
+    /// Calls `function_value` with positional and named arguments. Named arguments are only
+    /// accepted by functions, methods and class constructors.
+    pub fn call_value_with_named(
+        &'a self,
+        function_value: Value<'a>,
+        values: Vec<Value<'a>>,
+        named_values: Vec<(&'a str, Value<'a>)>,
+        location: &SourceCodeLocation<'a>,
+    ) -> EvaluationResult<'a> {
+        let bind = |f: &LoxFunction<'a>, values| {
+            f.bind_arguments(values, named_values.clone()).map_err(|message| ProgramError {
+                code: ErrorCode::ArgumentError,
+                location: location.clone(),
+                message,
+            })
+        };
+        match function_value {
             Value::Method(f, this) => {
-                let mut values = vec![Value::Object(this)];
-                for e in arguments {
-                    let value = self.evaluate_expression(e)?;
-                    values.push(value);
-                }
-                f.eval(&values, &self)
+                let mut method_values = vec![Value::Object(this)];
+                method_values.extend(values);
+                f.eval(&bind(&f, method_values)?, &self)
             }
-            Value::Function(f) => {
-                let mut values = vec![];
-                for e in arguments {
-                    let value = self.evaluate_expression(e)?;
-                    values.push(value);
+            Value::WeakMethod(f, this) => match this.0.upgrade() {
+                Some(this) => self.call_value_with_named(Value::Method(f, this), values, named_values, location),
+                None => Err(ProgramError {
+                    code: ErrorCode::RuntimeError,
+                    location: location.clone(),
+                    message: "Method receiver was dropped".to_owned(),
+                }),
+            },
+            Value::Function(f) => f.eval(&bind(&f, values)?, &self),
+            Value::Class(c) => {
+                let instance = LoxObject::new(c, self.config.weak_method_binding);
+                instance.init(&values, &named_values, &self, location)?;
+                Ok(Value::Object(instance))
+            }
+            _ if !named_values.is_empty() => Err(ProgramError {
+                code: ErrorCode::ArgumentError,
+                location: location.clone(),
+                message: "Named arguments can only be passed to functions, methods and classes!".to_owned(),
+            }),
+            Value::NativeFunction(f) => f.eval(&values, &self, location),
+            Value::NativeMethod(f, receiver) => f.eval_method(*receiver, values, &self, location),
+            Value::EnumVariant(lox_enum, variant) => {
+                let fields = lox_enum.variant(variant).map_or(0, |v| v.fields.len());
+                if fields != values.len() {
+                    return Err(ProgramError {
+                        code: ErrorCode::ArgumentError,
+                        location: location.clone(),
+                        message: format!(
+                            "Wrong number of arguments! Expected: {} Got: {}",
+                            fields,
+                            values.len()
+                        ),
+                    });
                 }
-                f.eval(&values, &self)
+                Ok(Value::EnumInstance(Rc::new(LoxEnumInstance {
+                    lox_enum,
+                    variant,
+                    values,
+                })))
             }
-            _ => Err(callee.create_program_error("Only functions or classes can be called!")),
+            _ => Err(ProgramError {
+                code: ErrorCode::TypeError,
+                location: location.clone(),
+                message: "Only functions or classes can be called!".to_owned(),
+            }),
         }
     }
 
@@ -757,9 +1320,8 @@ impl<'a> Interpreter<'a> {
         then_branch: &'a Expression<'a>,
         else_branch: &'a Expression<'a>,
     ) -> EvaluationResult<'a> {
-        let condition = self.evaluate_expression(condition)?;
         self.evaluate_expression(
-             if condition.is_truthy() {
+             if self.evaluate_condition(condition)? {
                  then_branch
              } else {
                  else_branch
@@ -771,11 +1333,37 @@ impl<'a> Interpreter<'a> {
         &'a self,
         left: &'a Expression<'a>,
         right: &'a Expression<'a>,
-        op: fn(Value<'a>, Value<'a>) -> Value<'a>,
+        op: fn(bool, Value<'a>, Value<'a>) -> Value<'a>,
     ) -> EvaluationResult<'a> {
         let left_value = self.evaluate_expression(left)?;
         let right_value = self.evaluate_expression(right)?;
-        Ok(op(left_value, right_value))
+        let left_truthy = self.is_truthy(&left_value, &left.location)?;
+        Ok(op(left_truthy, left_value, right_value))
+    }
+
+    fn evaluate_condition(&'a self, condition: &'a Expression<'a>) -> Result<bool, ProgramError<'a>> {
+        let value = self.evaluate_expression(condition)?;
+        self.is_truthy(&value, &condition.location)
+    }
+
+    /// Truthiness of `value` in a condition. Objects whose class defines a `to_bool` method
+    /// are as truthy as it says; everything else follows `Value::is_truthy`.
+    pub(crate) fn is_truthy(&'a self, value: &Value<'a>, location: &SourceCodeLocation<'a>) -> Result<bool, ProgramError<'a>> {
+        let method = match value {
+            Value::Object(instance) => match instance.get("to_bool") {
+                Some(method @ Value::Method(..)) | Some(method @ Value::WeakMethod(..)) => method,
+                _ => return Ok(true),
+            },
+            _ => return Ok(value.is_truthy()),
+        };
+        match self.call_value(method, vec![], location)? {
+            Value::Boolean { value } => Ok(value),
+            _ => Err(ProgramError {
+                code: ErrorCode::TypeError,
+                location: location.clone(),
+                message: "`to_bool` should return a boolean".to_owned(),
+            }),
+        }
     }
 
     fn value_comparison_operation(
@@ -799,10 +1387,32 @@ impl<'a> Interpreter<'a> {
         let left_value = self.evaluate_expression(left)?;
         let right_value = self.evaluate_expression(right)?;
         Ok(Value::Boolean {
-            value: left_value == right_value,
+            value: self.values_equal(&left_value, &right_value),
         })
     }
 
+    /// Equality as seen by `==`, following the configured numeric equality.
+    pub fn values_equal(&self, left: &Value<'a>, right: &Value<'a>) -> bool {
+        match (left, right, self.config.numeric_equality) {
+            (Value::Integer { value: i }, Value::Float { value: f }, NumericEquality::Promoting) |
+            (Value::Float { value: f }, Value::Integer { value: i }, NumericEquality::Promoting) =>
+                *i as f64 == *f as f64,
+            _ => left == right,
+        }
+    }
+
+    /// Ordering consistent with `values_equal`: with promoting numeric equality, an integer
+    /// and a float that are equal as floats compare as equal.
+    pub fn compare_values(&self, left: &Value<'a>, right: &Value<'a>) -> Option<Ordering> {
+        match (left, right, self.config.numeric_equality) {
+            (Value::Integer { value: l }, Value::Float { value: r }, NumericEquality::Promoting) =>
+                (*l as f64).partial_cmp(&(*r as f64)),
+            (Value::Float { value: l }, Value::Integer { value: r }, NumericEquality::Promoting) =>
+                (*l as f64).partial_cmp(&(*r as f64)),
+            _ => left.partial_cmp(right),
+        }
+    }
+
     fn value_math_operation(
         &'a self,
         left: &'a Expression<'a>,
@@ -817,25 +1427,57 @@ impl<'a> Interpreter<'a> {
             .map_err(|e| e.into_program_error(location))
     }
 
+    /// Division and remainder, which fail on a zero divisor or an overflowing `i64::MIN / -1`
+    /// instead of panicking.
     fn div_expressions(
         &'a self,
         left: &'a Expression<'a>,
         right: &'a Expression<'a>,
         location: &SourceCodeLocation<'a>,
+        i64_op: fn(i64, i64) -> Option<i64>,
+        f32_op: fn(f32, f32) -> f32,
     ) -> EvaluationResult<'a> {
         let left_value = self.evaluate_expression(left)?;
         let right_value = self.evaluate_expression(right)?;
         match right_value {
             Value::Float { value } if value == 0f32 => {
-                Err(right.create_program_error("Division by zero!"))
+                Err(right.create_program_error(ErrorCode::DivisionByZero, "Division by zero!"))
             }
             Value::Integer { value } if value == 0 => {
-                Err(right.create_program_error("Division by zero!"))
+                Err(right.create_program_error(ErrorCode::DivisionByZero, "Division by zero!"))
             }
             _ => Ok(()),
         }?;
-        math_operation(left_value, right_value, i64::div, f32::div)
-            .map_err(|e| e.into_program_error(&location))
+        match promote(left_value, right_value).map_err(|e| e.into_program_error(location))? {
+            NumericOperands::Integers(l, r) => i64_op(l, r)
+                .map(|value| Value::Integer { value })
+                .ok_or_else(|| ProgramError {
+                    code: ErrorCode::OverflowError,
+                    location: location.clone(),
+                    message: "Integer overflow!".to_owned(),
+                }),
+            NumericOperands::Floats(l, r) => Ok(Value::Float { value: f32_op(l, r) }),
+        }
+    }
+
+    fn repeated_element_array(
+        &'a self,
+        expression: &'a Expression<'a>,
+        element: &'a Expression<'a>,
+        length: &'a Expression<'a>,
+    ) -> EvaluationResult<'a> {
+        let element = self.evaluate_expression(element)?;
+        let length = match self.evaluate_expression(length)? {
+            Value::Integer { value } if value >= 0 => value as usize,
+            Value::Integer { .. } =>
+                return Err(expression.create_program_error(ErrorCode::InvalidValue, "Array length can't be negative")),
+            _ => return Err(expression.create_program_error(ErrorCode::TypeError, "Array length should be an integer")),
+        };
+        self.check_collection_size(length, &expression.location)?;
+        Ok(Value::Array(Rc::new(RefCell::new(LoxArray {
+            elements: vec![Box::new(element); length],
+            capacity: length,
+        }))))
     }
 
     fn add_expressions(
@@ -850,13 +1492,13 @@ impl<'a> Interpreter<'a> {
             math_operation(left_value, right_value, i64::add, f32::add)
                 .map_err(|e| e.into_program_error(location))
         } else {
-            let left_string: String = left_value
-                .try_into()
-                .map_err(|e: ValueError| e.into_program_error(location))?;
+            let left_string = left_value
+                .coerce_string()
+                .map_err(|e| e.into_program_error(location))?;
             let right_value = self.evaluate_expression(right)?;
-            let right_string: String = right_value
-                .try_into()
-                .map_err(|e: ValueError| e.into_program_error(location))?;
+            let right_string = right_value
+                .coerce_string()
+                .map_err(|e| e.into_program_error(location))?;
             Ok(Value::String {
                 value: format!("{}{}", left_string, right_string),
             })
@@ -866,9 +1508,9 @@ impl<'a> Interpreter<'a> {
     fn get_property(
         &'a self,
         callee: &'a Expression<'a>,
+        object: Value<'a>,
         property: &'a str,
     ) -> EvaluationResult<'a> {
-        let object = self.evaluate_expression(callee)?;
         match object {
             Value::Object(instance) => {
                 if let Some(v) = instance.get(property) {
@@ -878,7 +1520,7 @@ impl<'a> Interpreter<'a> {
                         v.eval(&[Value::Object(instance)], &self)
                     } else {
                         Err(callee
-                            .create_program_error(format!("Undefined property {}.", property).as_str()))
+                            .create_program_error(ErrorCode::NameError, format!("Undefined property {}.", property).as_str()))
                     }
                 }
             }
@@ -887,13 +1529,73 @@ impl<'a> Interpreter<'a> {
                     Ok(v)
                 } else {
                     Err(callee
-                        .create_program_error(format!("Undefined property {}.", property).as_str()))
+                        .create_program_error(ErrorCode::NameError, format!("Undefined property {}.", property).as_str()))
                 }
             }
-            _ => Err(callee.create_program_error("Only instances have properties")),
+            Value::String { .. } => match string_method(property) {
+                Some(method) => Ok(Value::NativeMethod(Rc::new(method), Box::new(object))),
+                None => Err(callee
+                    .create_program_error(ErrorCode::NameError, format!("Undefined property {}.", property).as_str())),
+            },
+            Value::StringBuffer(_) => match string_buffer_method(property) {
+                Some(method) => Ok(Value::NativeMethod(Rc::new(method), Box::new(object))),
+                None => Err(callee
+                    .create_program_error(ErrorCode::NameError, format!("Undefined property {}.", property).as_str())),
+            },
+            Value::Template(_) => match template_method(property) {
+                Some(method) => Ok(Value::NativeMethod(Rc::new(method), Box::new(object))),
+                None => Err(callee
+                    .create_program_error(ErrorCode::NameError, format!("Undefined property {}.", property).as_str())),
+            },
+            Value::Array(_) => match array_method(property) {
+                Some(method) => Ok(Value::NativeMethod(Rc::new(method), Box::new(object))),
+                None => Err(callee
+                    .create_program_error(ErrorCode::NameError, format!("Undefined property {}.", property).as_str())),
+            },
+            Value::Enum(e) => match e.variant(property) {
+                Some(variant) if variant.fields.is_empty() => {
+                    Ok(Value::EnumInstance(Rc::new(LoxEnumInstance {
+                        lox_enum: e.clone(),
+                        variant: variant.name,
+                        values: vec![],
+                    })))
+                }
+                Some(variant) => Ok(Value::EnumVariant(e.clone(), variant.name)),
+                None => Err(callee.create_program_error(
+                    ErrorCode::NameError,
+                    format!("Undefined variant {} in enum {}.", property, e.name).as_str(),
+                )),
+            },
+            Value::Nil => Err(self.nil_receiver_error(callee, "read", property)),
+            _ => Err(callee.create_program_error(ErrorCode::TypeError, "Only instances have properties")),
         }
     }
 
+    /// Error for accessing `property` on a nil `callee`, naming the variable or property
+    /// the nil came from when the expression tells.
+    fn nil_receiver_error(
+        &self,
+        callee: &'a Expression<'a>,
+        action: &str,
+        property: &str,
+    ) -> ProgramError<'a> {
+        let origin = match &callee.expression_type {
+            ExpressionType::VariableLiteral { identifier } => format!(" (variable `{}`)", identifier),
+            ExpressionType::Get { property, .. } | ExpressionType::OptionalGet { property, .. } =>
+                format!(" (property `{}`)", property),
+            ExpressionType::ModuleLiteral { module, field } => match &field.expression_type {
+                ExpressionType::VariableLiteral { identifier } =>
+                    format!(" (variable `{}::{}`)", module, identifier),
+                _ => "".to_owned(),
+            },
+            _ => "".to_owned(),
+        };
+        callee.create_program_error(
+            ErrorCode::TypeError,
+            format!("Can't {} property `{}` of nil{}", action, property, origin).as_str(),
+        )
+    }
+
     fn set_property(
         &'a self,
         callee: &'a Expression<'a>,
@@ -906,11 +1608,56 @@ impl<'a> Interpreter<'a> {
             if let Some(f) = instance.get_setter(property) {
                 f.eval(&[Value::Object(instance), value], &self)
             } else {
+                self.check_known_field(&instance, property, &callee.location)?;
+                self.check_field_type(&instance, property, &value, &callee.location)?;
                 instance.set(property, value.clone());
                 Ok(value)
             }
+        } else if let Value::Nil = object {
+            Err(self.nil_receiver_error(callee, "set", property))
         } else {
-            Err(callee.create_program_error("Only instances have properties"))
+            Err(callee.create_program_error(ErrorCode::TypeError, "Only instances have properties"))
+        }
+    }
+
+    /// Fails in `strict_fields` mode when `property` isn't a field declared by the object's
+    /// class.
+    pub(crate) fn check_known_field(
+        &self,
+        object: &LoxObject<'a>,
+        property: &str,
+        location: &SourceCodeLocation<'a>,
+    ) -> Result<(), ProgramError<'a>> {
+        if self.config.strict_fields && !object.has_field(property) {
+            return Err(ProgramError {
+                code: ErrorCode::NameError,
+                location: location.clone(),
+                message: format!("Unknown field '{}'", property),
+            });
+        }
+        Ok(())
+    }
+
+    /// Fails when `property` was declared with a type in the object's class and `value`
+    /// isn't of that type. Undeclared and untyped fields accept anything.
+    pub(crate) fn check_field_type(
+        &'a self,
+        object: &LoxObject<'a>,
+        property: &str,
+        value: &Value<'a>,
+        location: &SourceCodeLocation<'a>,
+    ) -> Result<(), ProgramError<'a>> {
+        match object.get_field_type(property) {
+            Some(field_type) if self.is_value_type(value, field_type, location)? != (Value::Boolean { value: true }) =>
+                Err(ProgramError {
+                    code: ErrorCode::TypeError,
+                    location: location.clone(),
+                    message: format!(
+                        "Type error! Field `{}` of {} can't hold {}",
+                        property, object.class_name, value,
+                    ),
+                }),
+            _ => Ok(()),
         }
     }
 
@@ -920,8 +1667,21 @@ impl<'a> Interpreter<'a> {
         index: &'a Expression<'a>,
         value: &'a Expression<'a>,
     ) -> EvaluationResult<'a> {
+        let array_value = self.evaluate_expression(array)?;
+        if let Value::Map(m) = array_value {
+            let key = self.evaluate_map_key(index)?;
+            let value = self.evaluate_expression(value)?;
+            if m.borrow().get(&key).is_none() {
+                self.check_collection_size(m.borrow().len() + 1, &array.location)?;
+            }
+            m.borrow_mut().insert(key, value.clone());
+            return Ok(value);
+        }
+        if let Value::Tuple(_) = array_value {
+            return Err(array.create_program_error(ErrorCode::TypeError, "Tuples can't be modified"));
+        }
         self.array_element_operation(
-            array, index, |array, index_value| {
+            array, array_value, index, |array, index_value| {
                 let value = self.evaluate_expression(value)?;
                 array.borrow_mut().elements[index_value] = Box::new(value.clone());
                 Ok(value)
@@ -932,31 +1692,69 @@ impl<'a> Interpreter<'a> {
     fn array_element_expression(
         &'a self,
         array: &'a Expression<'a>,
+        array_value: Value<'a>,
         index: &'a Expression<'a>,
     ) -> EvaluationResult<'a> {
+        if let Value::Map(m) = array_value {
+            let key = self.evaluate_map_key(index)?;
+            return Ok(m.borrow().get(&key).cloned().unwrap_or(Value::Nil));
+        }
+        if let Value::Tuple(elements) = array_value {
+            let index_value = self.evaluate_index(index)?;
+            return elements.get(index_value as usize).cloned().ok_or_else(|| index.create_program_error(
+                ErrorCode::IndexError,
+                format!(
+                    "You can't access element {} in a tuple of {} elements",
+                    index_value, elements.len()
+                )
+                    .as_str(),
+            ));
+        }
+        if let Value::EnumInstance(v) = array_value {
+            let index_value = self.evaluate_index(index)?;
+            return v.values.get(index_value as usize).cloned().ok_or_else(|| index.create_program_error(
+                ErrorCode::IndexError,
+                format!(
+                    "You can't access field {} in a variant of {} fields",
+                    index_value, v.values.len()
+                )
+                    .as_str(),
+            ));
+        }
         self.array_element_operation(
-            array, index, |array, index_value| {
+            array, array_value, index, |array, index_value| {
                 Ok(*array.borrow().elements[index_value].clone())
             }
         )
     }
 
+    fn evaluate_index(&'a self, index: &'a Expression<'a>) -> Result<i64, ProgramError<'a>> {
+        let index_value = self.evaluate_expression(index)?;
+        index_value.coerce_integer().map_err(|e| index.create_program_error(
+            ErrorCode::TypeError,
+            e.to_string().as_str(),
+        ))
+    }
+
+    fn evaluate_map_key(&'a self, index: &'a Expression<'a>) -> Result<MapKey, ProgramError<'a>> {
+        let index_value = self.evaluate_expression(index)?;
+        MapKey::try_from(&index_value).map_err(|e| e.into_program_error(&index.location))
+    }
+
     fn array_element_operation<I: Fn(Rc<RefCell<LoxArray<'a>>>, usize) -> EvaluationResult<'a>>(
         &'a self,
         array: &'a Expression<'a>,
+        array_value: Value<'a>,
         index: &'a Expression<'a>,
         op: I,
     ) -> EvaluationResult<'a> {
-        let array_value = self.evaluate_expression(array)?;
         if let Value::Array(a) = array_value {
-            let index_value = self.evaluate_expression(index)?;
-            let index_value: i64 = i64::try_from(index_value).map_err(|e: ValueError| index.create_program_error(
-                e.to_string().as_str(),
-            ))?;
+            let index_value = self.evaluate_index(index)?;
             if (index_value as usize) < a.borrow().capacity {
                 op(a, index_value as usize)
             } else {
                 Err(index.create_program_error(
+                    ErrorCode::IndexError,
                     format!(
                         "You can't access element {} in an array of {} elements",
                         index_value, a.borrow().capacity
@@ -965,7 +1763,7 @@ impl<'a> Interpreter<'a> {
                 ))
             }
         } else {
-            Err(array.create_program_error("You can only index arrays"))
+            Err(array.create_program_error(ErrorCode::TypeError, "You can only index arrays and maps"))
         }
     }
 
@@ -974,10 +1772,11 @@ impl<'a> Interpreter<'a> {
         expression_id: usize,
         name: &str,
     ) -> Option<Value<'a>> {
-        if let Some(env) = self.locals.get(&expression_id) {
+        if let Some(env) = self.locals.borrow().get(&expression_id) {
             self.state.borrow().get_at(name, *env)
         } else {
             self.state.borrow().get_global(name)
+                .or_else(|| self.natives.borrow().get(name).cloned())
         }
     }
 
@@ -1000,9 +1799,17 @@ impl<'a> Interpreter<'a> {
             (Value::Module { .. }, _) => Ok(Value::Boolean { value: false }),
             (Value::String { .. }, Type::String) => Ok(Value::Boolean { value: true }),
             (Value::String { .. }, _) => Ok(Value::Boolean { value: false }),
+            (Value::StringBuffer(_), _) => Ok(Value::Boolean { value: false }),
+            (Value::Template(_), _) => Ok(Value::Boolean { value: false }),
             (Value::Array { .. }, Type::Array) => Ok(Value::Boolean { value: true }),
             (Value::Array { .. }, _) => Ok(Value::Boolean { value: false }),
+            (Value::Map(_), _) => Ok(Value::Boolean { value: false }),
+            (Value::Tuple(_), _) => Ok(Value::Boolean { value: false }),
             (Value::Function(_), Type::Function) => Ok(Value::Boolean { value: true }),
+            (Value::NativeFunction(_), Type::Function) => Ok(Value::Boolean { value: true }),
+            (Value::NativeFunction(_), _) => Ok(Value::Boolean { value: false }),
+            (Value::NativeMethod(..), Type::Function) => Ok(Value::Boolean { value: true }),
+            (Value::NativeMethod(..), _) => Ok(Value::Boolean { value: false }),
             (Value::Function(_), _) => Ok(Value::Boolean { value: false }),
             (Value::Trait { .. }, Type::Trait) => Ok(Value::Boolean { value: true }),
             (Value::Trait { .. }, _) => Ok(Value::Boolean { value: false }),
@@ -1018,13 +1825,28 @@ impl<'a> Interpreter<'a> {
                         Ok(Value::Boolean { value: is_trait(&t.name, obj) })
                     }
                     _ => Err(ProgramError {
+                        code: ErrorCode::TypeError,
                         location: c.location.clone(),
                         message: "Objects can only be either an implementation of a class or an object".to_owned(),
                     })
                 }
             },
             (Value::Object(_), _) => Ok(Value::Boolean { value: false }),
+            (Value::EnumInstance(v), Type::UserDefined(c)) => {
+                match self.evaluate_expression(c)? {
+                    Value::Enum(e) => Ok(Value::Boolean { value: Rc::ptr_eq(&e, &v.lox_enum) }),
+                    Value::EnumVariant(e, variant) => Ok(Value::Boolean {
+                        value: Rc::ptr_eq(&e, &v.lox_enum) && variant == v.variant,
+                    }),
+                    Value::EnumInstance(unit) => Ok(Value::Boolean {
+                        value: Rc::ptr_eq(&unit.lox_enum, &v.lox_enum) && unit.variant == v.variant,
+                    }),
+                    _ => Ok(Value::Boolean { value: false }),
+                }
+            }
+            (Value::EnumInstance(_), _) => Ok(Value::Boolean { value: false }),
             _ => Err(ProgramError {
+                code: ErrorCode::TypeError,
                 location: location.clone(),
                 message: "Invalid value to check for type".to_owned(),
             })
@@ -1033,8 +1855,12 @@ impl<'a> Interpreter<'a> {
 }
 
 #[cfg(test)]
-mod common_test {
-    use parser::types::{ExpressionType, SourceCodeLocation, Expression, ExpressionFactory};
+pub(crate) mod common_test {
+    use parser::types::{ExpressionType, SourceCodeLocation, Expression, ExpressionFactory, Statement};
+
+    pub fn parse<'a>(source: &'a str) -> Vec<Statement<'a>> {
+        super::parse_module(source, "test").unwrap()
+    }
 
     pub fn create_expression<'a>(
         expression_type: ExpressionType<'a>,
@@ -1061,7 +1887,7 @@ mod test_statement {
     use crate::value::Value;
     use ahash::{AHashMap as HashMap};
     use parser::types::{
-        Expression, ExpressionType, Literal, ProgramError, SourceCodeLocation,
+        ErrorCode, Expression, ExpressionType, Literal, ProgramError, SourceCodeLocation,
         Statement, StatementType, TokenType,
     };
     use super::common_test::{create_expression, get_variable};
@@ -1077,7 +1903,7 @@ mod test_statement {
         let mut locals = HashMap::default();
         locals.insert(0, 0);
         let mut interpreter = Interpreter::new(&[], "");
-        interpreter.locals = locals;
+        interpreter.locals = RefCell::new(locals);
         let statement = Statement {
             statement_type: StatementType::If {
                 condition: create_expression_number(1.0, &location),
@@ -1110,7 +1936,7 @@ mod test_statement {
         let mut locals = HashMap::default();
         locals.insert(0, 0);
         let mut interpreter = Interpreter::new(&[], "");
-        interpreter.locals = locals;
+        interpreter.locals = RefCell::new(locals);
         let statement = Statement {
             statement_type: StatementType::If {
                 condition: create_expression_number(0.0, &location),
@@ -1143,7 +1969,7 @@ mod test_statement {
         let mut locals = HashMap::default();
         locals.insert(0, 0);
         let mut interpreter = Interpreter::new(&[], "");
-        interpreter.locals = locals;
+        interpreter.locals = RefCell::new(locals);
         let statement = create_variable_assignment_statement("identifier", 0.0, &location);
         let mut state = State::default();
         state.insert("identifier", Value::Float { value: 2.0 });
@@ -1161,7 +1987,7 @@ mod test_statement {
         let mut locals = HashMap::default();
         locals.insert(0, 0);
         let mut interpreter = Interpreter::new(&[], "");
-        interpreter.locals = locals;
+        interpreter.locals = RefCell::new(locals);
         let statement = Statement {
             statement_type: StatementType::Block {
                 body: vec![
@@ -1226,11 +2052,13 @@ mod test_statement {
             statement_type: StatementType::FunctionDeclaration {
                 name: "function",
                 arguments: vec![],
+                keyword_arguments: None,
                 body: vec![Box::new(Statement {
                     statement_type: StatementType::EOF,
                     location: location.clone(),
                 })],
                 context_variables: vec![],
+                annotations: vec![],
             },
             location: location.clone(),
         };
@@ -1293,6 +2121,7 @@ mod test_statement {
         assert_eq!(
             r,
             Err(ProgramError {
+                code: ErrorCode::RuntimeError,
                 message: "Return outside function".to_owned(),
                 location,
             })
@@ -1333,6 +2162,7 @@ mod test_statement {
         assert_eq!(
             r,
             Err(ProgramError {
+                code: ErrorCode::RuntimeError,
                 message: "Break outside loop".to_owned(),
                 location,
             })
@@ -1349,7 +2179,7 @@ mod test_statement {
         let mut locals = HashMap::default();
         locals.insert(0, 0);
         let mut interpreter = Interpreter::new(&[], "");
-        interpreter.locals = locals;
+        interpreter.locals = RefCell::new(locals);
         let statement = Statement {
             statement_type: StatementType::While {
                 condition: create_expression(
@@ -1421,15 +2251,16 @@ mod test_statement {
 #[cfg(test)]
 mod test_expression {
     use ahash::{AHashMap as HashMap};
+    use crate::config::NumericEquality;
     use crate::function::LoxFunction;
     use crate::interpreter::Interpreter;
     use crate::state::State;
     use crate::value::Value;
     use parser::types::{
-        DataKeyword, Expression, ExpressionType, Literal, SourceCodeLocation,
+        DataKeyword, ErrorCode, Expression, ExpressionType, Literal, SourceCodeLocation,
         Statement, StatementType, TokenType,
     };
-    use super::common_test::{create_expression, get_variable};
+    use super::common_test::{create_expression, get_variable, parse};
     use std::rc::Rc;
     use std::cell::RefCell;
 
@@ -1497,6 +2328,30 @@ mod test_expression {
         assert_eq!(got, Value::Float { value: -1.0 });
     }
 
+    #[test]
+    fn test_minus_operator_on_string_points_at_operand() {
+        let location = SourceCodeLocation {
+            line: 1,
+            file: "",
+        };
+        let operand_location = SourceCodeLocation {
+            line: 2,
+            file: "",
+        };
+        let expression = create_expression(
+            ExpressionType::Unary {
+                operator: TokenType::Minus,
+                operand: Box::new(get_string("text", &operand_location)),
+            },
+            location,
+        );
+        let interpreter = Interpreter::new(&[], "");
+        let error = interpreter.evaluate_expression(&expression).unwrap_err();
+        assert_eq!(error.message, "Can only negate numbers");
+        assert_eq!(error.code, ErrorCode::TypeError);
+        assert_eq!(error.location, operand_location);
+    }
+
     #[test]
     fn test_bang_operator() {
         let location = SourceCodeLocation {
@@ -1615,6 +2470,53 @@ mod test_expression {
         assert_eq!(got, Value::Float { value: 1.0 });
     }
 
+    #[test]
+    fn test_arithmetic_promotion() {
+        let location = SourceCodeLocation {
+            line: 1,
+            file: "",
+        };
+        let integer = |n: i64| create_expression(
+            ExpressionType::ExpressionLiteral {
+                value: Literal::Integer(n),
+            },
+            location.clone(),
+        );
+        let operators = [
+            (TokenType::Plus, 9, 9.0),
+            (TokenType::Minus, 5, 5.0),
+            (TokenType::Star, 14, 14.0),
+            (TokenType::Slash, 3, 3.5),
+            (TokenType::Percent, 1, 1.0),
+        ];
+        for (operator, integer_result, float_result) in operators.iter() {
+            let operands = [
+                (integer(7), integer(2), false),
+                (integer(7), get_number(2.0, &location), true),
+                (get_number(7.0, &location), integer(2), true),
+                (get_number(7.0, &location), get_number(2.0, &location), true),
+            ];
+            for (left, right, is_float) in operands.iter() {
+                let expression = create_expression(
+                    ExpressionType::Binary {
+                        operator: operator.clone(),
+                        left: Box::new(left.clone()),
+                        right: Box::new(right.clone()),
+                    },
+                    location.clone(),
+                );
+                let interpreter = Interpreter::new(&[], "");
+                let got = interpreter.evaluate_expression(&expression).unwrap();
+                let expected = if *is_float {
+                    Value::Float { value: *float_result }
+                } else {
+                    Value::Integer { value: *integer_result }
+                };
+                assert_eq!(got, expected, "{:?} {:?} {:?}", left, operator, right);
+            }
+        }
+    }
+
     #[test]
     fn test_greater() {
         let location = SourceCodeLocation {
@@ -1748,6 +2650,54 @@ mod test_expression {
         assert_eq!(got, Value::Boolean { value: true });
     }
 
+    fn integer_equals_float() -> Expression<'static> {
+        let location = SourceCodeLocation {
+            line: 1,
+            file: "",
+        };
+        create_expression(
+            ExpressionType::Binary {
+                operator: TokenType::EqualEqual,
+                left: Box::new(create_expression(
+                    ExpressionType::ExpressionLiteral {
+                        value: Literal::Integer(1),
+                    },
+                    location.clone(),
+                )),
+                right: Box::new(get_number(1.0, &location)),
+            },
+            location,
+        )
+    }
+
+    #[test]
+    fn test_equal_integer_float_strict() {
+        let expression = integer_equals_float();
+        let interpreter = Interpreter::new(&[], "");
+        let got = interpreter.evaluate_expression(&expression).unwrap();
+        assert_eq!(got, Value::Boolean { value: false });
+    }
+
+    #[test]
+    fn test_equal_integer_float_promoting() {
+        let expression = integer_equals_float();
+        let mut interpreter = Interpreter::new(&[], "");
+        interpreter.config.numeric_equality = NumericEquality::Promoting;
+        let got = interpreter.evaluate_expression(&expression).unwrap();
+        assert_eq!(got, Value::Boolean { value: true });
+    }
+
+    #[test]
+    fn test_binary_search_follows_numeric_equality() {
+        let source = parse("var index = [1, 2.0, 3].binary_search(2);");
+        for (numeric_equality, index) in [(NumericEquality::Strict, -2), (NumericEquality::Promoting, 1)].iter() {
+            let mut interpreter = Interpreter::new(&[], "");
+            interpreter.config.numeric_equality = *numeric_equality;
+            interpreter.run(&source).unwrap();
+            assert_eq!(interpreter.state.borrow().get_global("index"), Some(Value::Integer { value: *index }));
+        }
+    }
+
     #[test]
     fn test_different() {
         let location = SourceCodeLocation {
@@ -1852,7 +2802,7 @@ mod test_expression {
         let mut locals = HashMap::default();
         locals.insert(0, 0);
         let mut interpreter = Interpreter::new(&[], "");
-        interpreter.locals = locals;
+        interpreter.locals = RefCell::new(locals);
         let expression = create_expression(
             ExpressionType::VariableAssignment {
                 identifier: "identifier",
@@ -1893,10 +2843,13 @@ mod test_expression {
         state.insert(
             "function",
             Value::Function(Rc::new(LoxFunction {
+                name: Some("function"),
                 arguments: vec![],
+                keyword_arguments: None,
                 environments: state.get_environments(),
                 body: vec![&s],
                 location,
+                annotations: &[],
             })),
         );
         interpreter.state = RefCell::new(state);
@@ -1936,3 +2889,259 @@ mod test_expression {
         )
     }
 }
+
+#[cfg(test)]
+mod test_interpreter {
+    use crate::interpreter::{EvaluationResult, Interpreter};
+    use std::rc::Rc;
+    use crate::value::Value;
+    use parser::resolver::Resolver;
+    use parser::types::{ErrorCode, Pass, SourceCodeLocation, Statement};
+    use super::common_test::parse;
+
+    fn answer<'a>(
+        _interpreter: &'a Interpreter<'a>,
+        _values: &[Value<'a>],
+        _location: &SourceCodeLocation<'a>,
+    ) -> EvaluationResult<'a> {
+        Ok(Value::Integer { value: 42 })
+    }
+
+    fn run_imports<'a>(interpreter: &'a Interpreter<'a>, source: &'a [Statement<'a>], preload: bool) -> Vec<Option<Value<'a>>> {
+        interpreter.locals.replace(Resolver::new().run(source).unwrap());
+        if preload {
+            interpreter.preload_imports(source);
+        }
+        interpreter.run(source).unwrap();
+        let state = interpreter.state.borrow();
+        vec![state.get_global("total"), state.get_global("text"), state.get_global("variable")]
+    }
+
+    #[test]
+    fn test_preloaded_imports_match_sequential() {
+        let paths = [concat!(env!("CARGO_MANIFEST_DIR"), "/tests/imports").to_owned()];
+        let source = parse(
+            "import numbers; import words; import module; \
+             var total = numbers::one + numbers::two; var text = words::greeting; \
+             var variable = module::variable;"
+        );
+        let sequential = Interpreter::new(&paths, "");
+        let preloaded = Interpreter::new(&paths, "");
+        let expected = vec![
+            Some(Value::Integer { value: 3 }),
+            Some(Value::String { value: "hello".to_owned() }),
+            Some(Value::Integer { value: 1 }),
+        ];
+        assert_eq!(run_imports(&sequential, &source, false), expected);
+        assert_eq!(run_imports(&preloaded, &source, true), expected);
+    }
+
+    #[test]
+    fn test_collection_size_limit() {
+        let within = parse("var a = [0; 3]; var m = {1: 1}; m[2] = 2; m[1] = 3; var s = \"ab\" + \"cd\";");
+        let repeated = parse("var b = [0; 4];");
+        let push = parse("a.push(1);");
+        let resize = parse("a.resize(5);");
+        let insert = parse("m[3] = 3; m[4] = 4;");
+        let mut interpreter = Interpreter::new(&[], "");
+        interpreter.config.max_collection_size = Some(3);
+        interpreter.run(&within).unwrap();
+        for source in [&repeated, &push, &resize, &insert].iter() {
+            let error = interpreter.run(source).unwrap_err();
+            assert_eq!(error.message, "Collection size limit exceeded");
+        }
+        assert_eq!(interpreter.state.borrow().get_global("a").map(|a| a.to_string()), Some("[ 0, 0, 0, ]".to_owned()));
+    }
+
+    #[test]
+    fn test_error_codes() {
+        let cases = [
+            ("var a = 1 + \"a\";", ErrorCode::TypeError),
+            ("var a = [1, 2]; var b = a[5];", ErrorCode::IndexError),
+            ("var a = 1 / 0;", ErrorCode::DivisionByZero),
+            ("var a = (-9223372036854775807 - 1) / -1;", ErrorCode::OverflowError),
+            ("var a = (-9223372036854775807 - 1) % -1;", ErrorCode::OverflowError),
+            ("var a = divmod(-9223372036854775807 - 1, -1);", ErrorCode::OverflowError),
+            ("var a = -(-9223372036854775807 - 1);", ErrorCode::OverflowError),
+            ("var a = [0; 4];", ErrorCode::LimitExceeded),
+            ("var a = [0; -1];", ErrorCode::InvalidValue),
+            ("var a = deep_merge({\"a\": [1, 2]}, {\"a\": [3, 4]}, true);", ErrorCode::LimitExceeded),
+            ("var a = deep_merge({\"a\": 1, \"b\": 2}, {\"c\": 3, \"d\": 4});", ErrorCode::LimitExceeded),
+        ];
+        for (source, code) in cases.iter() {
+            let statements = parse(source);
+            let mut interpreter = Interpreter::new(&[], "");
+            interpreter.config.max_collection_size = Some(3);
+            assert_eq!(interpreter.run(&statements).unwrap_err().code, *code);
+        }
+    }
+
+    #[test]
+    fn test_match_checks_bound_variant_fields() {
+        let cases = [
+            ("Shape.Circle(1)", "Shape.Circle(r, extra) => { print r + extra; }", 1, 2),
+            ("Shape.Rectangle(2, 3)", "Shape.Rectangle(w) => { print w; }", 2, 1),
+        ];
+        for (value, branch, fields, bindings) in cases.iter() {
+            let source = format!(
+                "enum Shape {{ Circle(radius), Rectangle(width, height) }} match {} {{ {}, * => {{}}, }}",
+                value, branch
+            );
+            let statements = parse(&source);
+            let interpreter = Interpreter::new(&[], "");
+            interpreter.locals.replace(Resolver::new().run(&statements).unwrap());
+            let error = interpreter.run(&statements).unwrap_err();
+            assert_eq!(error.code, ErrorCode::InvalidValue);
+            assert_eq!(error.message, format!("Can't destructure {} values into {} variables", fields, bindings));
+        }
+    }
+
+    #[test]
+    fn test_shadowing_builtin_warning() {
+        let source = parse(
+            "class Totals { sum(a) { return a; } } \
+             fun total(values) { var sum = 0; for (var v in values) sum = sum + v; return sum; } \
+             print total([1, 2]); print Totals().sum(1);"
+        );
+        let interpreter = Interpreter::new(&[], "");
+        let mut resolver = Resolver::new().with_builtins(interpreter.builtin_names());
+        resolver.run(&source).unwrap();
+        let warnings = resolver.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, ErrorCode::NameError);
+        assert_eq!(warnings[0].message, "`sum` shadows a built-in global.");
+    }
+
+    #[test]
+    fn test_weak_method_binding() {
+        let source = parse("class A { f() { return 1; } } var m; { var a = A(); a.g = a.f; m = a.g; }");
+        let call = parse("m();");
+        let temporary = parse("var t = A().f();");
+        for weak in [false, true].iter() {
+            let mut interpreter = Interpreter::new(&[], "");
+            interpreter.config.weak_method_binding = *weak;
+            interpreter.locals.replace(Resolver::new().run(&source).unwrap());
+            interpreter.run(&source).unwrap();
+            interpreter.locals.replace(Resolver::new().run(&temporary).unwrap());
+            interpreter.run(&temporary).unwrap();
+            assert_eq!(interpreter.state.borrow().get_global("t"), Some(Value::Integer { value: 1 }));
+            interpreter.locals.replace(Resolver::new().run(&call).unwrap());
+            let result = interpreter.run(&call);
+            if *weak {
+                assert_eq!(result.unwrap_err().message, "Method receiver was dropped");
+            } else {
+                assert!(result.is_ok());
+            }
+        }
+    }
+
+    #[test]
+    fn test_strict_fields() {
+        let source = parse(
+            "class Point { var x; init(x) { this.x = x; } } \
+             class Point3 < Point { var z; init(x) { this.z = x; } } \
+             var p = Point3(1); p.x = 2; p.z = 3;"
+        );
+        let typos = [
+            parse("p.y = 4;"),
+            parse("var q = with(p, \"y\", 4);"),
+            parse("var q = from_map(Point, {\"y\": 4});"),
+        ];
+        for strict in [false, true].iter() {
+            for typo in typos.iter() {
+                let mut interpreter = Interpreter::new(&[], "");
+                interpreter.config.strict_fields = *strict;
+                interpreter.locals.replace(Resolver::new().run(&source).unwrap());
+                interpreter.run(&source).unwrap();
+                interpreter.locals.replace(Resolver::new().run(typo).unwrap());
+                let result = interpreter.run(typo);
+                if *strict {
+                    let error = result.unwrap_err();
+                    assert_eq!(error.code, ErrorCode::NameError);
+                    assert_eq!(error.message, "Unknown field 'y'");
+                } else {
+                    assert!(result.is_ok());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_failed_loop_leaves_loop() {
+        let source = parse("for (var x in [1]) x.missing();");
+        let interpreter = Interpreter::new(&[], "");
+        interpreter.locals.replace(Resolver::new().run(&source).unwrap());
+        assert!(interpreter.run(&source).is_err());
+        assert_eq!(interpreter.state.borrow().loop_count, 0);
+    }
+
+    #[test]
+    fn test_reset_keeps_natives() {
+        let first = parse("var leaked = answer();");
+        let second = parse("var fresh = answer();");
+        let third = parse("print leaked;");
+        let interpreter = Interpreter::new(&[], "");
+        interpreter.register_native("answer", 0..=0, answer);
+
+        interpreter.locals.replace(Resolver::new().run(&first).unwrap());
+        interpreter.run(&first).unwrap();
+        assert_eq!(interpreter.state.borrow().get_global("leaked"), Some(Value::Integer { value: 42 }));
+
+        interpreter.reset();
+        interpreter.locals.replace(Resolver::new().run(&second).unwrap());
+        interpreter.run(&second).unwrap();
+        assert_eq!(interpreter.state.borrow().get_global("fresh"), Some(Value::Integer { value: 42 }));
+        assert_eq!(interpreter.state.borrow().get_global("leaked"), None);
+
+        interpreter.reset();
+        interpreter.locals.replace(Resolver::new().run(&third).unwrap());
+        assert!(interpreter.run(&third).is_err());
+    }
+
+    #[test]
+    fn test_reset_keeps_module_code_alive() {
+        let paths = [concat!(env!("CARGO_MANIFEST_DIR"), "/tests/imports").to_owned()];
+        let source = parse("import counter; var f = counter::increment;");
+        let interpreter = Interpreter::new(&paths, "");
+        interpreter.locals.replace(Resolver::new().run(&source).unwrap());
+        interpreter.run(&source).unwrap();
+        let f = match interpreter.state.borrow().get_global("f") {
+            Some(Value::Function(f)) => f,
+            _ => panic!("Expected a function"),
+        };
+        let body = format!("{:?}", f.body);
+
+        interpreter.reset();
+        interpreter.locals.replace(Resolver::new().run(&source).unwrap());
+        interpreter.run(&source).unwrap();
+        interpreter.reset();
+        assert_eq!(f.name, Some("increment"));
+        assert_eq!(format!("{:?}", f.body), body);
+    }
+
+    #[test]
+    fn test_module_cache_does_not_own_modules() {
+        let paths = [concat!(env!("CARGO_MANIFEST_DIR"), "/tests/imports").to_owned()];
+        let source = parse("import numbers; var total = numbers::one;");
+        let interpreter = Interpreter::new(&paths, "");
+        interpreter.locals.replace(Resolver::new().run(&source).unwrap());
+        interpreter.run(&source).unwrap();
+        let module = unsafe { interpreter.module_interpreters.as_ptr().as_ref() }.unwrap()["numbers"].clone();
+        assert_eq!(Rc::strong_count(&module), 2);
+        assert_eq!(interpreter.module_cache.borrow().len(), 1);
+    }
+
+    #[test]
+    fn test_reset_drops_modules() {
+        let paths = [concat!(env!("CARGO_MANIFEST_DIR"), "/tests/imports").to_owned()];
+        let source = parse("import numbers; var total = numbers::one;");
+        let interpreter = Interpreter::new(&paths, "");
+        for _ in 0..2 {
+            interpreter.locals.replace(Resolver::new().run(&source).unwrap());
+            interpreter.run(&source).unwrap();
+            assert_eq!(unsafe { interpreter.module_interpreters.as_ptr().as_ref() }.unwrap().len(), 1);
+            interpreter.reset();
+            assert!(unsafe { interpreter.module_interpreters.as_ptr().as_ref() }.unwrap().is_empty());
+        }
+    }
+}
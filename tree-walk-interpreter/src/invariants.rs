@@ -0,0 +1,341 @@
+use crate::interpreter::{EvaluationResult, Interpreter};
+use crate::value::Value;
+use crate::rewrite::rewrite_expressions;
+use ahash::{AHashMap as HashMap, AHashSet as HashSet};
+use parser::types::{Expression, ExpressionType, Pass, ProgramError, Statement, StatementType};
+use std::mem;
+
+/// Finds the operations inside loops that give the same result on every iteration: unary,
+/// binary and conditional expressions over literals and variables that are never assigned
+/// and aren't declared inside any loop. Function bodies start over, as they don't run when
+/// the loop that contains them does.
+struct LoopInvariants<'a> {
+    loop_depth: usize,
+    unstable: HashSet<&'a str>,
+    candidates: Vec<(usize, Vec<&'a str>)>,
+}
+
+impl<'a> LoopInvariants<'a> {
+    fn new() -> LoopInvariants<'a> {
+        LoopInvariants {
+            loop_depth: 0,
+            unstable: HashSet::default(),
+            candidates: vec![],
+        }
+    }
+
+    /// Collects the variables read by `expression` and tells whether it only combines
+    /// literals and variables with operators that can't have side effects.
+    fn operands(expression: &'a Expression<'a>, variables: &mut Vec<&'a Expression<'a>>) -> bool {
+        match &expression.expression_type {
+            ExpressionType::ExpressionLiteral { .. } => true,
+            ExpressionType::VariableLiteral { .. } => {
+                variables.push(expression);
+                true
+            }
+            ExpressionType::Grouping { expression } => LoopInvariants::operands(expression, variables),
+            ExpressionType::Unary { operand, .. } => LoopInvariants::operands(operand, variables),
+            ExpressionType::Binary { left, right, .. } =>
+                LoopInvariants::operands(left, variables) && LoopInvariants::operands(right, variables),
+            ExpressionType::Conditional { condition, then_branch, else_branch } =>
+                LoopInvariants::operands(condition, variables)
+                    && LoopInvariants::operands(then_branch, variables)
+                    && LoopInvariants::operands(else_branch, variables),
+            _ => false,
+        }
+    }
+
+    fn outside_loops<F: FnOnce(&mut Self) -> Result<(), Vec<ProgramError<'a>>>>(
+        &mut self,
+        action: F,
+    ) -> Result<(), Vec<ProgramError<'a>>> {
+        let loop_depth = mem::replace(&mut self.loop_depth, 0);
+        let result = action(self);
+        self.loop_depth = loop_depth;
+        result
+    }
+}
+
+impl<'a> Pass<'a, HashSet<usize>> for LoopInvariants<'a> {
+    fn run(&mut self, ss: &'a [Statement<'a>]) -> Result<HashSet<usize>, Vec<ProgramError<'a>>> {
+        for s in ss {
+            self.pass(s)?;
+        }
+        let unstable = &self.unstable;
+        Ok(self.candidates
+            .iter()
+            .filter(|(_, names)| names.iter().all(|name| !unstable.contains(name)))
+            .map(|(id, _)| *id)
+            .collect())
+    }
+
+    fn before_pass(&mut self, s: &Statement<'a>) {
+        if self.loop_depth == 0 {
+            return;
+        }
+        match &s.statement_type {
+            StatementType::VariableDeclaration { name, .. }
+            | StatementType::ClassDeclaration { name, .. }
+            | StatementType::TraitDeclaration { name, .. }
+            | StatementType::EnumDeclaration { name, .. }
+            | StatementType::FunctionDeclaration { name, .. }
+            | StatementType::Import { name } => {
+                self.unstable.insert(*name);
+            }
+            StatementType::TupleDeclaration { names, .. } => self.unstable.extend(names.iter().copied()),
+            _ => {}
+        }
+    }
+
+    fn pass_expression(&mut self, expression: &'a Expression<'a>) -> Result<(), Vec<ProgramError<'a>>> {
+        if self.loop_depth > 0 {
+            if let ExpressionType::Unary { .. } | ExpressionType::Binary { .. } | ExpressionType::Conditional { .. } =
+                &expression.expression_type
+            {
+                let mut variables = vec![];
+                if LoopInvariants::operands(expression, &mut variables) {
+                    let names = variables
+                        .iter()
+                        .filter_map(|v| match &v.expression_type {
+                            ExpressionType::VariableLiteral { identifier } => Some(*identifier),
+                            _ => None,
+                        })
+                        .collect();
+                    self.candidates.push((expression.id(), names));
+                }
+            }
+        }
+        match &expression.expression_type {
+            ExpressionType::VariableAssignment { identifier, expression: value } => {
+                self.unstable.insert(identifier);
+                self.pass_expression(value)
+            }
+            ExpressionType::TupleAssignment { targets, value } => {
+                for target in targets {
+                    if let ExpressionType::VariableLiteral { identifier } = &target.expression_type {
+                        self.unstable.insert(identifier);
+                    }
+                }
+                self.pass_expression(value)
+            }
+            ExpressionType::AnonymousFunction { body, .. } => self.outside_loops(|pass| {
+                for s in body {
+                    pass.pass(s)?;
+                }
+                Ok(())
+            }),
+            ExpressionType::Conditional { condition, then_branch, else_branch } => {
+                self.pass_expression(condition)?;
+                self.pass_expression(then_branch)?;
+                self.pass_expression(else_branch)
+            }
+            ExpressionType::Binary { left, right, .. } => {
+                self.pass_expression(left)?;
+                self.pass_expression(right)
+            }
+            ExpressionType::Call { callee, arguments } => {
+                self.pass_expression(callee)?;
+                for argument in arguments {
+                    self.pass_expression(argument)?;
+                }
+                Ok(())
+            }
+            ExpressionType::Array { elements } | ExpressionType::Tuple { elements } => {
+                for element in elements {
+                    self.pass_expression(element)?;
+                }
+                Ok(())
+            }
+            ExpressionType::Map { entries } => {
+                for (key, value) in entries {
+                    self.pass_expression(key)?;
+                    self.pass_expression(value)?;
+                }
+                Ok(())
+            }
+            ExpressionType::Set { callee, value, .. } => {
+                self.pass_expression(callee)?;
+                self.pass_expression(value)
+            }
+            ExpressionType::RepeatedElementArray { element: first, length: second }
+            | ExpressionType::ArrayElement { array: first, index: second } => {
+                self.pass_expression(first)?;
+                self.pass_expression(second)
+            }
+            ExpressionType::ArrayElementSet { array, index, value } => {
+                self.pass_expression(array)?;
+                self.pass_expression(index)?;
+                self.pass_expression(value)
+            }
+            ExpressionType::NamedArgument { value: operand, .. }
+            | ExpressionType::Unary { operand, .. }
+            | ExpressionType::Grouping { expression: operand }
+            | ExpressionType::Get { callee: operand, .. }
+            | ExpressionType::OptionalGet { callee: operand, .. }
+            | ExpressionType::IsType { value: operand, .. } => self.pass_expression(operand),
+            ExpressionType::ExpressionLiteral { .. }
+            | ExpressionType::ModuleLiteral { .. }
+            | ExpressionType::VariableLiteral { .. }
+            | ExpressionType::UpliftFunctionVariables(_)
+            | ExpressionType::UpliftClassVariables(_) => Ok(()),
+        }
+    }
+
+    fn pass_function_declaration(
+        &mut self,
+        _name: &'a str,
+        _arguments: &'a [&'a str],
+        body: &'a [Box<Statement<'a>>],
+        _statement: &'a Statement<'a>,
+        _context_variables: &'a [&'a str],
+    ) -> Result<(), Vec<ProgramError<'a>>> {
+        self.outside_loops(|pass| {
+            for s in body {
+                pass.pass(s)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn pass_while(
+        &mut self,
+        condition: &'a Expression<'a>,
+        action: &'a Statement<'a>,
+    ) -> Result<(), Vec<ProgramError<'a>>> {
+        self.loop_depth += 1;
+        let result = self.pass_expression(condition).and_then(|_| self.pass(action));
+        self.loop_depth -= 1;
+        result
+    }
+
+    fn pass_for_in(
+        &mut self,
+        variable: &'a str,
+        iterable: &'a Expression<'a>,
+        body: &'a Statement<'a>,
+        _statement: &'a Statement<'a>,
+    ) -> Result<(), Vec<ProgramError<'a>>> {
+        self.unstable.insert(variable);
+        self.pass_expression(iterable)?;
+        self.loop_depth += 1;
+        let result = self.pass(body);
+        self.loop_depth -= 1;
+        result
+    }
+}
+
+fn is_primitive(value: &Value) -> bool {
+    matches!(
+        value,
+        Value::Nil
+            | Value::Boolean { .. }
+            | Value::Integer { .. }
+            | Value::Float { .. }
+            | Value::String { .. }
+            | Value::Symbol(_)
+    )
+}
+
+/// Marks the loop invariant operations in `ss`, so they're evaluated once per run of the
+/// loop that contains them instead of on every iteration. Meant to run after resolution.
+pub fn hoist_loop_invariants(ss: &mut [Statement<'_>]) {
+    let invariants = match LoopInvariants::new().run(ss) {
+        Ok(invariants) => invariants,
+        Err(_) => return,
+    };
+    if !invariants.is_empty() {
+        rewrite_expressions(ss, &mut |expression| {
+            if invariants.contains(&expression.id()) {
+                expression.mark_loop_invariant();
+            }
+            true
+        });
+    }
+}
+
+impl<'a> Interpreter<'a> {
+    /// Evaluates a loop invariant the first time the current run of its loop reaches it.
+    /// Errors aren't cached, so an invariant that fails does it in the same iteration it
+    /// would without hoisting. Invariants that read arrays, maps or objects are evaluated
+    /// every time, as those can change without their variable being assigned. Their
+    /// variables are never assigned, so that's only checked the first time.
+    pub(crate) fn evaluate_loop_invariant(&'a self, expression: &'a Expression<'a>) -> EvaluationResult<'a> {
+        let cached = self.loop_caches.borrow().last().and_then(|cache| cache.get(&expression.id()).cloned());
+        match cached {
+            Some(Some(value)) => Ok(value),
+            Some(None) => self.evaluate_expression_uncached(expression),
+            None if self.has_primitive_operands(expression) => {
+                let value = self.evaluate_expression_uncached(expression)?;
+                self.cache_loop_invariant(expression, Some(value.clone()));
+                Ok(value)
+            }
+            None => {
+                self.cache_loop_invariant(expression, None);
+                self.evaluate_expression_uncached(expression)
+            }
+        }
+    }
+
+    fn cache_loop_invariant(&self, expression: &Expression<'a>, value: Option<Value<'a>>) {
+        if let Some(cache) = self.loop_caches.borrow_mut().last_mut() {
+            cache.insert(expression.id(), value);
+        }
+    }
+
+    fn has_primitive_operands(&'a self, expression: &'a Expression<'a>) -> bool {
+        let mut variables = vec![];
+        LoopInvariants::operands(expression, &mut variables);
+        variables.into_iter().all(|variable| {
+            self.evaluate_expression_uncached(variable).map(|v| is_primitive(&v)).unwrap_or(false)
+        })
+    }
+
+    pub(crate) fn push_loop_cache(&self) {
+        self.loop_caches.borrow_mut().push(HashMap::default());
+    }
+
+    pub(crate) fn pop_loop_cache(&self) {
+        self.loop_caches.borrow_mut().pop();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::interpreter::Interpreter;
+    use crate::interpreter::common_test::parse;
+    use crate::invariants::hoist_loop_invariants;
+    use crate::rewrite::rewrite_expressions;
+    use parser::resolver::Resolver;
+    use parser::types::Pass;
+
+    #[test]
+    fn test_hoist_loop_invariants() {
+        let program = "var limit = 4; var scale = 3; var zero = 0; var total = 0; \
+             var i = 0; while (i < limit * 2) { total = total + scale * scale + i; i = i + 1; } \
+             var items = [1]; var other = [1]; var same = 0; \
+             for (var j = 0; j < 3; j = j + 1) { if (items == other) same = same + 1; items.push(j); } \
+             var k = 0; while (k < 3) { k = k + 1; if (k == 2) print scale / zero; }";
+        let source = parse(program);
+        let locals = Resolver::new().run(&source).unwrap();
+        let plain = Interpreter::new(&[], "");
+        plain.locals.replace(locals.clone());
+        let plain_error = plain.run(&source).unwrap_err();
+
+        let mut marked = parse(program);
+        hoist_loop_invariants(&mut marked);
+        let mut invariants = 0;
+        rewrite_expressions(&mut marked, &mut |expression| {
+            invariants += expression.is_loop_invariant() as usize;
+            true
+        });
+        assert_eq!(invariants, 4);
+
+        let hoisted = Interpreter::new(&[], "");
+        hoisted.locals.replace(locals);
+        let hoisted_error = hoisted.run(&marked).unwrap_err();
+        assert_eq!(hoisted_error, plain_error);
+        for name in ["total", "i", "same", "k"].iter() {
+            assert_eq!(hoisted.state.borrow().get_global(name), plain.state.borrow().get_global(name));
+        }
+    }
+}
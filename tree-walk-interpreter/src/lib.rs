@@ -5,4 +5,16 @@ pub mod interpreter;
 pub mod value;
 pub mod state;
 pub mod function;
-pub mod class;
\ No newline at end of file
+pub mod array;
+pub mod builtins;
+pub mod class;
+pub mod config;
+pub mod constants;
+pub mod hooks;
+pub mod invariants;
+pub mod native;
+pub mod rewrite;
+pub mod snapshot;
+pub mod stack;
+pub mod string;
+pub mod template;
\ No newline at end of file
@@ -11,10 +11,21 @@ use std::env;
 use std::env::Args;
 use std::process::exit;
 
+mod array;
+mod builtins;
 mod class;
+mod config;
+mod constants;
 mod function;
+mod hooks;
+mod invariants;
 pub mod interpreter;
+mod native;
+mod rewrite;
+mod stack;
 mod state;
+mod string;
+mod template;
 mod value;
 
 struct Config {
@@ -54,23 +65,30 @@ fn main() -> io::Result<()> {
             let parser = Parser::new(ts.into_iter().peekable());
             parser.parse()
         });
-    let (ss, _, _) = match result {
+    let (mut ss, _, _) = match result {
         Ok(ss) => ss,
         Err(es) => {
             es.iter().for_each(|e| eprintln!("{}", e));
             exit(1);
         },
     };
-    let mut interpreter = Interpreter::new(&config.paths, "");
-    let mut resolver = Resolver::new();
-    let locals = match resolver.run(&ss) {
+    let interpreter = Interpreter::new(&config.paths, "");
+    let mut resolver = Resolver::new().with_builtins(interpreter.builtin_names());
+    let result = resolver.run(&ss);
+    resolver.warnings().iter().for_each(|w| {
+        eprintln!("Warning! [file {} line {}] {}", w.location.file, w.location.line + 1, w.message)
+    });
+    let locals = match result {
         Ok(l) => l,
         Err(es) => {
             es.iter().for_each(|e| eprintln!("{}", e));
             exit(1);
         },
     };
-    interpreter.locals = locals;
+    interpreter.locals.replace(locals);
+    constants::propagate_constants(&mut ss);
+    invariants::hoist_loop_invariants(&mut ss);
+    interpreter.preload_imports(&ss);
     if let Err(e) = interpreter.run(&ss) {
         eprintln!("{}", e);
         exit(1);
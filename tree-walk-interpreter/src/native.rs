@@ -0,0 +1,73 @@
+use crate::interpreter::{EvaluationResult, Interpreter};
+use crate::value::Value;
+use parser::types::{ErrorCode, ProgramError, SourceCodeLocation};
+use std::fmt::{Debug, Error, Formatter};
+use std::ops::RangeInclusive;
+
+pub type NativeCallback<'a> =
+    fn(&'a Interpreter<'a>, &[Value<'a>], &SourceCodeLocation<'a>) -> EvaluationResult<'a>;
+
+#[derive(Clone)]
+pub struct NativeFunction<'a> {
+    pub name: &'a str,
+    pub arity: RangeInclusive<usize>,
+    pub function: NativeCallback<'a>,
+}
+
+impl<'a> NativeFunction<'a> {
+    fn check_arity(
+        &self,
+        got: usize,
+        kind: &str,
+        location: &SourceCodeLocation<'a>,
+    ) -> Result<(), ProgramError<'a>> {
+        if self.arity.contains(&got) {
+            return Ok(());
+        }
+        let expected = if self.arity.start() == self.arity.end() {
+            self.arity.start().to_string()
+        } else {
+            format!("{} to {}", self.arity.start(), self.arity.end())
+        };
+        Err(ProgramError {
+            code: ErrorCode::ArgumentError,
+            message: format!("Wrong number of arguments{}! Expected: {} Got: {}", kind, expected, got),
+            location: location.clone(),
+        })
+    }
+
+    pub fn eval(
+        &self,
+        values: &[Value<'a>],
+        interpreter: &'a Interpreter<'a>,
+        location: &SourceCodeLocation<'a>,
+    ) -> EvaluationResult<'a> {
+        self.check_arity(values.len(), "", location)?;
+        (self.function)(interpreter, values, location)
+    }
+
+    pub fn eval_method(
+        &self,
+        receiver: Value<'a>,
+        arguments: Vec<Value<'a>>,
+        interpreter: &'a Interpreter<'a>,
+        location: &SourceCodeLocation<'a>,
+    ) -> EvaluationResult<'a> {
+        self.check_arity(arguments.len(), " in method", location)?;
+        let mut values = vec![receiver];
+        values.extend(arguments);
+        (self.function)(interpreter, &values, location)
+    }
+}
+
+impl<'a> PartialEq for NativeFunction<'a> {
+    fn eq(&self, other: &NativeFunction<'a>) -> bool {
+        self.name == other.name && self.arity == other.arity
+    }
+}
+
+impl<'a> Debug for NativeFunction<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        f.write_str(format!("[Native Function: {} Arity {:?}]", self.name, self.arity).as_str())
+    }
+}
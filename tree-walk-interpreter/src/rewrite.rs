@@ -0,0 +1,152 @@
+use parser::types::{Expression, ExpressionType, Statement, StatementType};
+
+/// Calls `visit` on every expression in `ss`, parents before their children, so passes
+/// that run before the interpreter can rewrite or mark them in place. The children of an
+/// expression are skipped when `visit` returns false.
+pub fn rewrite_expressions<'a, F>(ss: &mut [Statement<'a>], visit: &mut F)
+where
+    F: FnMut(&mut Expression<'a>) -> bool,
+{
+    rewrite_statements(ss.iter_mut(), visit);
+}
+
+fn rewrite_statements<'a, 'b, I, F>(ss: I, visit: &mut F)
+where
+    'a: 'b,
+    I: Iterator<Item = &'b mut Statement<'a>>,
+    F: FnMut(&mut Expression<'a>) -> bool,
+{
+    for s in ss {
+        rewrite_statement(s, visit);
+    }
+}
+
+fn rewrite_statement<'a, F>(statement: &mut Statement<'a>, visit: &mut F)
+where
+    F: FnMut(&mut Expression<'a>) -> bool,
+{
+    match &mut statement.statement_type {
+        StatementType::Module { statements: body, .. }
+        | StatementType::Block { body }
+        | StatementType::FunctionDeclaration { body, .. } =>
+            rewrite_statements(body.iter_mut().map(|s| s.as_mut()), visit),
+        StatementType::Expression { expression }
+        | StatementType::PrintStatement { expression }
+        | StatementType::TupleDeclaration { expression, .. } => rewrite_expression(expression, visit),
+        StatementType::VariableDeclaration { expression, .. } | StatementType::Return { value: expression } => {
+            if let Some(e) = expression {
+                rewrite_expression(e, visit);
+            }
+        }
+        StatementType::ClassDeclaration {
+            superclass,
+            methods,
+            static_methods,
+            getters,
+            setters,
+            ..
+        } => {
+            if let Some(e) = superclass {
+                rewrite_expression(e, visit);
+            }
+            for ss in [methods, static_methods, getters, setters] {
+                rewrite_statements(ss.iter_mut().map(|s| s.as_mut()), visit);
+            }
+        }
+        StatementType::TraitImplementation {
+            trait_name,
+            class_name,
+            methods,
+            static_methods,
+            getters,
+            setters,
+        } => {
+            rewrite_expression(trait_name, visit);
+            rewrite_expression(class_name, visit);
+            for ss in [methods, static_methods, getters, setters] {
+                rewrite_statements(ss.iter_mut().map(|s| s.as_mut()), visit);
+            }
+        }
+        StatementType::If { condition, then, otherwise } => {
+            rewrite_expression(condition, visit);
+            rewrite_statement(then, visit);
+            if let Some(s) = otherwise {
+                rewrite_statement(s, visit);
+            }
+        }
+        StatementType::While { condition: expression, action: body }
+        | StatementType::ForIn { iterable: expression, body, .. } => {
+            rewrite_expression(expression, visit);
+            rewrite_statement(body, visit);
+        }
+        StatementType::TraitDeclaration { .. }
+        | StatementType::EnumDeclaration { .. }
+        | StatementType::Import { .. }
+        | StatementType::Break
+        | StatementType::RebindLoopVariables
+        | StatementType::EOF => {}
+    }
+}
+
+fn rewrite_expression<'a, F>(expression: &mut Expression<'a>, visit: &mut F)
+where
+    F: FnMut(&mut Expression<'a>) -> bool,
+{
+    if !visit(expression) {
+        return;
+    }
+    match &mut expression.expression_type {
+        ExpressionType::Conditional {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            rewrite_expression(condition, visit);
+            rewrite_expression(then_branch, visit);
+            rewrite_expression(else_branch, visit);
+        }
+        ExpressionType::Binary { left, right, .. }
+        | ExpressionType::RepeatedElementArray { element: left, length: right }
+        | ExpressionType::ArrayElement { array: left, index: right }
+        | ExpressionType::Set { callee: left, value: right, .. } => {
+            rewrite_expression(left, visit);
+            rewrite_expression(right, visit);
+        }
+        ExpressionType::ArrayElementSet { array, index, value } => {
+            rewrite_expression(array, visit);
+            rewrite_expression(index, visit);
+            rewrite_expression(value, visit);
+        }
+        ExpressionType::Call { callee, arguments } => {
+            rewrite_expression(callee, visit);
+            for argument in arguments {
+                rewrite_expression(argument, visit);
+            }
+        }
+        ExpressionType::Array { elements } | ExpressionType::Tuple { elements } => {
+            for element in elements {
+                rewrite_expression(element, visit);
+            }
+        }
+        ExpressionType::Map { entries } => {
+            for (key, value) in entries {
+                rewrite_expression(key, visit);
+                rewrite_expression(value, visit);
+            }
+        }
+        ExpressionType::NamedArgument { value: e, .. }
+        | ExpressionType::Unary { operand: e, .. }
+        | ExpressionType::Grouping { expression: e }
+        | ExpressionType::VariableAssignment { expression: e, .. }
+        | ExpressionType::TupleAssignment { value: e, .. }
+        | ExpressionType::Get { callee: e, .. }
+        | ExpressionType::OptionalGet { callee: e, .. }
+        | ExpressionType::IsType { value: e, .. } => rewrite_expression(e, visit),
+        ExpressionType::AnonymousFunction { body, .. } => rewrite_statements(body.iter_mut(), visit),
+        ExpressionType::ExpressionLiteral { .. }
+        | ExpressionType::ModuleLiteral { .. }
+        | ExpressionType::VariableLiteral { .. }
+        | ExpressionType::UpliftFunctionVariables(_)
+        | ExpressionType::UpliftClassVariables(_) => {}
+    }
+}
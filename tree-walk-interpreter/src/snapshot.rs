@@ -0,0 +1,305 @@
+use crate::interpreter::Interpreter;
+use crate::value::{LoxArray, LoxMap, MapKey, Value};
+use parser::types::{ErrorCode, ProgramError, SourceCodeLocation};
+use std::cell::RefCell;
+use std::iter::Peekable;
+use std::rc::Rc;
+use std::str::CharIndices;
+
+const SNAPSHOT_LOCATION: SourceCodeLocation<'static> = SourceCodeLocation {
+    file: "snapshot",
+    line: 1,
+};
+
+pub struct Snapshot<'a> {
+    pub blob: String,
+    pub skipped: Vec<&'a str>,
+}
+
+fn serialize_string(value: &str) -> String {
+    let mut output = String::with_capacity(value.len() + 2);
+    output.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\t' => output.push_str("\\t"),
+            '\r' => output.push_str("\\r"),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+    output
+}
+
+pub fn serialize(value: &Value) -> Option<String> {
+    serialize_value(value, &mut vec![])
+}
+
+fn serialize_value(value: &Value, parents: &mut Vec<*const ()>) -> Option<String> {
+    match value {
+        Value::Nil => Some("null".to_owned()),
+        Value::Boolean { value } => Some(value.to_string()),
+        Value::Integer { value } => Some(value.to_string()),
+        Value::Float { value } if value.is_finite() => Some(format!("{:?}", value)),
+        Value::String { value } => Some(serialize_string(value)),
+        Value::Array(a) => {
+            let pointer = Rc::as_ptr(a) as *const ();
+            if parents.contains(&pointer) {
+                return None;
+            }
+            parents.push(pointer);
+            let elements = a
+                .borrow()
+                .elements
+                .iter()
+                .map(|e| serialize_value(e, parents))
+                .collect::<Option<Vec<String>>>();
+            parents.pop();
+            Some(format!("[{}]", elements?.join(",")))
+        }
+        Value::Map(m) => {
+            let pointer = Rc::as_ptr(m) as *const ();
+            if parents.contains(&pointer) {
+                return None;
+            }
+            parents.push(pointer);
+            let entries = m
+                .borrow()
+                .iter()
+                .map(|(k, v)| match k {
+                    MapKey::String(k) => Some(format!("{}:{}", serialize_string(k), serialize_value(v, parents)?)),
+                    _ => None,
+                })
+                .collect::<Option<Vec<String>>>();
+            parents.pop();
+            Some(format!("{{{}}}", entries?.join(",")))
+        }
+        _ => None,
+    }
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn snapshot(&self) -> Snapshot<'a> {
+        let state = self.state.borrow();
+        let globals = state.environments[0].borrow();
+        let mut names: Vec<&'a str> = globals.keys().cloned().collect();
+        names.sort_unstable();
+        let mut entries = vec![];
+        let mut skipped = vec![];
+        for name in names {
+            match serialize(&globals[name]) {
+                Some(value) => entries.push(format!("{}:{}", serialize_string(name), value)),
+                None => skipped.push(name),
+            }
+        }
+        Snapshot {
+            blob: format!("{{{}}}", entries.join(",")),
+            skipped,
+        }
+    }
+
+    pub fn restore(&self, blob: &'a str) -> Result<(), ProgramError<'a>> {
+        let globals = SnapshotReader::new(blob).read_globals()?;
+        let state = self.state.borrow();
+        for (name, value) in globals {
+            state.environments[0].borrow_mut().insert(name, value);
+        }
+        Ok(())
+    }
+}
+
+struct SnapshotReader<'a> {
+    blob: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl<'a> SnapshotReader<'a> {
+    fn new(blob: &'a str) -> SnapshotReader<'a> {
+        SnapshotReader {
+            blob,
+            chars: blob.char_indices().peekable(),
+        }
+    }
+
+    fn error(&self, message: &str) -> ProgramError<'a> {
+        ProgramError {
+            code: ErrorCode::InvalidValue,
+            location: SNAPSHOT_LOCATION,
+            message: format!("Invalid snapshot: {}", message),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some((_, c)) = self.chars.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.chars.next();
+        }
+    }
+
+    fn consume(&mut self, expected: char) -> Result<(), ProgramError<'a>> {
+        self.skip_whitespace();
+        match self.chars.next() {
+            Some((_, c)) if c == expected => Ok(()),
+            _ => Err(self.error(format!("expected `{}`", expected).as_str())),
+        }
+    }
+
+    fn peek_is(&mut self, expected: char) -> bool {
+        self.skip_whitespace();
+        matches!(self.chars.peek(), Some((_, c)) if *c == expected)
+    }
+
+    fn read_globals(&mut self) -> Result<Vec<(&'a str, Value<'a>)>, ProgramError<'a>> {
+        let mut globals = vec![];
+        self.consume('{')?;
+        while !self.peek_is('}') {
+            if !globals.is_empty() {
+                self.consume(',')?;
+            }
+            let name = self.read_name()?;
+            self.consume(':')?;
+            globals.push((name, self.read_value()?));
+        }
+        self.consume('}')?;
+        self.skip_whitespace();
+        if self.chars.next().is_some() {
+            return Err(self.error("unexpected content after globals"));
+        }
+        Ok(globals)
+    }
+
+    fn read_name(&mut self) -> Result<&'a str, ProgramError<'a>> {
+        self.consume('"')?;
+        let start = self.chars.peek().map_or(self.blob.len(), |(i, _)| *i);
+        for (i, c) in self.chars.by_ref() {
+            if c == '"' {
+                return Ok(&self.blob[start..i]);
+            }
+        }
+        Err(self.error("unterminated name"))
+    }
+
+    fn read_string(&mut self) -> Result<String, ProgramError<'a>> {
+        self.consume('"')?;
+        let mut value = String::new();
+        while let Some((_, c)) = self.chars.next() {
+            match c {
+                '"' => return Ok(value),
+                '\\' => match self.chars.next() {
+                    Some((_, 'n')) => value.push('\n'),
+                    Some((_, 't')) => value.push('\t'),
+                    Some((_, 'r')) => value.push('\r'),
+                    Some((_, c)) => value.push(c),
+                    None => break,
+                },
+                c => value.push(c),
+            }
+        }
+        Err(self.error("unterminated string"))
+    }
+
+    fn read_word(&mut self) -> &'a str {
+        let start = self.chars.peek().map_or(self.blob.len(), |(i, _)| *i);
+        let mut end = start;
+        while let Some((i, c)) = self.chars.peek() {
+            if !(c.is_alphanumeric() || *c == '-' || *c == '+' || *c == '.') {
+                break;
+            }
+            end = *i + c.len_utf8();
+            self.chars.next();
+        }
+        &self.blob[start..end]
+    }
+
+    fn read_value(&mut self) -> Result<Value<'a>, ProgramError<'a>> {
+        self.skip_whitespace();
+        match self.chars.peek().map(|(_, c)| *c) {
+            Some('"') => Ok(Value::String {
+                value: self.read_string()?,
+            }),
+            Some('{') => {
+                self.consume('{')?;
+                let mut map = LoxMap::default();
+                while !self.peek_is('}') {
+                    if !map.is_empty() {
+                        self.consume(',')?;
+                    }
+                    let key = self.read_string()?;
+                    self.consume(':')?;
+                    map.insert(MapKey::String(key), self.read_value()?);
+                }
+                self.consume('}')?;
+                Ok(Value::new_map(map))
+            }
+            Some('[') => {
+                self.consume('[')?;
+                let mut elements = vec![];
+                while !self.peek_is(']') {
+                    if !elements.is_empty() {
+                        self.consume(',')?;
+                    }
+                    elements.push(Box::new(self.read_value()?));
+                }
+                self.consume(']')?;
+                Ok(Value::Array(Rc::new(RefCell::new(LoxArray {
+                    capacity: elements.len(),
+                    elements,
+                }))))
+            }
+            Some(_) => {
+                let word = self.read_word();
+                match word {
+                    "null" => Ok(Value::Nil),
+                    "true" => Ok(Value::Boolean { value: true }),
+                    "false" => Ok(Value::Boolean { value: false }),
+                    w if w.contains('.') || w.contains('e') => w
+                        .parse()
+                        .map(|value| Value::Float { value })
+                        .map_err(|_| self.error(format!("invalid number `{}`", w).as_str())),
+                    w => w
+                        .parse()
+                        .map(|value| Value::Integer { value })
+                        .map_err(|_| self.error(format!("unexpected `{}`", w).as_str())),
+                }
+            }
+            None => Err(self.error("unexpected end of snapshot")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::interpreter::Interpreter;
+    use crate::interpreter::common_test::parse;
+    use crate::value::Value;
+
+    #[test]
+    fn test_snapshot_and_restore() {
+        let source = parse(
+            "var a = 1; var b = [2.5, \"xy\", nil, true]; var c = {\"k\": [1]}; fun f() {} \
+             var d = {}; d[\"d\"] = d; var e = [1]; e[0] = e;"
+        );
+        let interpreter = Interpreter::new(&[], "");
+        interpreter.run(&source).unwrap();
+        let snapshot = interpreter.snapshot();
+        assert_eq!(snapshot.skipped, vec!["d", "e", "f"]);
+
+        let restored = Interpreter::new(&[], "");
+        restored.restore(&snapshot.blob).unwrap();
+        let state = restored.state.borrow();
+        assert_eq!(state.get_global("a"), Some(Value::Integer { value: 1 }));
+        assert_eq!(state.get_global("b").map(|b| b.to_string()), Some("[ 2.5, xy, nil, true, ]".to_owned()));
+        assert_eq!(state.get_global("c").map(|c| c.to_string()), Some("{ k: [ 1, ], }".to_owned()));
+        assert_eq!(state.get_global("f"), None);
+    }
+
+    #[test]
+    fn test_restore_invalid_snapshot() {
+        let interpreter = Interpreter::new(&[], "");
+        assert!(interpreter.restore("{\"a\":").is_err());
+    }
+}
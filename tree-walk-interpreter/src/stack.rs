@@ -0,0 +1,131 @@
+use crate::interpreter::EvaluationResult;
+use std::cell::Cell;
+use std::panic;
+use std::thread;
+
+/// Size of the stack of each thread calls move to.
+const SEGMENT_SIZE: usize = 64 << 20;
+/// Space left in the current stack below which a call moves to a new thread. It has to
+/// cover everything the interpreter pushes between two function calls. Going past it hits
+/// the guard page of the thread instead of corrupting memory.
+const RED_ZONE: usize = 512 << 10;
+
+thread_local! {
+    /// Lowest address calls on this thread can use before moving to a new one, or zero on
+    /// threads `grow` didn't start, as there is no portable way to know their stack size.
+    static STACK_LIMIT: Cell<usize> = const { Cell::new(0) };
+}
+
+fn stack_pointer() -> usize {
+    let marker = 0u8;
+    &marker as *const u8 as usize
+}
+
+/// Runs `f` on a new thread with a stack of its own when the current one is running low,
+/// so recursion through it is only bounded by memory. Calls made from a thread `grow`
+/// didn't start always move, as there's no telling how much of its stack is left.
+pub fn grow<'a, F: FnOnce() -> EvaluationResult<'a>>(f: F) -> EvaluationResult<'a> {
+    let limit = STACK_LIMIT.with(|limit| limit.get());
+    if limit != 0 && stack_pointer() > limit + RED_ZONE {
+        return f();
+    }
+    on_new_thread(Call(Box::new(f)))
+}
+
+/// A call moved to the thread `grow` starts.
+struct Call<'f, 'a>(Box<dyn FnOnce() -> EvaluationResult<'a> + 'f>);
+
+/// The result of a `Call`, moved back to the thread that started it.
+struct CallResult<'a>(EvaluationResult<'a>);
+
+// SAFETY: a call and its result hold values that aren't thread safe, like `Rc`s into the
+// interpreter. They only cross to the thread `grow` starts and back while the thread that
+// started it is blocked joining it, so they're never used from two threads at once.
+unsafe impl Send for Call<'_, '_> {}
+unsafe impl Send for CallResult<'_> {}
+
+fn on_new_thread<'a>(call: Call<'_, 'a>) -> EvaluationResult<'a> {
+    let result = thread::scope(|scope| {
+        thread::Builder::new()
+            .stack_size(SEGMENT_SIZE)
+            .spawn_scoped(scope, move || {
+                // The runtime keeps a bit of the stack for itself, so leave a red zone at
+                // the bottom unused too.
+                let limit = stack_pointer() - SEGMENT_SIZE + RED_ZONE;
+                STACK_LIMIT.with(|stack_limit| stack_limit.set(limit));
+                CallResult((call.0)())
+            })
+            .expect("Couldn't start a thread for the call stack")
+            .join()
+    });
+    match result {
+        Ok(CallResult(value)) => value,
+        Err(payload) => panic::resume_unwind(payload),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::interpreter::Interpreter;
+    use crate::interpreter::common_test::parse;
+    use crate::value::Value;
+    use parser::resolver::Resolver;
+    use parser::types::Pass;
+    use std::env;
+    use std::process::Command;
+    use std::thread;
+
+    /// Sums 10000 numbers recursively on a thread whose stack is far too small for it.
+    fn sum_recursively_on_small_stack(heap_call_stack: bool) {
+        thread::Builder::new()
+            .stack_size(256 << 10)
+            .spawn(move || {
+                let source = parse(
+                    "fun sum(n) { if (n == 0) return 0; return n + sum(n - 1); } var result = sum(10000);"
+                );
+                let mut interpreter = Interpreter::new(&[], "");
+                interpreter.config.heap_call_stack = heap_call_stack;
+                interpreter.locals.replace(Resolver::new().run(&source).unwrap());
+                interpreter.run(&source).unwrap();
+                assert_eq!(interpreter.state.borrow().get_global("result"), Some(Value::Integer { value: 50005000 }));
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_heap_call_stack_recursion() {
+        // Overflowing the stack aborts the process, so running without the heap call stack
+        // happens in a copy of this test binary.
+        if env::var_os("SMOKED_STACK_OVERFLOW_TEST").is_some() {
+            sum_recursively_on_small_stack(true);
+            eprintln!("Summed with the heap call stack");
+            sum_recursively_on_small_stack(false);
+            return;
+        }
+        let source = parse(
+            "fun sum(n) { if (n == 0) return 0; return n + sum(n - 1); } \
+             var result = sum(10000); \
+             fun fail(n) { if (n == 0) return nil + 1; return fail(n - 1) + 1; } \
+             fail(10000);"
+        );
+        let mut interpreter = Interpreter::new(&[], "");
+        interpreter.config.heap_call_stack = true;
+        interpreter.locals.replace(Resolver::new().run(&source).unwrap());
+        let error = interpreter.run(&source).unwrap_err();
+
+        assert_eq!(interpreter.state.borrow().get_global("result"), Some(Value::Integer { value: 50005000 }));
+        assert_eq!(error.location.line, 0);
+
+        let output = Command::new(env::current_exe().unwrap())
+            .args(["stack::test::test_heap_call_stack_recursion", "--exact", "--nocapture"])
+            .env("SMOKED_STACK_OVERFLOW_TEST", "1")
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(!output.status.success());
+        assert!(stderr.contains("Summed with the heap call stack"));
+        assert!(stderr.contains("has overflowed its stack"));
+    }
+}
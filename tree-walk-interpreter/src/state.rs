@@ -81,6 +81,14 @@ impl<'a> State<'a> {
             .flatten()
     }
 
+    /// Replaces the environment `depth` levels below the innermost one with a copy, leaving
+    /// the original to the closures that captured it.
+    pub fn copy_environment(&mut self, depth: usize) {
+        let index = self.environments.len() - 1 - depth;
+        let copy = self.environments[index].borrow().clone();
+        self.environments[index] = Rc::new(RefCell::new(copy));
+    }
+
     pub fn add_return_value(&mut self, v: Value<'a>) {
         self.return_value = Some(Box::new(v));
     }
@@ -0,0 +1,222 @@
+use crate::interpreter::{EvaluationResult, Interpreter};
+use crate::native::NativeFunction;
+use crate::value::{Value, ValueError};
+use parser::types::{ErrorCode, ProgramError, SourceCodeLocation};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+fn receiver<'b>(values: &'b [Value]) -> &'b str {
+    match values.first() {
+        Some(Value::String { value }) => value.as_str(),
+        _ => unreachable!("String methods are only bound to strings"),
+    }
+}
+
+fn buffer_receiver<'a, 'b>(values: &'b [Value<'a>]) -> &'b Rc<RefCell<String>> {
+    match values.first() {
+        Some(Value::StringBuffer(buffer)) => buffer,
+        _ => unreachable!("String buffer methods are only bound to string buffers"),
+    }
+}
+
+fn bytes<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    _location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    Ok(Value::new_array(
+        receiver(values)
+            .bytes()
+            .map(|b| Value::Integer { value: b as _ })
+            .collect(),
+    ))
+}
+
+fn byte_len<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    _location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    Ok(Value::Integer {
+        value: receiver(values).len() as _,
+    })
+}
+
+/// Pads the receiver to `values[1]` characters, repeating the optional pad string in
+/// `values[2]` (a space by default). Strings that are already wide enough are unchanged.
+fn pad<'a>(values: &[Value<'a>], location: &SourceCodeLocation<'a>, at_start: bool) -> EvaluationResult<'a> {
+    let value = receiver(values);
+    let width = match values[1] {
+        Value::Integer { value } => value.max(0) as usize,
+        _ => return Err(ValueError::ExpectingInteger.into_program_error(location)),
+    };
+    let pad = match values.get(2) {
+        Some(Value::String { value }) => value.as_str(),
+        Some(_) => return Err(ValueError::ExpectingString.into_program_error(location)),
+        None => " ",
+    };
+    if pad.is_empty() {
+        return Err(ProgramError {
+            code: ErrorCode::InvalidValue,
+            location: location.clone(),
+            message: "Padding string can't be empty".to_owned(),
+        });
+    }
+    let padding: String = pad.chars().cycle().take(width.saturating_sub(value.chars().count())).collect();
+    Ok(Value::String {
+        value: if at_start { padding + value } else { value.to_owned() + &padding },
+    })
+}
+
+fn pad_start<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    pad(values, location, true)
+}
+
+fn pad_end<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    pad(values, location, false)
+}
+
+/// Character indices where the substring in `values[1]` starts, overlapping occurrences
+/// included.
+fn occurrences<'a>(values: &[Value<'a>], location: &SourceCodeLocation<'a>) -> Result<Vec<i64>, ProgramError<'a>> {
+    let value = receiver(values);
+    let sub = match &values[1] {
+        Value::String { value } => value.as_str(),
+        _ => return Err(ValueError::ExpectingString.into_program_error(location)),
+    };
+    if sub.is_empty() {
+        return Err(ProgramError {
+            code: ErrorCode::InvalidValue,
+            location: location.clone(),
+            message: "Search string can't be empty".to_owned(),
+        });
+    }
+    Ok(value
+        .char_indices()
+        .enumerate()
+        .filter(|(_, (offset, _))| value[*offset..].starts_with(sub))
+        .map(|(index, _)| index as i64)
+        .collect())
+}
+
+fn index_of<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    Ok(Value::Integer {
+        value: occurrences(values, location)?.first().copied().unwrap_or(-1),
+    })
+}
+
+fn last_index_of<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    Ok(Value::Integer {
+        value: occurrences(values, location)?.last().copied().unwrap_or(-1),
+    })
+}
+
+fn find_all<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    Ok(Value::new_array(
+        occurrences(values, location)?
+            .into_iter()
+            .map(|value| Value::Integer { value })
+            .collect(),
+    ))
+}
+
+pub fn string_method<'a>(name: &str) -> Option<NativeFunction<'a>> {
+    match name {
+        "bytes" => Some(NativeFunction { name: "bytes", arity: 0..=0, function: bytes }),
+        "byte_len" => Some(NativeFunction { name: "byte_len", arity: 0..=0, function: byte_len }),
+        "pad_start" => Some(NativeFunction { name: "pad_start", arity: 1..=2, function: pad_start }),
+        "pad_end" => Some(NativeFunction { name: "pad_end", arity: 1..=2, function: pad_end }),
+        "index_of" => Some(NativeFunction { name: "index_of", arity: 1..=1, function: index_of }),
+        "last_index_of" => Some(NativeFunction { name: "last_index_of", arity: 1..=1, function: last_index_of }),
+        "find_all" => Some(NativeFunction { name: "find_all", arity: 1..=1, function: find_all }),
+        _ => None,
+    }
+}
+
+/// Appends the string form of `values[1]` to the buffer.
+fn append<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    _location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    let addition = values[1].to_string();
+    let mut buffer = buffer_receiver(values).borrow_mut();
+    buffer.push_str(&addition);
+    Ok(Value::Nil)
+}
+
+/// Inserts the string form of `values[2]` before the character at index `values[1]`.
+fn insert<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    // The addition can print the buffer itself, so it's built before borrowing it.
+    let addition = values[2].to_string();
+    let mut buffer = buffer_receiver(values).borrow_mut();
+    let index = match values[1] {
+        Value::Integer { value } => value,
+        _ => return Err(ValueError::ExpectingInteger.into_program_error(location)),
+    };
+    let length = buffer.chars().count();
+    if index < 0 || index as usize > length {
+        return Err(ProgramError {
+            code: ErrorCode::IndexError,
+            location: location.clone(),
+            message: format!("Can't insert at {} in a buffer of {} characters", index, length),
+        });
+    }
+    let offset = buffer.char_indices().nth(index as usize).map_or(buffer.len(), |(offset, _)| offset);
+    buffer.insert_str(offset, &addition);
+    Ok(Value::Nil)
+}
+
+fn clear<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    _location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    buffer_receiver(values).borrow_mut().clear();
+    Ok(Value::Nil)
+}
+
+/// Copies the buffer into an immutable string; later changes to the buffer don't affect it.
+fn buffer_to_string<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    _location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    Ok(Value::String {
+        value: buffer_receiver(values).borrow().clone(),
+    })
+}
+
+pub fn string_buffer_method<'a>(name: &str) -> Option<NativeFunction<'a>> {
+    match name {
+        "append" => Some(NativeFunction { name: "append", arity: 1..=1, function: append }),
+        "insert" => Some(NativeFunction { name: "insert", arity: 2..=2, function: insert }),
+        "clear" => Some(NativeFunction { name: "clear", arity: 0..=0, function: clear }),
+        "to_string" => Some(NativeFunction { name: "to_string", arity: 0..=0, function: buffer_to_string }),
+        _ => None,
+    }
+}
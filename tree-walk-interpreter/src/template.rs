@@ -0,0 +1,151 @@
+use crate::interpreter::{EvaluationResult, Interpreter};
+use crate::native::NativeFunction;
+use crate::value::{MapKey, Value};
+use parser::types::{ErrorCode, ProgramError, SourceCodeLocation};
+use std::rc::Rc;
+
+#[derive(Debug, PartialEq)]
+enum TemplatePart {
+    Text(String),
+    Placeholder(String),
+}
+
+/// A string with `${name}` placeholders, split once so it can be rendered many times. `$${`
+/// stands for a literal `${`.
+#[derive(Debug, PartialEq)]
+pub struct LoxTemplate {
+    pub source: String,
+    parts: Vec<TemplatePart>,
+}
+
+impl LoxTemplate {
+    pub fn compile(source: &str) -> Result<LoxTemplate, String> {
+        let mut parts = vec![];
+        let mut text = String::new();
+        let mut rest = source;
+        while let Some(start) = rest.find("${") {
+            if rest[..start].ends_with('$') {
+                text.push_str(&rest[..start - 1]);
+                text.push_str("${");
+                rest = &rest[start + 2..];
+                continue;
+            }
+            text.push_str(&rest[..start]);
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("Unterminated placeholder in template: {}", &rest[start..]))?;
+            let name = rest[start + 2..start + end].trim();
+            if name.is_empty() {
+                return Err("Empty placeholder in template".to_owned());
+            }
+            if !text.is_empty() {
+                parts.push(TemplatePart::Text(std::mem::take(&mut text)));
+            }
+            parts.push(TemplatePart::Placeholder(name.to_owned()));
+            rest = &rest[start + end + 1..];
+        }
+        text.push_str(rest);
+        if !text.is_empty() {
+            parts.push(TemplatePart::Text(text));
+        }
+        Ok(LoxTemplate {
+            source: source.to_owned(),
+            parts,
+        })
+    }
+
+    /// Substitutes every placeholder with the string form of the entry of the same name in
+    /// `lookup`. Missing entries render empty when `allow_missing` is set, and are returned
+    /// as the error otherwise.
+    pub fn render<'a, F: Fn(&str) -> Option<Value<'a>>>(
+        &self,
+        lookup: F,
+        allow_missing: bool,
+    ) -> Result<String, &str> {
+        let mut result = String::new();
+        for part in self.parts.iter() {
+            match part {
+                TemplatePart::Text(text) => result.push_str(text),
+                TemplatePart::Placeholder(name) => match lookup(name) {
+                    Some(value) => result.push_str(&value.to_string()),
+                    None if allow_missing => {}
+                    None => return Err(name),
+                },
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// Compiles the string in `values[0]` into a template.
+pub fn compile<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    let source = values[0].clone().coerce_string().map_err(|e| e.into_program_error(location))?;
+    let template = LoxTemplate::compile(&source).map_err(|message| ProgramError {
+        code: ErrorCode::InvalidValue,
+        location: location.clone(),
+        message,
+    })?;
+    Ok(Value::Template(Rc::new(template)))
+}
+
+/// Renders the template with the entries of the map in `values[1]`. A placeholder without
+/// an entry is an error, unless the optional `values[2]` is truthy, which renders it empty.
+fn render<'a>(
+    _interpreter: &'a Interpreter<'a>,
+    values: &[Value<'a>],
+    location: &SourceCodeLocation<'a>,
+) -> EvaluationResult<'a> {
+    let template = match values.first() {
+        Some(Value::Template(t)) => t,
+        _ => unreachable!("Template methods are only bound to templates"),
+    };
+    let map = match &values[1] {
+        Value::Map(m) => m,
+        _ => {
+            return Err(ProgramError {
+                code: ErrorCode::TypeError,
+                location: location.clone(),
+                message: "Template values should be a map".to_owned(),
+            })
+        }
+    };
+    let allow_missing = matches!(values.get(2), Some(v) if v.is_truthy());
+    let value = template
+        .render(|name| map.borrow().get(&MapKey::String(name.to_owned())).cloned(), allow_missing)
+        .map_err(|name| ProgramError {
+            code: ErrorCode::NameError,
+            location: location.clone(),
+            message: format!("Missing template value `{}`", name),
+        })?;
+    Ok(Value::String { value })
+}
+
+pub fn template_method<'a>(name: &str) -> Option<NativeFunction<'a>> {
+    match name {
+        "render" => Some(NativeFunction { name: "render", arity: 1..=2, function: render }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::template::LoxTemplate;
+    use crate::value::Value;
+
+    #[test]
+    fn test_compile_and_render() {
+        let template = LoxTemplate::compile("Hi ${ name }, $${name} costs ${price}!").unwrap();
+        let lookup = |name: &str| match name {
+            "name" => Some(Value::String { value: "Ana".to_owned() }),
+            _ => None,
+        };
+        assert_eq!(template.render(lookup, true), Ok("Hi Ana, ${name} costs !".to_owned()));
+        assert_eq!(template.render(lookup, false), Err("price"));
+        assert!(LoxTemplate::compile("${open").is_err());
+        assert!(LoxTemplate::compile("${}").is_err());
+    }
+}
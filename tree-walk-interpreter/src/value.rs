@@ -1,10 +1,17 @@
-use crate::class::{LoxClass, LoxObject};
+use crate::class::{LoxClass, LoxObject, WeakObject};
+use crate::config::PrintFormat;
+use ahash::{AHashMap as HashMap};
 use crate::function::LoxFunction;
-use parser::types::{DataKeyword, FunctionHeader, Literal, ProgramError, SourceCodeLocation};
+use crate::native::NativeFunction;
+use crate::template::LoxTemplate;
+use parser::types::{DataKeyword, EnumVariant, ErrorCode, FunctionHeader, Literal, ProgramError, SourceCodeLocation};
 use std::cell::RefCell;
-use std::convert::{TryInto, TryFrom};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
 use std::fmt::{Display, Error, Formatter, Debug};
-use std::ops::{Neg, Not};
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
+use std::ops::Not;
 use std::rc::Rc;
 
 #[derive(Debug, PartialEq)]
@@ -17,12 +24,212 @@ pub struct LoxTrait<'a> {
 }
 
 #[derive(Debug, PartialEq)]
+pub struct LoxEnum<'a> {
+    pub name: &'a str,
+    pub variants: Vec<EnumVariant<'a>>,
+}
+
+impl<'a> LoxEnum<'a> {
+    pub fn variant(&self, name: &str) -> Option<&EnumVariant<'a>> {
+        self.variants.iter().find(|v| v.name == name)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct LoxEnumInstance<'a> {
+    pub lox_enum: Rc<LoxEnum<'a>>,
+    pub variant: &'a str,
+    pub values: Vec<Value<'a>>,
+}
+
+/// An interned name. Symbols with the same name share their allocation, so they're compared
+/// and hashed by address instead of by content.
+#[derive(Clone, Debug)]
+pub struct Symbol(pub Rc<str>);
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Symbol) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (Rc::as_ptr(&self.0) as *const u8 as usize).hash(state);
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, ":{}", self.0)
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum MapKey {
+    Nil,
+    Boolean(bool),
+    Integer(i64),
+    String(String),
+    Symbol(Symbol),
+}
+
+impl Display for MapKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            MapKey::Nil => f.write_str("nil"),
+            MapKey::Boolean(value) => f.write_str(value.to_string().as_str()),
+            MapKey::Integer(value) => f.write_str(value.to_string().as_str()),
+            MapKey::String(value) => f.write_str(value.as_str()),
+            MapKey::Symbol(symbol) => write!(f, "{}", symbol),
+        }
+    }
+}
+
+impl<'a> TryFrom<&Value<'a>> for MapKey {
+    type Error = ValueError;
+    fn try_from(value: &Value<'a>) -> Result<MapKey, Self::Error> {
+        match value {
+            Value::Nil => Ok(MapKey::Nil),
+            Value::Boolean { value } => Ok(MapKey::Boolean(*value)),
+            Value::Integer { value } => Ok(MapKey::Integer(*value)),
+            Value::String { value } => Ok(MapKey::String(value.clone())),
+            Value::Symbol(symbol) => Ok(MapKey::Symbol(symbol.clone())),
+            _ => Err(ValueError::InvalidMapKey),
+        }
+    }
+}
+
+impl<'a> From<&MapKey> for Value<'a> {
+    fn from(key: &MapKey) -> Value<'a> {
+        match key {
+            MapKey::Nil => Value::Nil,
+            MapKey::Boolean(value) => Value::Boolean { value: *value },
+            MapKey::Integer(value) => Value::Integer { value: *value },
+            MapKey::String(value) => Value::String { value: value.clone() },
+            MapKey::Symbol(symbol) => Value::Symbol(symbol.clone()),
+        }
+    }
+}
+
+/// A map that iterates its entries in insertion order.
+#[derive(Clone)]
+pub struct OrderedMap<K, V> {
+    entries: Vec<(K, V)>,
+    index: HashMap<K, usize>,
+}
+
+pub type LoxMap<'a> = OrderedMap<MapKey, Value<'a>>;
+
+impl<K, V> Default for OrderedMap<K, V> {
+    fn default() -> OrderedMap<K, V> {
+        OrderedMap {
+            entries: vec![],
+            index: HashMap::default(),
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone, V> OrderedMap<K, V> {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get<Q: Hash + Eq + ?Sized>(&self, key: &Q) -> Option<&V> where K: std::borrow::Borrow<Q> {
+        self.index.get(key).map(|i| &self.entries[*i].1)
+    }
+
+    pub fn get_mut<Q: Hash + Eq + ?Sized>(&mut self, key: &Q) -> Option<&mut V> where K: std::borrow::Borrow<Q> {
+        let i = *self.index.get(key)?;
+        Some(&mut self.entries[i].1)
+    }
+
+    pub fn insert(&mut self, key: K, value: V) {
+        match self.index.get(&key) {
+            Some(i) => self.entries[*i].1 = value,
+            None => {
+                self.index.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+    }
+
+    pub fn remove<Q: Hash + Eq + ?Sized>(&mut self, key: &Q) -> Option<V> where K: std::borrow::Borrow<Q> {
+        let i = self.index.remove(key)?;
+        let (_, value) = self.entries.remove(i);
+        for (key, _) in self.entries[i..].iter() {
+            if let Some(j) = self.index.get_mut::<K>(key) {
+                *j -= 1;
+            }
+        }
+        Some(value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries.iter().map(|(k, v)| (k, v))
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: PartialEq> PartialEq for OrderedMap<K, V> {
+    fn eq(&self, other: &OrderedMap<K, V>) -> bool {
+        self.len() == other.len() && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+impl<K: Hash + Eq + Clone + Debug, V: Debug> Debug for OrderedMap<K, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Hash + Eq + Clone, V> Extend<(K, V)> for OrderedMap<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone, V> FromIterator<(K, V)> for OrderedMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> OrderedMap<K, V> {
+        let mut map = OrderedMap::default();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K, V> IntoIterator for OrderedMap<K, V> {
+    type Item = (K, V);
+    type IntoIter = std::vec::IntoIter<(K, V)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+#[derive(Debug)]
 pub struct LoxArray<'a> {
     pub capacity: usize,
     pub elements: Vec<Box<Value<'a>>>,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+/// Arrays of different lengths are never equal, so that's checked before looking at any
+/// element.
+impl<'a> PartialEq for LoxArray<'a> {
+    fn eq(&self, other: &LoxArray<'a>) -> bool {
+        std::ptr::eq(self, other)
+            || self.elements.len() == other.elements.len()
+            && self.elements.iter().zip(other.elements.iter()).all(|(a, b)| a == b)
+    }
+}
+
+#[derive(Clone, PartialEq)]
 pub enum Value<'a> {
     Nil,
     Uninitialized,
@@ -38,16 +245,133 @@ pub enum Value<'a> {
     String {
         value: String,
     },
+    StringBuffer(Rc<RefCell<String>>),
+    Template(Rc<LoxTemplate>),
+    Symbol(Symbol),
     Function(Rc<LoxFunction<'a>>),
+    NativeFunction(Rc<NativeFunction<'a>>),
+    NativeMethod(Rc<NativeFunction<'a>>, Box<Value<'a>>),
     Method(Rc<LoxFunction<'a>>, Rc<LoxObject<'a>>),
+    WeakMethod(Rc<LoxFunction<'a>>, WeakObject<'a>),
     Class(Rc<LoxClass<'a>>),
     Object(Rc<LoxObject<'a>>),
     Trait(Rc<LoxTrait<'a>>),
     Array(Rc<RefCell<LoxArray<'a>>>),
+    Tuple(Rc<Vec<Value<'a>>>),
+    Map(Rc<RefCell<LoxMap<'a>>>),
     Module(&'a str),
+    Enum(Rc<LoxEnum<'a>>),
+    EnumVariant(Rc<LoxEnum<'a>>, &'a str),
+    EnumInstance(Rc<LoxEnumInstance<'a>>),
 }
 
 impl<'a> Value<'a> {
+    pub fn new_array(elements: Vec<Value<'a>>) -> Value<'a> {
+        Value::Array(Rc::new(RefCell::new(LoxArray {
+            capacity: elements.len(),
+            elements: elements.into_iter().map(Box::new).collect(),
+        })))
+    }
+
+    pub fn new_tuple(elements: Vec<Value<'a>>) -> Value<'a> {
+        Value::Tuple(Rc::new(elements))
+    }
+
+    pub fn new_string_buffer(value: String) -> Value<'a> {
+        Value::StringBuffer(Rc::new(RefCell::new(value)))
+    }
+
+    pub fn new_map(map: LoxMap<'a>) -> Value<'a> {
+        Value::Map(Rc::new(RefCell::new(map)))
+    }
+
+    /// Writes the debug representation of the value, eliding arrays, maps and objects that
+    /// are already being formatted further up so cyclic graphs don't recurse forever.
+    fn fmt_debug(&self, f: &mut Formatter<'_>, parents: &mut Vec<*const ()>) -> Result<(), Error> {
+        let pointer = match self {
+            Value::Array(a) => Rc::as_ptr(a) as *const (),
+            Value::Map(m) => Rc::as_ptr(m) as *const (),
+            Value::Object(o) => Rc::as_ptr(o) as *const (),
+            Value::Nil => return f.write_str("Nil"),
+            Value::Uninitialized => return f.write_str("Uninitialized"),
+            Value::Boolean { value } => return write!(f, "Boolean {{ value: {:?} }}", value),
+            Value::Integer { value } => return write!(f, "Integer {{ value: {:?} }}", value),
+            Value::Float { value } => return write!(f, "Float {{ value: {:?} }}", value),
+            Value::String { value } => return write!(f, "String {{ value: {:?} }}", value),
+            Value::StringBuffer(buffer) => return write!(f, "StringBuffer({:?})", buffer.borrow()),
+            Value::Template(template) => return write!(f, "Template({:?})", template.source),
+            Value::Symbol(symbol) => return write!(f, "Symbol({:?})", symbol.0),
+            Value::Function(lf) => return write!(f, "Function({:?})", lf),
+            Value::NativeFunction(nf) => return write!(f, "NativeFunction({:?})", nf),
+            Value::NativeMethod(nf, receiver) => {
+                write!(f, "NativeMethod({:?}, ", nf)?;
+                receiver.fmt_debug(f, parents)?;
+                return f.write_str(")");
+            }
+            Value::Method(lf, o) => return write!(f, "Method({:?}, {} instance)", lf, o.class_name),
+            Value::WeakMethod(lf, o) => return match o.0.upgrade() {
+                Some(o) => write!(f, "WeakMethod({:?}, {} instance)", lf, o.class_name),
+                None => write!(f, "WeakMethod({:?}, <dropped>)", lf),
+            },
+            Value::Class(c) => return write!(f, "Class({})", c.name),
+            Value::Trait(t) => return write!(f, "Trait({})", t.name),
+            Value::Module(name) => return write!(f, "Module({:?})", name),
+            Value::Enum(e) => return write!(f, "Enum({})", e.name),
+            Value::EnumVariant(e, variant) => return write!(f, "EnumVariant({}.{})", e.name, variant),
+            Value::Tuple(elements) => {
+                f.write_str("Tuple(")?;
+                for e in elements.iter() {
+                    e.fmt_debug(f, parents)?;
+                    f.write_str(", ")?;
+                }
+                return f.write_str(")");
+            }
+            Value::EnumInstance(v) => {
+                write!(f, "EnumInstance({}.{}(", v.lox_enum.name, v.variant)?;
+                for value in v.values.iter() {
+                    value.fmt_debug(f, parents)?;
+                    f.write_str(", ")?;
+                }
+                return f.write_str("))");
+            }
+        };
+        if parents.contains(&pointer) {
+            return f.write_str("<cycle>");
+        }
+        parents.push(pointer);
+        match self {
+            Value::Array(a) => {
+                f.write_str("Array([")?;
+                for e in a.borrow().elements.iter() {
+                    e.fmt_debug(f, parents)?;
+                    f.write_str(", ")?;
+                }
+                f.write_str("])")?;
+            }
+            Value::Map(m) => {
+                f.write_str("Map({")?;
+                for (key, value) in m.borrow().iter() {
+                    write!(f, "{:?}: ", key)?;
+                    value.fmt_debug(f, parents)?;
+                    f.write_str(", ")?;
+                }
+                f.write_str("})")?;
+            }
+            Value::Object(o) => {
+                write!(f, "Object({} {{ ", o.class_name)?;
+                for (name, value) in o.properties() {
+                    write!(f, "{}: ", name)?;
+                    value.fmt_debug(f, parents)?;
+                    f.write_str(", ")?;
+                }
+                f.write_str("})")?;
+            }
+            _ => unreachable!(),
+        }
+        parents.pop();
+        Ok(())
+    }
+
     pub fn is_number(&self) -> bool {
         match self {
             Value::Integer { .. } => true,
@@ -70,6 +394,17 @@ impl<'a> Value<'a> {
         }
     }
 
+    pub fn negate(self) -> Result<Value<'a>, ValueError> {
+        match self {
+            Value::Integer { value } => value.checked_neg()
+                .map(|value| Value::Integer { value })
+                .ok_or(ValueError::IntegerOverflow),
+            Value::Float { value } => Ok(Value::Float { value: -value }),
+            _ => Err(ValueError::ExpectingNumber),
+        }
+    }
+
+    /// Truthiness without calling `to_bool` on objects, which `Interpreter::is_truthy` does.
     pub fn is_truthy(&self) -> bool {
         match self {
             Value::Nil => false,
@@ -82,14 +417,26 @@ impl<'a> Value<'a> {
     }
 }
 
-impl<'a> Neg for Value<'a> {
-    type Output = Value<'a>;
+impl<'a> Debug for Value<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        self.fmt_debug(f, &mut vec![])
+    }
+}
 
-    fn neg(self) -> Value<'a> {
-        match self {
-            Value::Integer { value } => Value::Integer { value: -value },
-            Value::Float { value } => Value::Float { value: -value },
-            _ => panic!("Only numbers can change sign"),
+impl<'a> PartialOrd for Value<'a> {
+    fn partial_cmp(&self, other: &Value<'a>) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Integer { value: l }, Value::Integer { value: r }) => l.partial_cmp(r),
+            (Value::Float { value: l }, Value::Float { value: r }) => l.partial_cmp(r),
+            // Like strict `==`, an integer never equals a float, so on a tie the integer goes
+            // first. `Interpreter::compare_values` promotes instead when equality does.
+            (Value::Integer { value: l }, Value::Float { value: r }) =>
+                (*l as f64).partial_cmp(&(*r as f64)).map(|o| o.then(Ordering::Less)),
+            (Value::Float { value: l }, Value::Integer { value: r }) =>
+                (*l as f64).partial_cmp(&(*r as f64)).map(|o| o.then(Ordering::Greater)),
+            (Value::String { value: l }, Value::String { value: r }) => l.partial_cmp(r),
+            (Value::Boolean { value: l }, Value::Boolean { value: r }) => l.partial_cmp(r),
+            _ => None,
         }
     }
 }
@@ -107,16 +454,24 @@ impl<'a> Not for Value<'a> {
     }
 }
 
+#[derive(Debug, PartialEq)]
 pub enum ValueError {
-    ExpectingDouble,
+    ExpectingBoolean,
     ExpectingInteger,
     ExpectingNumber,
     ExpectingString,
+    IntegerOverflow,
+    InvalidMapKey,
 }
 
 impl ValueError {
     pub fn into_program_error<'a>(self, location: &SourceCodeLocation<'a>) -> ProgramError<'a> {
+        let code = match self {
+            ValueError::IntegerOverflow => ErrorCode::OverflowError,
+            _ => ErrorCode::TypeError,
+        };
         ProgramError {
+            code,
             location: location.clone(),
             message: self.to_string(),
         }
@@ -126,46 +481,81 @@ impl ValueError {
 impl ToString for ValueError {
     fn to_string(&self) -> String {
         match self {
-            ValueError::ExpectingDouble => "Type error! Expecting a double!".to_owned(),
+            ValueError::ExpectingBoolean => "Type error! Expecting a boolean!".to_owned(),
             ValueError::ExpectingInteger => "Type error! Expecting an integer!".to_owned(),
             ValueError::ExpectingNumber => "Type error! Expecting a number!".to_owned(),
             ValueError::ExpectingString => "Type error! Expecting a string!".to_owned(),
+            ValueError::IntegerOverflow => "Integer overflow!".to_owned(),
+            ValueError::InvalidMapKey => "Type error! Map keys must be nil, booleans, integers, strings or symbols!".to_owned(),
         }
     }
 }
 
-impl<'a> TryFrom<Value<'a>> for i64 {
-    type Error = ValueError;
-    fn try_from(value: Value<'a>) -> Result<i64, Self::Error> {
+/// Fallible conversion from a runtime value into a host type. Every operator and builtin
+/// that needs a concrete Rust value goes through this trait, so a given mismatch always
+/// reports the same `ValueError`.
+pub trait Coerce<'a>: Sized {
+    fn coerce(value: Value<'a>) -> Result<Self, ValueError>;
+}
+
+/// Floats are truncated, which is what lets `a[1.0]` index an array.
+impl<'a> Coerce<'a> for i64 {
+    fn coerce(value: Value<'a>) -> Result<i64, ValueError> {
         match value {
             Value::Integer { value } => Ok(value),
             Value::Float { value } => Ok(value as _),
-            _ => Err(ValueError::ExpectingDouble),
+            _ => Err(ValueError::ExpectingInteger),
         }
     }
 }
 
-impl<'a> TryFrom<Value<'a>> for f32 {
-    type Error = ValueError;
-    fn try_from(value: Value<'a>) -> Result<f32, Self::Error> {
+impl<'a> Coerce<'a> for f32 {
+    fn coerce(value: Value<'a>) -> Result<f32, ValueError> {
         match value {
             Value::Float { value } => Ok(value),
             Value::Integer { value } => Ok(value as _),
-            _ => Err(ValueError::ExpectingDouble),
+            _ => Err(ValueError::ExpectingNumber),
         }
     }
 }
 
-impl<'a> TryInto<String> for Value<'a> {
-    type Error = ValueError;
-    fn try_into(self) -> Result<String, Self::Error> {
-        match self {
+impl<'a> Coerce<'a> for String {
+    fn coerce(value: Value<'a>) -> Result<String, ValueError> {
+        match value {
             Value::String { value } => Ok(value),
             _ => Err(ValueError::ExpectingString),
         }
     }
 }
 
+/// Only booleans coerce; use `Value::is_truthy` for the language's notion of truthiness.
+impl<'a> Coerce<'a> for bool {
+    fn coerce(value: Value<'a>) -> Result<bool, ValueError> {
+        match value {
+            Value::Boolean { value } => Ok(value),
+            _ => Err(ValueError::ExpectingBoolean),
+        }
+    }
+}
+
+impl<'a> Value<'a> {
+    pub fn coerce_integer(self) -> Result<i64, ValueError> {
+        i64::coerce(self)
+    }
+
+    pub fn coerce_number(self) -> Result<f32, ValueError> {
+        f32::coerce(self)
+    }
+
+    pub fn coerce_string(self) -> Result<String, ValueError> {
+        String::coerce(self)
+    }
+
+    pub fn coerce_bool(self) -> Result<bool, ValueError> {
+        bool::coerce(self)
+    }
+}
+
 impl<'a> Into<Value<'a>> for &Literal<'a> {
     fn into(self) -> Value<'a> {
         match self {
@@ -181,28 +571,276 @@ impl<'a> Into<Value<'a>> for &Literal<'a> {
     }
 }
 
+/// A value formatted with a `PrintFormat`, as returned by `Value::display`.
+pub struct FormattedValue<'v, 'a> {
+    value: &'v Value<'a>,
+    format: &'v PrintFormat,
+}
+
+impl<'v, 'a> Display for FormattedValue<'v, 'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        self.value.fmt_with(f, self.format)
+    }
+}
+
 impl<'a> Display for Value<'a> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        self.fmt_with(f, &PrintFormat::default())
+    }
+}
+
+impl<'a> Value<'a> {
+    /// Formats the value using `format` for nil and booleans, including nested ones.
+    pub fn display<'v>(&'v self, format: &'v PrintFormat) -> FormattedValue<'v, 'a> {
+        FormattedValue { value: self, format }
+    }
+
+    fn fmt_with(&self, f: &mut Formatter<'_>, format: &PrintFormat) -> Result<(), Error> {
+        self.fmt_display(f, format, &mut vec![])
+    }
+
+    /// Like `fmt_debug`, prints arrays and maps that are already being formatted further up
+    /// as `<cycle>`.
+    fn fmt_display(&self, f: &mut Formatter<'_>, format: &PrintFormat, parents: &mut Vec<*const ()>) -> Result<(), Error> {
         match self {
             Value::Float { value } => f.write_str(value.to_string().as_str()),
             Value::Integer { value } => f.write_str(value.to_string().as_str()),
             Value::String { value } => f.write_str(value.as_str()),
-            Value::Boolean { value } => f.write_str(value.to_string().as_str()),
-            Value::Uninitialized => f.write_str("Uninitialized"),
-            Value::Nil => f.write_str("Nil"),
+            Value::StringBuffer(buffer) => f.write_str(buffer.borrow().as_str()),
+            Value::Template(template) => write!(f, "Template({})", template.source),
+            Value::Symbol(symbol) => write!(f, "{}", symbol),
+            Value::Boolean { value } => f.write_str(format.boolean(*value)),
+            Value::Nil => f.write_str(&format.nil),
+            // Reading an uninitialized variable fails first, so this is never meant to show.
+            Value::Uninitialized => Ok(()),
             Value::Function(lf) => f.write_str(format!("{:?}", *lf).as_str()),
+            Value::NativeFunction(nf) => f.write_str(format!("{:?}", *nf).as_str()),
+            Value::NativeMethod(nf, receiver) => {
+                write!(f, "Method {:?} of ", nf)?;
+                receiver.fmt_display(f, format, parents)
+            }
             Value::Class(c) => f.write_str(format!("{}", c.name).as_str()),
             Value::Object(c) => f.write_str(format!("{} instance", c.class_name).as_str()),
             Value::Method(lf, o) => f.write_str(format!("Method {:?} of {}", lf, o.class_name).as_str()),
+            Value::WeakMethod(lf, o) => match o.0.upgrade() {
+                Some(o) => f.write_str(format!("Method {:?} of {}", lf, o.class_name).as_str()),
+                None => f.write_str(format!("Method {:?} of dropped instance", lf).as_str()),
+            },
             Value::Trait(t) => f.write_str(t.name),
             Value::Array(a) => {
+                let pointer = Rc::as_ptr(a) as *const ();
+                if parents.contains(&pointer) {
+                    return f.write_str("<cycle>");
+                }
+                parents.push(pointer);
                 f.write_str("[ ")?;
                 for e in a.borrow().elements.iter() {
-                    f.write_str(format!("{}, ", e).as_str())?;
+                    e.fmt_display(f, format, parents)?;
+                    f.write_str(", ")?;
                 }
+                parents.pop();
                 f.write_str("]")
             }
+            Value::Tuple(elements) => {
+                f.write_str("(")?;
+                for (i, e) in elements.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    e.fmt_display(f, format, parents)?;
+                }
+                if elements.len() == 1 {
+                    f.write_str(",")?;
+                }
+                f.write_str(")")
+            }
+            Value::Map(m) => {
+                let pointer = Rc::as_ptr(m) as *const ();
+                if parents.contains(&pointer) {
+                    return f.write_str("<cycle>");
+                }
+                parents.push(pointer);
+                f.write_str("{ ")?;
+                for (key, value) in m.borrow().iter() {
+                    match key {
+                        MapKey::Nil => f.write_str(&format.nil)?,
+                        MapKey::Boolean(b) => f.write_str(format.boolean(*b))?,
+                        _ => write!(f, "{}", key)?,
+                    };
+                    f.write_str(": ")?;
+                    value.fmt_display(f, format, parents)?;
+                    f.write_str(", ")?;
+                }
+                parents.pop();
+                f.write_str("}")
+            }
             Value::Module(_) => f.write_str("[Module]"),
+            Value::Enum(e) => f.write_str(e.name),
+            Value::EnumVariant(e, variant) => f.write_str(format!("{}.{}", e.name, variant).as_str()),
+            Value::EnumInstance(v) => {
+                f.write_str(format!("{}.{}", v.lox_enum.name, v.variant).as_str())?;
+                if !v.values.is_empty() {
+                    f.write_str("(")?;
+                    for (i, value) in v.values.iter().enumerate() {
+                        if i > 0 {
+                            f.write_str(", ")?;
+                        }
+                        value.fmt_display(f, format, parents)?;
+                    }
+                    f.write_str(")")?;
+                }
+                Ok(())
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::config::PrintFormat;
+    use crate::interpreter::Interpreter;
+    use crate::interpreter::common_test::parse;
+    use crate::value::{LoxMap, MapKey, Value, ValueError};
+    use parser::resolver::Resolver;
+    use parser::types::Pass;
+    use std::cmp::Ordering;
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+    use std::rc::Rc;
+
+    #[test]
+    fn test_debug_cyclic_array() {
+        let array = Value::new_array(vec![Value::Integer { value: 1 }]);
+        if let Value::Array(a) = &array {
+            a.borrow_mut().elements.push(Box::new(array.clone()));
+        }
+        assert_eq!(format!("{:?}", array), "Array([Integer { value: 1 }, <cycle>, ])");
+    }
+
+    #[test]
+    fn test_display_cyclic_map() {
+        let map = Value::new_map(LoxMap::default());
+        let array = Value::new_array(vec![map.clone()]);
+        if let Value::Map(m) = &map {
+            m.borrow_mut().insert(MapKey::String("self".to_owned()), map.clone());
+            m.borrow_mut().insert(MapKey::String("list".to_owned()), array.clone());
+        }
+        assert_eq!(map.to_string(), "{ self: <cycle>, list: [ <cycle>, ], }");
+        assert_eq!(array.to_string(), "[ { self: <cycle>, list: <cycle>, }, ]");
+    }
+
+    #[test]
+    fn test_array_equality_checks_length_first() {
+        let inner = Value::new_array(vec![]);
+        let short = Value::new_array(vec![inner.clone()]);
+        let long = Value::new_array(
+            std::iter::once(inner.clone()).chain((0..100_000).map(|value| Value::Integer { value })).collect()
+        );
+        if let Value::Array(a) = &inner {
+            let _guard = a.borrow_mut();
+            assert_ne!(short, long);
+            assert_ne!(long, short);
+        }
+        assert_eq!(short, Value::new_array(vec![Value::new_array(vec![])]));
+        assert_ne!(Value::String { value: "a".repeat(100_000) }, Value::String { value: "a".to_owned() });
+    }
+
+    #[test]
+    fn test_mixed_number_ordering_agrees_with_equality() {
+        let integer = Value::Integer { value: 1 };
+        let float = Value::Float { value: 1.0 };
+        assert_ne!(integer, float);
+        assert_eq!(integer.partial_cmp(&float), Some(Ordering::Less));
+        assert_eq!(float.partial_cmp(&integer), Some(Ordering::Greater));
+        assert_eq!(integer.partial_cmp(&Value::Float { value: 1.5 }), Some(Ordering::Less));
+        assert_eq!(Value::Float { value: 0.5 }.partial_cmp(&integer), Some(Ordering::Less));
+    }
+
+    #[test]
+    fn test_debug_shared_value_is_not_a_cycle() {
+        let inner = Value::new_array(vec![]);
+        let map = Value::new_map(
+            vec![
+                (MapKey::Integer(1), inner.clone()),
+                (MapKey::Integer(2), inner),
+            ]
+            .into_iter()
+            .collect::<LoxMap>(),
+        );
+        assert_eq!(format!("{:?}", map), "Map({Integer(1): Array([]), Integer(2): Array([]), })");
+    }
+
+    #[test]
+    fn test_object_properties_keep_insertion_order() {
+        let ss = parse(
+            "class Point { init(y, x) { this.y = y; this.x = x; this.label = \"p\"; } norm() {} } \
+             var point = Point(1, 2); point.z = 3; point.y = 4;"
+        );
+        let interpreter = Interpreter::new(&[], "");
+        interpreter.locals.replace(Resolver::new().run(&ss).unwrap());
+        interpreter.run(&ss).unwrap();
+
+        let point = interpreter.state.borrow().get_global("point");
+        if let Some(Value::Object(point)) = point {
+            let names: Vec<&str> = point.properties().into_iter().map(|(name, _)| name).collect();
+            assert_eq!(names, vec!["init", "norm", "y", "x", "label", "z"]);
+            assert_eq!(point.get("y"), Some(Value::Integer { value: 4 }));
+        } else {
+            panic!("Expected an object, got {:?}", point);
+        }
+    }
+
+    #[test]
+    fn test_symbols_with_the_same_name_are_identical() {
+        let interpreter = Interpreter::new(&[], "");
+        let hash = |key: &MapKey| {
+            let mut hasher = DefaultHasher::new();
+            key.hash(&mut hasher);
+            hasher.finish()
+        };
+        let first = interpreter.symbol("tag");
+        let second = interpreter.symbol(&["t", "ag"].concat());
+        let other = interpreter.symbol("other");
+        assert!(Rc::ptr_eq(&first.0, &second.0));
+        assert_eq!(Value::Symbol(first.clone()), Value::Symbol(second.clone()));
+        assert_ne!(Value::Symbol(first.clone()), Value::Symbol(other.clone()));
+        assert_ne!(Value::Symbol(first.clone()), Value::String { value: "tag".to_owned() });
+        assert_eq!(hash(&MapKey::Symbol(first)), hash(&MapKey::Symbol(second)));
+    }
+
+    #[test]
+    fn test_coercions() {
+        let string = || Value::String { value: "a".to_owned() };
+        assert_eq!(Value::Integer { value: 2 }.coerce_integer(), Ok(2));
+        assert_eq!(Value::Float { value: 2.5 }.coerce_integer(), Ok(2));
+        assert_eq!(string().coerce_integer(), Err(ValueError::ExpectingInteger));
+        assert_eq!(Value::Integer { value: 2 }.coerce_number(), Ok(2.0));
+        assert_eq!(Value::Float { value: 2.5 }.coerce_number(), Ok(2.5));
+        assert_eq!(Value::Nil.coerce_number(), Err(ValueError::ExpectingNumber));
+        assert_eq!(string().coerce_string(), Ok("a".to_owned()));
+        assert_eq!(Value::Integer { value: 1 }.coerce_string(), Err(ValueError::ExpectingString));
+        assert_eq!(Value::Boolean { value: true }.coerce_bool(), Ok(true));
+        assert_eq!(Value::Integer { value: 1 }.coerce_bool(), Err(ValueError::ExpectingBoolean));
+    }
+
+    #[test]
+    fn test_display_nil_and_booleans() {
+        let values = Value::Tuple(Rc::new(vec![
+            Value::Nil,
+            Value::Uninitialized,
+            Value::Boolean { value: true },
+            Value::new_map(vec![(MapKey::Nil, Value::Boolean { value: false })].into_iter().collect::<LoxMap>()),
+        ]));
+        assert_eq!(Value::Nil.to_string(), "nil");
+        assert_eq!(Value::Uninitialized.to_string(), "");
+        assert_eq!(Value::Boolean { value: false }.to_string(), "false");
+        assert_eq!(values.to_string(), "(nil, , true, { nil: false, })");
+
+        let format = PrintFormat {
+            nil: "None".to_owned(),
+            true_text: "True".to_owned(),
+            false_text: "False".to_owned(),
+        };
+        assert_eq!(values.display(&format).to_string(), "(None, , True, { None: False, })");
+    }
+}
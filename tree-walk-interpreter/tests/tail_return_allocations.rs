@@ -0,0 +1,49 @@
+//! Counts allocations, so it lives in its own test binary: the counting allocator replaces
+//! the global allocator for everything in the binary.
+use parser::resolver::Resolver;
+use parser::types::{Pass, Statement};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use tree_walk_interpreter::interpreter::{parse_module, Interpreter};
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|a| a.set(a.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations(source: &[Statement]) -> usize {
+    let interpreter = Interpreter::new(&[], "");
+    interpreter.locals.replace(Resolver::new().run(source).unwrap());
+    let before = ALLOCATIONS.with(Cell::get);
+    interpreter.run(source).unwrap();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+#[test]
+fn test_tail_return_skips_return_value_allocation() {
+    let depth = 50;
+    let tail = parse_module(
+        "fun down(n) { if (n == 0) return 0; return down(n - 1); } var result = down(50);",
+        "test",
+    ).unwrap();
+    let grouped = parse_module(
+        "fun down(n) { if (n == 0) return 0; return (down(n - 1)); } var result = down(50);",
+        "test",
+    ).unwrap();
+    assert!(allocations(&tail) + depth <= allocations(&grouped));
+}